@@ -1,19 +1,40 @@
 #[derive(Clone, Debug)]
 pub enum Type {
     Boolean,
+    /// A Unicode scalar value.
+    Char,
     Int(IntType),
     UInt(UIntType),
+    Float(FloatType),
     Function {
         parameters: Vec<Type>,
         return_type: Box<Type>,
     },
+    Array {
+        element_type: Box<Type>,
+        size: u64,
+    },
+    Named(Identifier),
+    Tuple(Vec<Type>),
+    /// A pointer and a length, `str` is `&[u8]`.
+    Slice(Box<Type>),
+    /// `*const T` or `*mut T`, only `*mut` pointers can be written through.
+    Pointer {
+        mutable: bool,
+        pointee: Box<Type>,
+    },
     None,
 }
 
 #[derive(Clone, Debug)]
 pub enum Literal {
     Int(u128, LiteralIntType),
+    Float(f64, LiteralFloatType),
     Boolean(bool),
+    Char(char),
+    Str(String),
+    /// `null` has no type until it is used where a pointer is expected.
+    Null(Option<Type>),
 }
 
 impl Literal {
@@ -24,7 +45,14 @@ impl Literal {
                 LiteralIntType::Unsigned(t) => Type::UInt(t.clone()),
                 LiteralIntType::Unsufixed => Type::None,
             },
+            Literal::Float(_, t) => match t {
+                LiteralFloatType::Suffixed(t) => Type::Float(t.clone()),
+                LiteralFloatType::Unsufixed => Type::Float(FloatType::F64),
+            },
             Literal::Boolean(_) => Type::Boolean,
+            Literal::Char(_) => Type::Char,
+            Literal::Str(_) => Type::Slice(Box::new(Type::UInt(UIntType::U8))),
+            Literal::Null(type_) => type_.clone().unwrap_or(Type::None),
         }
     }
 }
@@ -36,7 +64,29 @@ pub enum LiteralIntType {
     Unsufixed,
 }
 
+/// Unsuffixed float literals are `f64` unless they are used as an `f32`.
 #[derive(Clone, Debug)]
+pub enum LiteralFloatType {
+    Suffixed(FloatType),
+    Unsufixed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FloatType {
+    F32,
+    F64,
+}
+
+impl FloatType {
+    pub fn bits(&self) -> u32 {
+        match self {
+            FloatType::F32 => 32,
+            FloatType::F64 => 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum IntType {
     ISize,
     I8,
@@ -46,7 +96,20 @@ pub enum IntType {
     I128,
 }
 
-#[derive(Clone, Debug)]
+impl IntType {
+    pub fn bits(&self) -> u32 {
+        match self {
+            IntType::ISize => 64,
+            IntType::I8 => 8,
+            IntType::I16 => 16,
+            IntType::I32 => 32,
+            IntType::I64 => 64,
+            IntType::I128 => 128,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum UIntType {
     USize,
     U8,
@@ -56,13 +119,28 @@ pub enum UIntType {
     U128,
 }
 
+impl UIntType {
+    pub fn bits(&self) -> u32 {
+        match self {
+            UIntType::USize => 64,
+            UIntType::U8 => 8,
+            UIntType::U16 => 16,
+            UIntType::U32 => 32,
+            UIntType::U64 => 64,
+            UIntType::U128 => 128,
+        }
+    }
+}
+
 impl PartialEq for Type {
     fn eq(&self, other: &Self) -> bool {
         use Type::*;
         match (self, other) {
             (Boolean, Boolean) => true,
-            (Int(_), Int(_)) => true,
-            (UInt(_), UInt(_)) => true,
+            (Char, Char) => true,
+            (Int(lhs), Int(rhs)) => lhs == rhs,
+            (UInt(lhs), UInt(rhs)) => lhs == rhs,
+            (Float(lhs), Float(rhs)) => lhs == rhs,
             (
                 Function {
                     parameters,
@@ -73,51 +151,280 @@ impl PartialEq for Type {
                     return_type: return_type_rhs,
                 },
             ) => parameters == parameter_rhs && return_type.eq(return_type_rhs),
+            (
+                Array { element_type, size },
+                Array {
+                    element_type: element_type_rhs,
+                    size: size_rhs,
+                },
+            ) => size == size_rhs && element_type.eq(element_type_rhs),
+            (Named(id), Named(id_rhs)) => id == id_rhs,
+            (Tuple(types), Tuple(types_rhs)) => types == types_rhs,
+            (Slice(element_type), Slice(element_type_rhs)) => element_type == element_type_rhs,
+            (
+                Pointer { mutable, pointee },
+                Pointer {
+                    mutable: mutable_rhs,
+                    pointee: pointee_rhs,
+                },
+            ) => mutable == mutable_rhs && pointee == pointee_rhs,
+            (None, None) => true,
             _ => false,
         }
     }
 }
 
+/// Types are written as in the source.
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let list = |types: &[Type]| {
+            types
+                .iter()
+                .map(Type::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Type::Boolean => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Int(int) => match int {
+                IntType::ISize => write!(f, "isize"),
+                IntType::I8 => write!(f, "i8"),
+                IntType::I16 => write!(f, "i16"),
+                IntType::I32 => write!(f, "i32"),
+                IntType::I64 => write!(f, "i64"),
+                IntType::I128 => write!(f, "i128"),
+            },
+            Type::UInt(uint) => match uint {
+                UIntType::USize => write!(f, "usize"),
+                UIntType::U8 => write!(f, "u8"),
+                UIntType::U16 => write!(f, "u16"),
+                UIntType::U32 => write!(f, "u32"),
+                UIntType::U64 => write!(f, "u64"),
+                UIntType::U128 => write!(f, "u128"),
+            },
+            Type::Float(FloatType::F32) => write!(f, "f32"),
+            Type::Float(FloatType::F64) => write!(f, "f64"),
+            Type::Function {
+                parameters,
+                return_type,
+            } => write!(f, "fn({}): {}", list(parameters), return_type),
+            Type::Array { element_type, size } => write!(f, "[{}; {}]", element_type, size),
+            Type::Named(id) => write!(f, "{}", id.value),
+            Type::Tuple(types) if types.len() == 1 => write!(f, "({},)", types[0]),
+            Type::Tuple(types) => write!(f, "({})", list(types)),
+            Type::Slice(element_type) => write!(f, "&[{}]", element_type),
+            Type::Pointer {
+                mutable: true,
+                pointee,
+            } => write!(f, "*mut {}", pointee),
+            Type::Pointer {
+                mutable: false,
+                pointee,
+            } => write!(f, "*const {}", pointee),
+            // The type of unsuffixed integer literals, until they are used.
+            Type::None => write!(f, "{{integer}}"),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ModuleStatement {
     pub id: Identifier,
+    pub public: bool,
     pub statements: Vec<Statement>,
 }
 
+/// `mod foo;`, a module whose statements are in another file.
+#[derive(Clone, Debug)]
+pub struct ModuleDeclarationStatement {
+    pub id: Identifier,
+    pub public: bool,
+    pub span: (usize, usize),
+}
+
 #[derive(Clone, Debug)]
 pub struct ExternFunctionDeclarationStatement {
     pub id: Identifier,
+    pub public: bool,
     pub parameters: Vec<(Identifier, Type)>,
     pub return_type: Type,
 }
 
+/// A function with type parameters is generic, it is typechecked and compiled
+/// once for each list of types it is called with.
 #[derive(Clone, Debug)]
 pub struct FunctionDefinitionStatement {
     pub id: Identifier,
+    pub public: bool,
+    pub type_parameters: Vec<TypeParameter>,
     pub parameters: Vec<(Identifier, Type)>,
     pub return_type: Type,
     pub body: Expression,
 }
 
+/// `T: Add + Mul`, a type parameter only instantiated with types implementing
+/// each trait of `bounds`.
+#[derive(Clone, Debug)]
+pub struct TypeParameter {
+    pub id: Identifier,
+    pub bounds: Vec<Identifier>,
+}
+
+/// `fn f(): T = "symbol";`, a function defined in another object file under
+/// `symbol`, as listed in the interface of a separately compiled program.
+#[derive(Clone, Debug)]
+pub struct FunctionDeclarationStatement {
+    pub id: Identifier,
+    pub public: bool,
+    pub parameters: Vec<(Identifier, Type)>,
+    pub return_type: Type,
+    pub symbol: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct VariableDefinitionStatement {
     pub id: Identifier,
+    pub public: bool,
     pub type_: Type,
     pub initialize_expression: Expression,
 }
 
+/// `let (a, b) = e`, binds each element of a tuple to a variable. Without a
+/// type annotation the tuple type is taken from the initializer.
+#[derive(Clone, Debug)]
+pub struct TupleDefinitionStatement {
+    pub ids: Vec<Identifier>,
+    pub type_: Option<Type>,
+    pub initialize_expression: Expression,
+}
+
 #[derive(Clone, Debug)]
 pub struct ReturnStatement {
     pub expression: Expression,
 }
 
+#[derive(Clone, Debug)]
+pub struct AssignmentStatement {
+    pub target: Expression,
+    pub expression: Expression,
+}
+
+#[derive(Clone, Debug)]
+pub struct StructDefinitionStatement {
+    pub id: Identifier,
+    pub public: bool,
+    pub fields: Vec<(Identifier, Type)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EnumDefinitionStatement {
+    pub id: Identifier,
+    pub public: bool,
+    pub variants: Vec<(Identifier, Vec<Type>)>,
+}
+
+/// `type Meters = u32;`, another name for a type. The two are the same type.
+#[derive(Clone, Debug)]
+pub struct TypeAliasStatement {
+    pub id: Identifier,
+    pub public: bool,
+    pub type_: Type,
+}
+
+/// `newtype UserId = u64;`, a type with the values of another. Values are
+/// made with `UserId(1)` and unwrapped with `id.0`.
+#[derive(Clone, Debug)]
+pub struct NewtypeStatement {
+    pub id: Identifier,
+    pub public: bool,
+    pub type_: Type,
+}
+
+/// `trait Add { fn add(self, other: Self): Self; }`, the methods a type
+/// implementing the trait provides. `Self` is the implementing type.
+#[derive(Clone, Debug)]
+pub struct TraitDefinitionStatement {
+    pub id: Identifier,
+    pub public: bool,
+    pub methods: Vec<TraitMethod>,
+}
+
+/// A method signature of a trait, its first parameter is `self`.
+#[derive(Clone, Debug)]
+pub struct TraitMethod {
+    pub id: Identifier,
+    pub parameters: Vec<(Identifier, Type)>,
+    pub return_type: Type,
+}
+
+/// The name, parameters and return type of a method of an impl.
+pub type MethodSignature<'a> = (&'a Identifier, &'a [(Identifier, Type)], &'a Type);
+
+/// `impl Add for i32 { .. }`, the methods of `trait_` for `type_`, or
+/// `impl Point { .. }`, methods and associated functions of a struct or enum.
+/// The interface of a program declares the methods it defines.
+#[derive(Clone, Debug)]
+pub struct ImplStatement {
+    pub trait_: Option<Identifier>,
+    pub type_: Type,
+    pub functions: Vec<FunctionDefinitionStatement>,
+    pub declarations: Vec<FunctionDeclarationStatement>,
+}
+
+impl ImplStatement {
+    /// The name, parameters and return type of each method, defined or
+    /// declared.
+    pub fn signatures(&self) -> Vec<MethodSignature<'_>> {
+        let defined = self
+            .functions
+            .iter()
+            .map(|f| (&f.id, f.parameters.as_slice(), &f.return_type));
+        let declared = self
+            .declarations
+            .iter()
+            .map(|f| (&f.id, f.parameters.as_slice(), &f.return_type));
+
+        defined.chain(declared).collect()
+    }
+}
+
+/// `use a::b;`, `use a::b as c;` or `use a::*;`, brings items of other
+/// modules into scope.
+#[derive(Clone, Debug)]
+pub struct UseStatement {
+    pub path: Vec<Identifier>,
+    pub import: Import,
+    pub span: (usize, usize),
+}
+
+#[derive(Clone, Debug)]
+pub enum Import {
+    /// The last segment of the path, named after it or after the alias.
+    Item(Option<Identifier>),
+    /// Every item of the module the path names.
+    Glob,
+}
+
 #[derive(Clone, Debug)]
 pub enum Statement {
     Module(Box<ModuleStatement>),
+    ModuleDeclaration(Box<ModuleDeclarationStatement>),
+    Use(Box<UseStatement>),
     ExternFunctionDeclaration(Box<ExternFunctionDeclarationStatement>),
     FunctionDefinition(Box<FunctionDefinitionStatement>),
+    FunctionDeclaration(Box<FunctionDeclarationStatement>),
     VariableDefinition(Box<VariableDefinitionStatement>),
+    TupleDefinition(Box<TupleDefinitionStatement>),
     Return(Box<ReturnStatement>),
+    Assignment(Box<AssignmentStatement>),
+    StructDefinition(Box<StructDefinitionStatement>),
+    EnumDefinition(Box<EnumDefinitionStatement>),
+    TypeAlias(Box<TypeAliasStatement>),
+    Newtype(Box<NewtypeStatement>),
+    TraitDefinition(Box<TraitDefinitionStatement>),
+    Impl(Box<ImplStatement>),
 }
 
 #[derive(Clone, Debug)]
@@ -150,6 +457,35 @@ pub struct IfExpression {
 pub struct CallExpression {
     pub callee: Expression,
     pub arguments: Vec<Expression>,
+    pub span: (usize, usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchExpression {
+    pub scrutinee: Expression,
+    pub arms: Vec<MatchArm>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub patterns: Vec<Pattern>,
+    pub expression: Expression,
+    pub span: (usize, usize),
+}
+
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Identifier),
+    Literal(LiteralPattern),
+    Range(LiteralPattern, LiteralPattern),
+    Variant(Vec<Identifier>, Vec<Pattern>),
+}
+
+#[derive(Clone, Debug)]
+pub struct LiteralPattern {
+    pub negated: bool,
+    pub literal: Literal,
 }
 
 #[derive(Clone, Debug)]
@@ -158,14 +494,95 @@ pub struct AccessExpression {
     pub offset: Expression,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+#[derive(Clone, Debug)]
+pub enum ArrayExpression {
+    List(Vec<Expression>),
+    Repeat { expression: Expression, size: u64 },
+}
+
+#[derive(Clone, Debug)]
+pub struct TupleExpression {
+    pub elements: Vec<Expression>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StructExpression {
+    pub id: Identifier,
+    pub fields: Vec<(Identifier, Expression)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FieldExpression {
+    pub base: Expression,
+    pub field: Identifier,
+}
+
+/// `a::b::c`, a name qualified by the items it is defined in.
+#[derive(Clone, Debug)]
+pub struct PathExpression {
+    pub segments: Vec<Identifier>,
+    pub span: (usize, usize),
+}
+
+/// `f::<T>`, a generic function given its type arguments.
+#[derive(Clone, Debug)]
+pub struct InstantiationExpression {
+    pub expression: Expression,
+    pub type_arguments: Vec<Type>,
+}
+
+/// `|x: i32| x + y`, a function value that keeps copies of the local
+/// variables of its body defined outside it, its `captures`, which are found
+/// by name resolution.
+#[derive(Clone, Debug)]
+pub struct ClosureExpression {
+    pub parameters: Vec<(Identifier, Type)>,
+    pub body: Expression,
+    pub captures: Vec<Identifier>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CastExpression {
+    pub expression: Expression,
+    pub type_: Type,
+}
+
+/// A name. `span` is where it is written, names the compiler makes up have
+/// none. Names are compared by their value only.
+#[derive(Clone)]
 pub struct Identifier {
     pub value: String,
+    pub span: Option<(usize, usize)>,
 }
 
 impl From<String> for Identifier {
     fn from(string: String) -> Self {
-        Identifier { value: string }
+        Identifier {
+            value: string,
+            span: None,
+        }
+    }
+}
+
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for Identifier {}
+
+impl std::hash::Hash for Identifier {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
+impl std::fmt::Debug for Identifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Identifier")
+            .field("value", &self.value)
+            .finish()
     }
 }
 
@@ -175,8 +592,17 @@ pub enum Expression {
     Unary(Box<UnaryExpression>),
     Binary(Box<BinaryExpression>),
     If(Box<IfExpression>),
+    Match(Box<MatchExpression>),
     Call(Box<CallExpression>),
     Access(Box<AccessExpression>),
+    Cast(Box<CastExpression>),
+    Array(Box<ArrayExpression>),
+    Tuple(Box<TupleExpression>),
+    Struct(Box<StructExpression>),
+    Field(Box<FieldExpression>),
+    Path(Box<PathExpression>),
+    Instantiation(Box<InstantiationExpression>),
+    Closure(Box<ClosureExpression>),
     Identifier(Identifier),
     Literal(Literal),
 }
@@ -186,6 +612,9 @@ pub enum UnaryOperator {
     Not,
     Minus,
     Deref,
+    AddressOf,
+    MutAddressOf,
+    BitNot,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -193,6 +622,7 @@ pub enum BinaryOperator {
     GreaterThan,
     GreaterEqualThan,
     Equal,
+    NotEqual,
     LessEqualThan,
     LessThan,
     And,
@@ -201,4 +631,10 @@ pub enum BinaryOperator {
     Minus,
     Multiplication,
     Division,
+    Remainder,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
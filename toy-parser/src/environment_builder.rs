@@ -1,19 +1,143 @@
 use crate::ast::*;
+use crate::generics::{instance_name, method_name, Substitution};
+use crate::resolve::Diagnostic;
+use crate::typecheck::ir::Closure;
 use crate::visitor::{AstVisitor, Visitable};
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 
+/// What a `Type::Named` refers to.
+#[derive(Clone, Debug)]
+pub enum TypeDefinition {
+    Struct(Vec<(Identifier, Type)>),
+    Enum(Vec<(Identifier, Vec<Type>)>),
+    /// A newtype and the type it wraps.
+    Newtype(Type),
+    /// A type parameter of the generic function being checked against its
+    /// bounds, which only has the methods of the traits bounding it.
+    Parameter,
+}
+
 #[derive(Debug)]
 pub struct Environment {
     table: HashMap<Identifier, Type>,
+    assignable: HashSet<Identifier>,
+    /// Functions with the C calling convention, which can be called but are
+    /// not values.
+    externs: HashSet<Identifier>,
+    /// Constants of modules, replaced by their value where they are used.
+    constants: HashMap<Identifier, Expression>,
+    types: HashMap<Identifier, TypeDefinition>,
+    /// Generic functions are not values, they are typechecked from their
+    /// definition for each list of type arguments they are called with.
+    generics: HashMap<Identifier, FunctionDefinitionStatement>,
+    instances: RefCell<Instances>,
+    traits: HashMap<Identifier, TraitDefinitionStatement>,
+    impls: Vec<Impl>,
+    /// The functions of the closures typechecked so far, kept by the
+    /// outermost environment.
+    closures: RefCell<Vec<Closure>>,
+    /// The warnings and the errors that do not stop typechecking found so
+    /// far, kept by the outermost environment.
+    warnings: RefCell<Vec<Diagnostic>>,
+    errors: RefCell<Vec<Diagnostic>>,
+    /// Whether this is the environment of a generic function checked against
+    /// the bounds of its type parameters, whose closures and calls to other
+    /// generic functions are left to its instances.
+    generic_body: bool,
     father: Option<Rc<Self>>,
 }
 
+/// The methods of `trait_` for `type_`, or the inherent methods of `type_`
+/// without a trait, `Self` replaced by `type_`.
+#[derive(Clone, Debug)]
+pub struct Impl {
+    pub trait_: Option<Identifier>,
+    pub type_: Type,
+    pub methods: HashMap<Identifier, Method>,
+}
+
+impl Impl {
+    /// The methods type parameter `type_` has from its bound `trait_`.
+    pub fn bound(type_: &Type, trait_: &TraitDefinitionStatement) -> Self {
+        let substitution = Substitution::self_type(type_);
+        let methods = trait_
+            .methods
+            .iter()
+            .map(|method| {
+                let function = Identifier::from(method_name(type_, Some(&trait_.id), &method.id));
+                let type_ = Type::Function {
+                    parameters: method
+                        .parameters
+                        .iter()
+                        .map(|(_id, type_)| substitution.type_(type_))
+                        .collect(),
+                    return_type: Box::new(substitution.type_(&method.return_type)),
+                };
+
+                let bound = Method {
+                    function,
+                    type_,
+                    receiver: true,
+                };
+
+                (method.id.clone(), bound)
+            })
+            .collect();
+
+        Impl {
+            trait_: Some(trait_.id.clone()),
+            type_: type_.clone(),
+            methods,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Method {
+    /// The function the method is compiled to, named from the module of the
+    /// impl.
+    pub function: Identifier,
+    pub type_: Type,
+    /// Whether it takes `self`, associated functions are only called as
+    /// `Type::function`.
+    pub receiver: bool,
+}
+
+/// How many instances of generic functions may be asked for by one another,
+/// as a generic function calling itself with a larger type would ask for
+/// instances forever.
+pub const INSTANCE_DEPTH_LIMIT: usize = 64;
+
+/// The instances of the generic functions of an environment, named like
+/// `max::<i32>`, with those not typechecked yet and how many instances asked
+/// for them in turn.
+#[derive(Debug, Default)]
+struct Instances {
+    names: HashSet<Identifier>,
+    pending: VecDeque<(Identifier, Vec<Type>, usize)>,
+    /// The depth of the instance being typechecked, 0 outside of instances.
+    depth: usize,
+}
+
 impl Environment {
     pub fn new() -> Self {
         Environment {
             table: HashMap::new(),
+            assignable: HashSet::new(),
+            externs: HashSet::new(),
+            constants: HashMap::new(),
+            types: HashMap::new(),
+            generics: HashMap::new(),
+            instances: RefCell::default(),
+            traits: HashMap::new(),
+            impls: vec![],
+            closures: RefCell::default(),
+            warnings: RefCell::default(),
+            errors: RefCell::default(),
+            generic_body: false,
             father: None,
         }
     }
@@ -21,14 +145,311 @@ impl Environment {
     pub fn put(father: Rc<Self>) -> Self {
         Environment {
             table: HashMap::new(),
+            assignable: HashSet::new(),
+            externs: HashSet::new(),
+            constants: HashMap::new(),
+            types: HashMap::new(),
+            generics: HashMap::new(),
+            instances: RefCell::default(),
+            traits: HashMap::new(),
+            impls: vec![],
+            closures: RefCell::default(),
+            warnings: RefCell::default(),
+            errors: RefCell::default(),
+            generic_body: false,
             father: Some(father),
         }
     }
 
+    /// The environment of the body of a generic function, checked once with
+    /// its type parameters standing for any type satisfying their bounds.
+    pub fn generic_body(father: Rc<Self>) -> Self {
+        Environment {
+            generic_body: true,
+            ..Self::put(father)
+        }
+    }
+
+    fn in_generic_body(&self) -> bool {
+        self.generic_body || self.father.as_ref().is_some_and(|f| f.in_generic_body())
+    }
+
     pub fn insert(&mut self, id: Identifier, type_: Type) {
+        self.assignable.remove(&id);
+        self.externs.remove(&id);
+        self.constants.remove(&id);
         self.table.insert(id, type_);
     }
 
+    /// Inserts a constant of a module and the expression it stands for.
+    pub fn insert_constant(&mut self, id: Identifier, type_: Type, value: Expression) {
+        self.insert(id.clone(), type_);
+        self.constants.insert(id, value);
+    }
+
+    pub fn get_constant(&self, id: &Identifier) -> Option<&Expression> {
+        if self.table.contains_key(id) {
+            self.constants.get(id)
+        } else {
+            match &self.father {
+                Some(f) => f.get_constant(id),
+                None => None,
+            }
+        }
+    }
+
+    /// Inserts a function with the C calling convention.
+    pub fn insert_extern(&mut self, id: Identifier, type_: Type) {
+        self.assignable.remove(&id);
+        self.externs.insert(id.clone());
+        self.table.insert(id, type_);
+    }
+
+    pub fn is_extern(&self, id: &Identifier) -> bool {
+        if self.table.contains_key(id) {
+            self.externs.contains(id)
+        } else {
+            match &self.father {
+                Some(f) => f.is_extern(id),
+                None => false,
+            }
+        }
+    }
+
+    /// Inserts a variable that may be the target of an assignment.
+    pub fn insert_assignable(&mut self, id: Identifier, type_: Type) {
+        self.assignable.insert(id.clone());
+        self.table.insert(id, type_);
+    }
+
+    pub fn is_assignable(&self, id: &Identifier) -> bool {
+        if self.table.contains_key(id) {
+            self.assignable.contains(id)
+        } else {
+            match &self.father {
+                Some(f) => f.is_assignable(id),
+                None => false,
+            }
+        }
+    }
+
+    pub fn insert_type(&mut self, id: Identifier, definition: TypeDefinition) {
+        self.types.insert(id, definition);
+    }
+
+    pub fn get_type(&self, id: &Identifier) -> Option<&TypeDefinition> {
+        match self.types.get(id) {
+            Some(t) => Some(t),
+            None => match &self.father {
+                Some(f) => f.get_type(id),
+                None => None,
+            },
+        }
+    }
+
+    pub fn insert_generic(&mut self, id: Identifier, definition: FunctionDefinitionStatement) {
+        self.generics.insert(id, definition);
+    }
+
+    pub fn get_generic(&self, id: &Identifier) -> Option<&FunctionDefinitionStatement> {
+        match self.generics.get(id) {
+            Some(definition) => Some(definition),
+            None => match &self.father {
+                Some(f) => f.get_generic(id),
+                None => None,
+            },
+        }
+    }
+
+    /// The name of the instance of generic function `id` for `type_arguments`,
+    /// which is typechecked later unless an earlier call asked for it. `span`
+    /// is the call asking for it, blamed when there are too many nested
+    /// instances.
+    pub fn instantiate(
+        &self,
+        id: &Identifier,
+        type_arguments: Vec<Type>,
+        span: (usize, usize),
+    ) -> Identifier {
+        if self.in_generic_body() {
+            return Identifier::from(instance_name(&id.value, &type_arguments));
+        }
+
+        if !self.generics.contains_key(id) {
+            return match &self.father {
+                Some(f) => f.instantiate(id, type_arguments, span),
+                None => panic!("{} is not a generic function", id.value),
+            };
+        }
+
+        let name = Identifier::from(instance_name(&id.value, &type_arguments));
+        let mut instances = self.instances.borrow_mut();
+        let depth = instances.depth + 1;
+
+        if depth > INSTANCE_DEPTH_LIMIT {
+            self.error(Diagnostic {
+                span: Some(span),
+                message: format!(
+                    "instantiating {} recursively exceeds the limit of {} nested instances",
+                    id.value, INSTANCE_DEPTH_LIMIT
+                ),
+            });
+        } else if instances.names.insert(name.clone()) {
+            instances
+                .pending
+                .push_back((id.clone(), type_arguments, depth));
+        }
+
+        name
+    }
+
+    /// A generic function of this environment and the type arguments of an
+    /// instance of it that is not typechecked yet, which is typechecked next.
+    pub fn next_instance(&self) -> Option<(Identifier, Vec<Type>)> {
+        let mut instances = self.instances.borrow_mut();
+        let (id, type_arguments, depth) = instances.pending.pop_front()?;
+        instances.depth = depth;
+
+        Some((id, type_arguments))
+    }
+
+    pub fn insert_trait(&mut self, trait_: TraitDefinitionStatement) {
+        self.traits.insert(trait_.id.clone(), trait_);
+    }
+
+    pub fn get_trait(&self, id: &Identifier) -> Option<&TraitDefinitionStatement> {
+        match self.traits.get(id) {
+            Some(trait_) => Some(trait_),
+            None => match &self.father {
+                Some(f) => f.get_trait(id),
+                None => None,
+            },
+        }
+    }
+
+    pub fn insert_impl(&mut self, impl_: Impl) {
+        self.impls.push(impl_);
+    }
+
+    /// Every impl for `type_`, more than one for the same trait when they
+    /// conflict.
+    pub fn impls_for(&self, type_: &Type) -> Vec<&Impl> {
+        let mut impls: Vec<_> = self.impls.iter().filter(|i| i.type_ == *type_).collect();
+
+        if let Some(f) = &self.father {
+            impls.extend(f.impls_for(type_));
+        }
+
+        impls
+    }
+
+    pub fn implements(&self, type_: &Type, trait_: &Identifier) -> bool {
+        self.impls_for(type_)
+            .iter()
+            .any(|i| i.trait_.as_ref() == Some(trait_))
+    }
+
+    /// The name of the next closure added to the program, `{closure#N}`
+    /// after the closures before it.
+    pub fn next_closure_id(&self) -> Identifier {
+        match &self.father {
+            Some(f) => f.next_closure_id(),
+            None => Identifier::from(format!("{{closure#{}}}", self.closures.borrow().len())),
+        }
+    }
+
+    pub fn insert_closure(&self, closure: Closure) {
+        if self.in_generic_body() {
+            return;
+        }
+
+        match &self.father {
+            Some(f) => f.insert_closure(closure),
+            None => self.closures.borrow_mut().push(closure),
+        }
+    }
+
+    pub fn take_closures(&self) -> Vec<Closure> {
+        match &self.father {
+            Some(f) => f.take_closures(),
+            None => self.closures.take(),
+        }
+    }
+
+    /// Code of generic functions is typechecked once for each instance, so
+    /// the same warning is only kept once.
+    pub fn warn(&self, warning: Diagnostic) {
+        match &self.father {
+            Some(f) => f.warn(warning),
+            None => {
+                let mut warnings = self.warnings.borrow_mut();
+                if !warnings
+                    .iter()
+                    .any(|w| w.span == warning.span && w.message == warning.message)
+                {
+                    warnings.push(warning);
+                }
+            }
+        }
+    }
+
+    pub fn take_warnings(&self) -> Vec<Diagnostic> {
+        match &self.father {
+            Some(f) => f.take_warnings(),
+            None => self.warnings.take(),
+        }
+    }
+
+    pub fn error(&self, error: Diagnostic) {
+        match &self.father {
+            Some(f) => f.error(error),
+            None => self.errors.borrow_mut().push(error),
+        }
+    }
+
+    pub fn take_errors(&self) -> Vec<Diagnostic> {
+        match &self.father {
+            Some(f) => f.take_errors(),
+            None => self.errors.take(),
+        }
+    }
+
+    /// Makes the items of a nested module visible as `module::item`, its
+    /// types are already named after the module they are defined in.
+    pub fn insert_module(&mut self, module: &Identifier, module_env: Environment) {
+        for (id, type_) in module_env.table {
+            let qualified = Identifier::from(format!("{}::{}", module.value, id.value));
+
+            if module_env.assignable.contains(&id) {
+                self.insert_assignable(qualified, type_);
+            } else if let Some(value) = module_env.constants.get(&id) {
+                self.insert_constant(qualified, type_, value.clone());
+            } else if module_env.externs.contains(&id) {
+                self.insert_extern(qualified, type_);
+            } else {
+                self.insert(qualified, type_);
+            }
+        }
+
+        self.types.extend(module_env.types);
+
+        for (id, definition) in module_env.generics {
+            let qualified = Identifier::from(format!("{}::{}", module.value, id.value));
+
+            self.insert_generic(qualified, definition);
+        }
+
+        self.traits.extend(module_env.traits);
+
+        for mut impl_ in module_env.impls {
+            for method in impl_.methods.values_mut() {
+                method.function.value = format!("{}::{}", module.value, method.function.value);
+            }
+
+            self.insert_impl(impl_);
+        }
+    }
+
     pub fn get(&self, id: &Identifier) -> Option<&Type> {
         match self.table.get(id) {
             Some(t) => Some(t),
@@ -40,6 +461,12 @@ impl Environment {
     }
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct EnvironmentBuilder {}
 
 impl AstVisitor<Environment, Environment> for EnvironmentBuilder {
@@ -49,11 +476,27 @@ impl AstVisitor<Environment, Environment> for EnvironmentBuilder {
     fn visit_statement(&mut self, env: Self::Environment, s: &Statement) -> Self::Return {
         use Statement::*;
         match s {
-            Module(m) => m.accept(env, self),
+            Module(m) => {
+                let module_env = m.accept(Environment::new(), self);
+                let mut env = env;
+                env.insert_module(&m.id, module_env);
+                env
+            }
+            ModuleDeclaration(m) => m.accept(env, self),
+            Use(u) => u.accept(env, self),
             ExternFunctionDeclaration(s) => s.accept(env, self),
             FunctionDefinition(s) => s.accept(env, self),
+            FunctionDeclaration(s) => s.accept(env, self),
             VariableDefinition(s) => s.accept(env, self),
             Return(s) => s.accept(env, self),
+            Assignment(s) => s.accept(env, self),
+            TupleDefinition(s) => s.accept(env, self),
+            EnumDefinition(s) => s.accept(env, self),
+            TypeAlias(s) => s.accept(env, self),
+            Newtype(s) => s.accept(env, self),
+            StructDefinition(s) => s.accept(env, self),
+            TraitDefinition(s) => s.accept(env, self),
+            Impl(s) => s.accept(env, self),
         }
     }
 
@@ -71,13 +514,28 @@ impl AstVisitor<Environment, Environment> for EnvironmentBuilder {
         env
     }
 
+    /// Only modules loaded from their file have items.
+    fn visit_module_declaration_statement(
+        &mut self,
+        env: Self::Environment,
+        _m: &ModuleDeclarationStatement,
+    ) -> Self::Return {
+        env
+    }
+
+    /// Imports are resolved before typechecking, every item is in the
+    /// environment under its path from the root module.
+    fn visit_use_statement(&mut self, env: Self::Environment, _use: &UseStatement) -> Self::Return {
+        env
+    }
+
     fn visit_extern_function_declaration_statement(
         &mut self,
         env: Self::Environment,
         f: &ExternFunctionDeclarationStatement,
     ) -> Self::Return {
         let mut env = env;
-        env.insert(
+        env.insert_extern(
             f.id.clone(),
             Type::Function {
                 parameters: f
@@ -97,6 +555,32 @@ impl AstVisitor<Environment, Environment> for EnvironmentBuilder {
         function: &FunctionDefinitionStatement,
     ) -> Self::Return {
         let mut env = env;
+
+        if !function.type_parameters.is_empty() {
+            env.insert_generic(function.id.clone(), function.clone());
+            return env;
+        }
+
+        env.insert(
+            function.id.clone(),
+            Type::Function {
+                parameters: function
+                    .parameters
+                    .iter()
+                    .map(|(_id, type_)| type_.clone())
+                    .collect(),
+                return_type: Box::new(function.return_type.clone()),
+            },
+        );
+        env
+    }
+
+    fn visit_function_declaration_statement(
+        &mut self,
+        env: Self::Environment,
+        function: &FunctionDeclarationStatement,
+    ) -> Self::Return {
+        let mut env = env;
         env.insert(
             function.id.clone(),
             Type::Function {
@@ -117,15 +601,138 @@ impl AstVisitor<Environment, Environment> for EnvironmentBuilder {
         variable: &VariableDefinitionStatement,
     ) -> Self::Return {
         let mut env = env;
-        env.insert(variable.id.clone(), variable.type_.clone());
+        env.insert_constant(
+            variable.id.clone(),
+            variable.type_.clone(),
+            variable.initialize_expression.clone(),
+        );
         env
     }
 
     fn visit_return_statement(
         &mut self,
         env: Environment,
-        return_statement: &ReturnStatement,
+        _return_statement: &ReturnStatement,
+    ) -> Self::Return {
+        env
+    }
+
+    fn visit_assignment_statement(
+        &mut self,
+        env: Environment,
+        _assignment: &AssignmentStatement,
+    ) -> Self::Return {
+        env
+    }
+
+    fn visit_struct_definition_statement(
+        &mut self,
+        env: Self::Environment,
+        struct_: &StructDefinitionStatement,
+    ) -> Self::Return {
+        let mut env = env;
+        env.insert_type(
+            struct_.id.clone(),
+            TypeDefinition::Struct(struct_.fields.clone()),
+        );
+        env
+    }
+
+    fn visit_enum_definition_statement(
+        &mut self,
+        env: Self::Environment,
+        enum_: &EnumDefinitionStatement,
+    ) -> Self::Return {
+        let mut env = env;
+        env.insert_type(
+            enum_.id.clone(),
+            TypeDefinition::Enum(enum_.variants.clone()),
+        );
+        env
+    }
+
+    /// Aliases are replaced by their type before typechecking.
+    fn visit_type_alias_statement(
+        &mut self,
+        env: Self::Environment,
+        _alias: &TypeAliasStatement,
+    ) -> Self::Return {
+        env
+    }
+
+    fn visit_newtype_statement(
+        &mut self,
+        env: Self::Environment,
+        newtype: &NewtypeStatement,
+    ) -> Self::Return {
+        let mut env = env;
+        env.insert_type(
+            newtype.id.clone(),
+            TypeDefinition::Newtype(newtype.type_.clone()),
+        );
+        env
+    }
+
+    fn visit_trait_definition_statement(
+        &mut self,
+        env: Self::Environment,
+        trait_: &TraitDefinitionStatement,
     ) -> Self::Return {
+        let mut env = env;
+        env.insert_trait(trait_.clone());
+        env
+    }
+
+    fn visit_impl_statement(
+        &mut self,
+        env: Self::Environment,
+        impl_: &ImplStatement,
+    ) -> Self::Return {
+        let substitution = Substitution::self_type(&impl_.type_);
+        let methods = impl_
+            .signatures()
+            .into_iter()
+            .map(|(id, parameters, return_type)| {
+                let function =
+                    Identifier::from(method_name(&impl_.type_, impl_.trait_.as_ref(), id));
+                let type_ = Type::Function {
+                    parameters: parameters
+                        .iter()
+                        .map(|(_id, type_)| substitution.type_(type_))
+                        .collect(),
+                    return_type: Box::new(substitution.type_(return_type)),
+                };
+
+                let method = Method {
+                    function,
+                    type_,
+                    receiver: matches!(parameters.first(), Some((id, _)) if id.value == "self"),
+                };
+
+                (id.clone(), method)
+            })
+            .collect();
+
+        let mut env = env;
+        env.insert_impl(Impl {
+            trait_: impl_.trait_.clone(),
+            type_: impl_.type_.clone(),
+            methods,
+        });
+        env
+    }
+
+    fn visit_tuple_definition_statement(
+        &mut self,
+        env: Self::Environment,
+        tuple: &TupleDefinitionStatement,
+    ) -> Self::Return {
+        let mut env = env;
+        if let Some(Type::Tuple(types)) = &tuple.type_ {
+            for (id, type_) in tuple.ids.iter().zip(types) {
+                env.insert_assignable(id.clone(), type_.clone());
+            }
+        }
         env
     }
 
@@ -171,6 +778,62 @@ impl AstVisitor<Environment, Environment> for EnvironmentBuilder {
     ) -> Self::Return {
         todo!()
     }
+    fn visit_cast_expression(
+        &mut self,
+        _env: Self::Environment,
+        _cast: &CastExpression,
+    ) -> Self::Return {
+        todo!()
+    }
+    fn visit_array_expression(
+        &mut self,
+        _env: Self::Environment,
+        _array: &ArrayExpression,
+    ) -> Self::Return {
+        todo!()
+    }
+    fn visit_struct_expression(
+        &mut self,
+        _env: Self::Environment,
+        _struct_: &StructExpression,
+    ) -> Self::Return {
+        todo!()
+    }
+    fn visit_field_expression(
+        &mut self,
+        _env: Self::Environment,
+        _field: &FieldExpression,
+    ) -> Self::Return {
+        todo!()
+    }
+    fn visit_path_expression(
+        &mut self,
+        _env: Self::Environment,
+        _path: &PathExpression,
+    ) -> Self::Return {
+        todo!()
+    }
+    fn visit_instantiation_expression(
+        &mut self,
+        _env: Self::Environment,
+        _instantiation: &InstantiationExpression,
+    ) -> Self::Return {
+        todo!()
+    }
+    fn visit_tuple_expression(
+        &mut self,
+        _env: Self::Environment,
+        _tuple: &TupleExpression,
+    ) -> Self::Return {
+        todo!()
+    }
+    fn visit_closure_expression(
+        &mut self,
+        _env: Self::Environment,
+        _closure: &ClosureExpression,
+    ) -> Self::Return {
+        todo!()
+    }
     fn visit_identifier(&mut self, _env: Self::Environment, _id: &Identifier) -> Self::Return {
         todo!()
     }
@@ -181,6 +844,13 @@ impl AstVisitor<Environment, Environment> for EnvironmentBuilder {
     ) -> Environment {
         todo!()
     }
+    fn visit_match_expression(
+        &mut self,
+        _env: Environment,
+        _match_expression: &MatchExpression,
+    ) -> Environment {
+        todo!()
+    }
 }
 
 #[cfg(test)]
@@ -193,16 +863,16 @@ mod tests {
         let code = "
         let x : i32 = 5;
 
-        extern fn putc();
+        extern fn putc(): bool;
 
         extern fn extern_sum(lhs: i32, rhs: i32) : i32;
 
-        fn nop() => {};
+        fn nop(): bool => {};
 
-        fn sum(lhs: i32, rhs: i32) => lhs + rhs;
+        fn sum(lhs: i32, rhs: i32): i32 => lhs + rhs;
         ";
 
-        let module = ModuleParser::new().parse(code).unwrap();
+        let module = ModuleParser::new().parse(0, code).unwrap();
 
         let mut env_builder = super::EnvironmentBuilder {};
 
@@ -212,14 +882,14 @@ mod tests {
 
         assert_eq!(
             env.get(&Identifier::from("x".to_string())),
-            Some(&Type::I32)
+            Some(&Type::Int(IntType::I32))
         );
 
         assert_eq!(
             env.get(&Identifier::from("nop".to_string())),
             Some(&Type::Function {
                 parameters: vec![],
-                return_type: Box::new(Type::Void),
+                return_type: Box::new(Type::Boolean),
             })
         );
 
@@ -227,15 +897,15 @@ mod tests {
             env.get(&Identifier::from("putc".to_string())),
             Some(&Type::Function {
                 parameters: vec![],
-                return_type: Box::new(Type::Void),
+                return_type: Box::new(Type::Boolean),
             })
         );
 
         assert_eq!(
             env.get(&Identifier::from("extern_sum".to_string())),
             Some(&Type::Function {
-                parameters: vec![Type::I32, Type::I32],
-                return_type: Box::new(Type::I32),
+                parameters: vec![Type::Int(IntType::I32), Type::Int(IntType::I32)],
+                return_type: Box::new(Type::Int(IntType::I32)),
             })
         );
     }
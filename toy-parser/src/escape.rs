@@ -0,0 +1,207 @@
+/// An invalid escape sequence, `span` is the byte range of the escape
+/// inside the source the literal was taken from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscapeError {
+    pub span: (usize, usize),
+    pub message: String,
+}
+
+/// Decodes the escape sequences of the contents of a string or character
+/// literal. `offset` is the position of `literal` in the source and is only
+/// used to report errors.
+///
+/// Supports `\n`, `\r`, `\t`, `\\`, `\0`, `\'`, `\"`, `\xHH` up to `\x7F`
+/// and `\u{H}` with one to six hex digits naming a Unicode scalar value.
+pub fn unescape(literal: &str, offset: usize) -> Result<String, EscapeError> {
+    let mut value = String::with_capacity(literal.len());
+    let mut chars = literal.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+
+        let error = |end: usize, message: String| EscapeError {
+            span: (offset + start, offset + end),
+            message,
+        };
+
+        let (escape_start, escape) = match chars.next() {
+            Some(escape) => escape,
+            None => return Err(error(literal.len(), "unterminated escape".to_string())),
+        };
+        let escape_end = escape_start + escape.len_utf8();
+
+        let c = match escape {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '\\' => '\\',
+            '0' => '\0',
+            '\'' => '\'',
+            '"' => '"',
+            'x' => {
+                let digits = literal.get(escape_end..escape_end + 2).unwrap_or("");
+                let end = escape_end + digits.len();
+
+                let byte = match u8::from_str_radix(digits, 16) {
+                    Ok(byte) if digits.len() == 2 && is_hex(digits) => byte,
+                    _ => {
+                        return Err(error(
+                            end,
+                            "\\x must be followed by two hex digits".to_string(),
+                        ))
+                    }
+                };
+
+                if byte > 0x7f {
+                    return Err(error(
+                        end,
+                        format!("\\x{} is not ASCII, use \\u{{{:x}}} instead", digits, byte),
+                    ));
+                }
+
+                chars.nth(1);
+                byte as char
+            }
+            'u' => {
+                let rest = &literal[escape_end..];
+                let close = match (rest.starts_with('{'), rest.find('}')) {
+                    (true, Some(close)) => close,
+                    _ => {
+                        return Err(error(
+                            escape_end,
+                            "\\u must be followed by {hex digits}".to_string(),
+                        ))
+                    }
+                };
+                let digits = &rest[1..close];
+                let end = escape_end + close + 1;
+
+                let scalar = match u32::from_str_radix(digits, 16) {
+                    Ok(scalar) if digits.len() <= 6 && is_hex(digits) => scalar,
+                    _ => {
+                        return Err(error(
+                            end,
+                            format!("invalid unicode escape \\u{{{}}}", digits),
+                        ))
+                    }
+                };
+
+                let c = match std::char::from_u32(scalar) {
+                    Some(c) => c,
+                    None if (0xd800..=0xdfff).contains(&scalar) => {
+                        return Err(error(
+                            end,
+                            format!(
+                                "\\u{{{}}} is a surrogate, not a unicode scalar value",
+                                digits
+                            ),
+                        ))
+                    }
+                    None => {
+                        return Err(error(
+                            end,
+                            format!("\\u{{{}}} is out of the unicode range", digits),
+                        ))
+                    }
+                };
+
+                while let Some((index, _)) = chars.peek() {
+                    if *index >= end {
+                        break;
+                    }
+                    chars.next();
+                }
+                c
+            }
+            other => {
+                return Err(error(
+                    escape_end,
+                    format!("unknown escape \\{}", other.escape_debug()),
+                ))
+            }
+        };
+
+        value.push(c);
+    }
+
+    Ok(value)
+}
+
+/// Decodes the contents of a character literal, which must be exactly one
+/// character once escapes are resolved.
+pub fn unescape_char(literal: &str, offset: usize) -> Result<char, EscapeError> {
+    let value = unescape(literal, offset)?;
+    let mut chars = value.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(EscapeError {
+            span: (offset, offset + literal.len()),
+            message: "character literals must contain exactly one character".to_string(),
+        }),
+    }
+}
+
+/// Decodes the digits of an integer literal in `radix`, skipping `_`
+/// separators. `offset` is the position of `digits` in the source and is
+/// only used to report literals that do not fit in 128 bits.
+pub fn int_value(digits: &str, radix: u32, offset: usize) -> Result<u128, EscapeError> {
+    u128::from_str_radix(&digits.replace("_", ""), radix).map_err(|_| EscapeError {
+        span: (offset, offset + digits.len()),
+        message: "integer literal is too large".to_string(),
+    })
+}
+
+/// Decodes the digits of the size of an array type or of an array repeat
+/// expression, which must fit in 64 bits.
+pub fn array_size(digits: &str, offset: usize) -> Result<u64, EscapeError> {
+    digits.replace("_", "").parse().map_err(|_| EscapeError {
+        span: (offset, offset + digits.len()),
+        message: "array size is too large".to_string(),
+    })
+}
+
+fn is_hex(digits: &str) -> bool {
+    digits.bytes().all(|digit| digit.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            unescape(r#"a\tb\n\\\"\'\0\x41\u{e9}\u{1F600}"#, 0),
+            Ok("a\tb\n\\\"'\0A\u{e9}\u{1F600}".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_escapes_have_spans() {
+        let error = unescape(r"ab\q", 10).unwrap_err();
+        assert_eq!(error.span, (12, 14));
+        assert_eq!(error.message, "unknown escape \\q");
+
+        let error = unescape(r"\x+1", 0).unwrap_err();
+        assert_eq!(error.span, (0, 4));
+
+        let error = unescape(r"\xff", 0).unwrap_err();
+        assert_eq!(error.span, (0, 4));
+
+        let error = unescape(r"x\u{d800}", 0).unwrap_err();
+        assert_eq!(error.span, (1, 9));
+        assert!(error.message.contains("surrogate"));
+    }
+
+    #[test]
+    fn characters() {
+        assert_eq!(unescape_char("a", 0), Ok('a'));
+        assert_eq!(unescape_char(r"\u{1F600}", 0), Ok('\u{1F600}'));
+        assert_eq!(unescape_char("ab", 4).unwrap_err().span, (4, 6));
+        assert_eq!(unescape_char(r"\u{dfff}", 1).unwrap_err().span, (1, 9));
+    }
+}
@@ -0,0 +1,402 @@
+//! Generic functions, `fn max<T>(a: T, b: T): T`. They are monomorphized: a
+//! call to `max::<i32>`, or to `max` with `i32` arguments, asks for the
+//! instance of `max` with `T` replaced by `i32`, which is typechecked and
+//! compiled as a function of its own named `max::<i32>`. The body is also
+//! typechecked once on its own, where a type parameter only has the methods
+//! of the traits bounding it, so it can not rely on what its instances have.
+//!
+//! The methods of impls are functions too, `Self` replaced by the type of the
+//! impl, so calling a method is a direct call to one of them.
+//!
+//! Type aliases are replaced by their target the same way, in the whole
+//! program before it is typechecked.
+
+use crate::ast::*;
+
+/// The name of the instance of generic function `id` for `type_arguments`.
+pub fn instance_name(id: &str, type_arguments: &[Type]) -> String {
+    let type_arguments: Vec<_> = type_arguments.iter().map(Type::to_string).collect();
+
+    format!("{}::<{}>", id, type_arguments.join(", "))
+}
+
+/// The name of the function method `method` of `impl trait_ for type_` is
+/// compiled to, as in `<i32 as Add>::add`, or `<Point>::len` without a trait.
+pub fn method_name(type_: &Type, trait_: Option<&Identifier>, method: &Identifier) -> String {
+    match trait_ {
+        Some(trait_) => format!("<{} as {}>::{}", type_, trait_.value, method.value),
+        None => format!("<{}>::{}", type_, method.value),
+    }
+}
+
+/// The type arguments of a call to `definition` with arguments of types
+/// `arguments`. Type parameters only given unsuffixed integer literals are
+/// `i32`.
+pub fn infer(definition: &FunctionDefinitionStatement, arguments: &[Type]) -> Vec<Type> {
+    let mut inference = Inference {
+        type_parameters: &definition.type_parameters,
+        bindings: vec![None; definition.type_parameters.len()],
+        integer_literals: vec![false; definition.type_parameters.len()],
+    };
+
+    for ((_id, parameter), argument) in definition.parameters.iter().zip(arguments) {
+        inference.bind(parameter, argument);
+    }
+
+    definition
+        .type_parameters
+        .iter()
+        .zip(inference.bindings)
+        .zip(inference.integer_literals)
+        .map(|((parameter, binding), integer_literal)| match binding {
+            Some(type_) => type_,
+            None if integer_literal => Type::Int(IntType::I32),
+            None => panic!(
+                "can not infer type parameter {} of {}, call it as {}::<...>",
+                parameter.id.value, definition.id.value, definition.id.value
+            ),
+        })
+        .collect()
+}
+
+struct Inference<'a> {
+    type_parameters: &'a [TypeParameter],
+    bindings: Vec<Option<Type>>,
+    /// Type parameters given an unsuffixed integer literal.
+    integer_literals: Vec<bool>,
+}
+
+impl Inference<'_> {
+    /// Binds the type parameters in `parameter` to the matching types of
+    /// `argument`, the first argument binding a type parameter wins.
+    fn bind(&mut self, parameter: &Type, argument: &Type) {
+        match (parameter, argument) {
+            (Type::Named(id), _) => {
+                let index = match self.type_parameters.iter().position(|t| t.id == *id) {
+                    Some(index) => index,
+                    None => return,
+                };
+
+                match argument {
+                    Type::None => self.integer_literals[index] = true,
+                    _ if self.bindings[index].is_none() => {
+                        self.bindings[index] = Some(argument.clone())
+                    }
+                    _ => {}
+                }
+            }
+            (
+                Type::Array { element_type, .. },
+                Type::Array {
+                    element_type: argument,
+                    ..
+                },
+            )
+            | (Type::Slice(element_type), Type::Slice(argument))
+            | (
+                Type::Pointer {
+                    pointee: element_type,
+                    ..
+                },
+                Type::Pointer {
+                    pointee: argument, ..
+                },
+            ) => self.bind(element_type, argument),
+            (Type::Tuple(types), Type::Tuple(arguments)) => {
+                for (type_, argument) in types.iter().zip(arguments) {
+                    self.bind(type_, argument);
+                }
+            }
+            (
+                Type::Function {
+                    parameters,
+                    return_type,
+                },
+                Type::Function {
+                    parameters: arguments,
+                    return_type: argument,
+                },
+            ) => {
+                for (parameter, argument) in parameters.iter().zip(arguments) {
+                    self.bind(parameter, argument);
+                }
+                self.bind(return_type, argument);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replaces the type parameters of a generic function by its type arguments,
+/// `Self` by the type of an impl, or type aliases by their target.
+pub struct Substitution {
+    types: Vec<(Identifier, Type)>,
+}
+
+impl Substitution {
+    pub fn new(type_parameters: &[TypeParameter], type_arguments: &[Type]) -> Self {
+        Substitution {
+            types: type_parameters
+                .iter()
+                .map(|parameter| parameter.id.clone())
+                .zip(type_arguments.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// Replaces `Self` in the methods of an impl for `type_`.
+    pub fn self_type(type_: &Type) -> Self {
+        Substitution {
+            types: vec![(Identifier::from("Self".to_string()), type_.clone())],
+        }
+    }
+
+    /// Replaces aliases by their target, which must name no alias.
+    pub fn aliases(aliases: Vec<(Identifier, Type)>) -> Self {
+        Substitution { types: aliases }
+    }
+
+    /// `definition` with the types substituted, without type parameters.
+    pub fn function(
+        &self,
+        definition: &FunctionDefinitionStatement,
+    ) -> FunctionDefinitionStatement {
+        let mut instance = definition.clone();
+
+        instance.type_parameters = vec![];
+        self.function_types(&mut instance);
+
+        instance
+    }
+
+    /// Substitutes the types of the items of `module` and of the modules
+    /// nested in it.
+    pub fn module(&self, module: &mut ModuleStatement) {
+        for statement in &mut module.statements {
+            match statement {
+                Statement::Module(m) => self.module(m),
+                Statement::ExternFunctionDeclaration(function) => {
+                    for (_id, type_) in &mut function.parameters {
+                        *type_ = self.type_(type_);
+                    }
+                    function.return_type = self.type_(&function.return_type);
+                }
+                Statement::FunctionDeclaration(function) => {
+                    for (_id, type_) in &mut function.parameters {
+                        *type_ = self.type_(type_);
+                    }
+                    function.return_type = self.type_(&function.return_type);
+                }
+                // Type parameters hide the types of the same name.
+                Statement::FunctionDefinition(function) => Substitution {
+                    types: self
+                        .types
+                        .iter()
+                        .filter(|(id, _)| !function.type_parameters.iter().any(|p| p.id == *id))
+                        .cloned()
+                        .collect(),
+                }
+                .function_types(function),
+                Statement::StructDefinition(struct_) => {
+                    for (_id, type_) in &mut struct_.fields {
+                        *type_ = self.type_(type_);
+                    }
+                }
+                Statement::EnumDefinition(enum_) => {
+                    for type_ in enum_.variants.iter_mut().flat_map(|(_id, types)| types) {
+                        *type_ = self.type_(type_);
+                    }
+                }
+                Statement::TypeAlias(alias) => alias.type_ = self.type_(&alias.type_),
+                Statement::Newtype(newtype) => newtype.type_ = self.type_(&newtype.type_),
+                Statement::TraitDefinition(trait_) => {
+                    for method in &mut trait_.methods {
+                        for (_id, type_) in &mut method.parameters {
+                            *type_ = self.type_(type_);
+                        }
+                        method.return_type = self.type_(&method.return_type);
+                    }
+                }
+                Statement::Impl(impl_) => {
+                    impl_.type_ = self.type_(&impl_.type_);
+                    for function in &mut impl_.functions {
+                        self.function_types(function);
+                    }
+                    for function in &mut impl_.declarations {
+                        for (_id, type_) in &mut function.parameters {
+                            *type_ = self.type_(type_);
+                        }
+                        function.return_type = self.type_(&function.return_type);
+                    }
+                }
+                Statement::ModuleDeclaration(_) | Statement::Use(_) => {}
+                _ => self.statement(statement),
+            }
+        }
+    }
+
+    fn function_types(&self, function: &mut FunctionDefinitionStatement) {
+        for (_id, type_) in &mut function.parameters {
+            *type_ = self.type_(type_);
+        }
+        function.return_type = self.type_(&function.return_type);
+        self.expression(&mut function.body);
+    }
+
+    pub fn type_(&self, type_: &Type) -> Type {
+        match type_ {
+            Type::Named(id) => match self.types.iter().find(|(parameter, _)| parameter == id) {
+                Some((_, argument)) => argument.clone(),
+                None => type_.clone(),
+            },
+            Type::Function {
+                parameters,
+                return_type,
+            } => Type::Function {
+                parameters: parameters.iter().map(|type_| self.type_(type_)).collect(),
+                return_type: Box::new(self.type_(return_type)),
+            },
+            Type::Array { element_type, size } => Type::Array {
+                element_type: Box::new(self.type_(element_type)),
+                size: *size,
+            },
+            Type::Tuple(types) => {
+                Type::Tuple(types.iter().map(|type_| self.type_(type_)).collect())
+            }
+            Type::Slice(element_type) => Type::Slice(Box::new(self.type_(element_type))),
+            Type::Pointer { mutable, pointee } => Type::Pointer {
+                mutable: *mutable,
+                pointee: Box::new(self.type_(pointee)),
+            },
+            Type::Boolean
+            | Type::Char
+            | Type::Int(_)
+            | Type::UInt(_)
+            | Type::Float(_)
+            | Type::None => type_.clone(),
+        }
+    }
+
+    fn statement(&self, statement: &mut Statement) {
+        match statement {
+            Statement::VariableDefinition(variable) => {
+                variable.type_ = self.type_(&variable.type_);
+                self.expression(&mut variable.initialize_expression);
+            }
+            Statement::TupleDefinition(tuple) => {
+                if let Some(type_) = &mut tuple.type_ {
+                    *type_ = self.type_(type_);
+                }
+                self.expression(&mut tuple.initialize_expression);
+            }
+            Statement::Return(return_) => self.expression(&mut return_.expression),
+            Statement::Assignment(assignment) => {
+                self.expression(&mut assignment.target);
+                self.expression(&mut assignment.expression);
+            }
+            // Typechecking rejects items inside blocks.
+            _ => {}
+        }
+    }
+
+    fn expression(&self, expression: &mut Expression) {
+        match expression {
+            Expression::Block(block) => {
+                for statement in &mut block.statements {
+                    self.statement(statement);
+                }
+                if let Some(expression) = &mut block.return_expression {
+                    self.expression(expression);
+                }
+            }
+            Expression::Unary(unary) => self.expression(&mut unary.expression),
+            Expression::Binary(binary) => {
+                self.expression(&mut binary.left);
+                self.expression(&mut binary.right);
+            }
+            Expression::If(if_) => {
+                self.expression(&mut if_.condition);
+                self.expression(&mut if_.true_path);
+                self.expression(&mut if_.false_path);
+            }
+            Expression::Match(match_) => {
+                self.expression(&mut match_.scrutinee);
+                for arm in &mut match_.arms {
+                    for pattern in &mut arm.patterns {
+                        self.pattern(pattern);
+                    }
+                    self.expression(&mut arm.expression);
+                }
+            }
+            Expression::Call(call) => {
+                self.expression(&mut call.callee);
+                for argument in &mut call.arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Access(access) => {
+                self.expression(&mut access.base);
+                self.expression(&mut access.offset);
+            }
+            Expression::Cast(cast) => {
+                self.expression(&mut cast.expression);
+                cast.type_ = self.type_(&cast.type_);
+            }
+            Expression::Array(array) => match &mut **array {
+                ArrayExpression::List(elements) => {
+                    for element in elements {
+                        self.expression(element);
+                    }
+                }
+                ArrayExpression::Repeat { expression, .. } => self.expression(expression),
+            },
+            Expression::Tuple(tuple) => {
+                for element in &mut tuple.elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Struct(struct_) => {
+                // `T { .. }` builds the struct `T` stands for.
+                if let Type::Named(id) = self.type_(&Type::Named(struct_.id.clone())) {
+                    struct_.id = id;
+                }
+                for (_id, expression) in &mut struct_.fields {
+                    self.expression(expression);
+                }
+            }
+            Expression::Field(field) => self.expression(&mut field.base),
+            Expression::Instantiation(instantiation) => {
+                self.expression(&mut instantiation.expression);
+                for type_ in &mut instantiation.type_arguments {
+                    *type_ = self.type_(type_);
+                }
+            }
+            Expression::Closure(closure) => {
+                for (_id, type_) in &mut closure.parameters {
+                    *type_ = self.type_(type_);
+                }
+                self.expression(&mut closure.body);
+            }
+            Expression::Path(path) => self.member_path(&mut path.segments),
+            Expression::Identifier(_) | Expression::Literal(_) => {}
+        }
+    }
+
+    fn pattern(&self, pattern: &mut Pattern) {
+        if let Pattern::Variant(path, fields) = pattern {
+            self.member_path(path);
+            for field in fields {
+                self.pattern(field);
+            }
+        }
+    }
+
+    /// `Self::new` calls the function of the type `Self` stands for.
+    fn member_path(&self, path: &mut [Identifier]) {
+        if let [type_, _member] = path {
+            if let Type::Named(id) = self.type_(&Type::Named(type_.clone())) {
+                *type_ = id;
+            }
+        }
+    }
+}
@@ -11,11 +11,20 @@ lalrpop_mod!(
         clippy::new_without_default,
         clippy::let_and_return,
         clippy::inefficient_to_string,
+        clippy::just_underscores_and_digits,
+        clippy::type_complexity,
+        clippy::unused_unit,
+        clippy::let_unit_value,
+        clippy::manual_range_patterns,
     )]
     pub parser
 );
 
 pub mod ast;
+pub mod escape;
 pub mod visitor;
 pub mod environment_builder;
+pub mod generics;
+pub mod resolve;
+pub mod runtime;
 pub mod typecheck;
@@ -0,0 +1,866 @@
+//! Name resolution. Every name of an item is rewritten to the path of the
+//! item from the root module, so `add` inside `mod math` and `math::add` at
+//! the root both become `math::add`, and typechecking can look items up in a
+//! single `Environment`. Items of the root module keep their name.
+//!
+//! A name is looked up in the module it appears in, among the items defined
+//! there and the items brought in by `use`, and then in each module enclosing
+//! it. Items are private unless marked `pub`, a private item can only be
+//! named inside the module defining it and the modules nested in it.
+//!
+//! Names that are not items, like local variables and the runtime functions,
+//! are left for typechecking. Names that do not resolve are reported, except
+//! members of type parameters, which are only known once the generic
+//! function is instantiated.
+
+use crate::ast::*;
+use crate::runtime;
+
+use std::collections::{HashMap, HashSet};
+
+/// An error found while resolving names. `span` is the byte range of the
+/// code at fault, when the syntax tree has one for it.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub span: Option<(usize, usize)>,
+    pub message: String,
+}
+
+/// Rewrites the names of items in `root` to their path from the root module,
+/// which is what `typecheck::typecheck_root_module` expects.
+pub fn resolve_names(root: &mut ModuleStatement) -> Result<(), Vec<Diagnostic>> {
+    let items = ModuleItems::new(root);
+    let mut builtins: HashSet<String> = runtime::functions()
+        .into_iter()
+        .map(|function| function.id.value)
+        .collect();
+    builtins.extend(
+        ["print", "println", "offset", "len"]
+            .iter()
+            .map(|name| name.to_string()),
+    );
+
+    let mut resolver = Resolver {
+        root: &items,
+        builtins,
+        imports: HashMap::new(),
+        scope: vec![],
+        locals: vec![],
+        closures: vec![],
+        type_parameters: vec![],
+        diagnostics: vec![],
+    };
+
+    resolver.module(root);
+
+    if resolver.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(resolver.diagnostics)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Namespace {
+    Value,
+    Type,
+    Module,
+}
+
+const NAMESPACES: [Namespace; 3] = [Namespace::Value, Namespace::Type, Namespace::Module];
+
+/// The items defined in a module, with whether they are `pub`.
+#[derive(Default)]
+struct ModuleItems {
+    values: HashMap<String, bool>,
+    types: HashMap<String, bool>,
+    modules: HashMap<String, (bool, ModuleItems)>,
+}
+
+impl ModuleItems {
+    fn new(module: &ModuleStatement) -> Self {
+        let mut items = ModuleItems::default();
+
+        for statement in &module.statements {
+            match statement {
+                Statement::Module(m) => {
+                    items
+                        .modules
+                        .insert(m.id.value.clone(), (m.public, ModuleItems::new(m)));
+                }
+                Statement::ExternFunctionDeclaration(function) => {
+                    items
+                        .values
+                        .insert(function.id.value.clone(), function.public);
+                }
+                Statement::FunctionDefinition(function) => {
+                    items
+                        .values
+                        .insert(function.id.value.clone(), function.public);
+                }
+                Statement::FunctionDeclaration(function) => {
+                    items
+                        .values
+                        .insert(function.id.value.clone(), function.public);
+                }
+                Statement::VariableDefinition(variable) => {
+                    items
+                        .values
+                        .insert(variable.id.value.clone(), variable.public);
+                }
+                Statement::StructDefinition(struct_) => {
+                    items.types.insert(struct_.id.value.clone(), struct_.public);
+                }
+                Statement::EnumDefinition(enum_) => {
+                    items.types.insert(enum_.id.value.clone(), enum_.public);
+                }
+                Statement::TypeAlias(alias) => {
+                    items.types.insert(alias.id.value.clone(), alias.public);
+                }
+                // The name of a newtype is also the function making its
+                // values.
+                Statement::Newtype(newtype) => {
+                    items.types.insert(newtype.id.value.clone(), newtype.public);
+                    items
+                        .values
+                        .insert(newtype.id.value.clone(), newtype.public);
+                }
+                Statement::TraitDefinition(trait_) => {
+                    items.types.insert(trait_.id.value.clone(), trait_.public);
+                }
+                Statement::ModuleDeclaration(_)
+                | Statement::Use(_)
+                | Statement::Impl(_)
+                | Statement::TupleDefinition(_)
+                | Statement::Return(_)
+                | Statement::Assignment(_) => {}
+            }
+        }
+
+        items
+    }
+
+    fn module(&self, path: &[String]) -> Option<&ModuleItems> {
+        path.iter().try_fold(self, |module, segment| {
+            Some(&module.modules.get(segment)?.1)
+        })
+    }
+
+    /// Whether the item `name` is `pub`, if the module defines it.
+    fn get(&self, name: &str, namespace: Namespace) -> Option<bool> {
+        match namespace {
+            Namespace::Value => self.values.get(name).copied(),
+            Namespace::Type => self.types.get(name).copied(),
+            Namespace::Module => self.modules.get(name).map(|(public, _)| *public),
+        }
+    }
+}
+
+/// The names a module brings into scope with `use`.
+#[derive(Default)]
+struct Imports {
+    names: HashMap<String, Binding>,
+    /// The modules imported with `use module::*`.
+    globs: Vec<Vec<String>>,
+}
+
+/// The items an imported name refers to in each namespace.
+#[derive(Default)]
+struct Binding {
+    value: Option<Vec<String>>,
+    type_: Option<Vec<String>>,
+    module: Option<Vec<String>>,
+}
+
+impl Binding {
+    fn get(&self, namespace: Namespace) -> Option<&Vec<String>> {
+        match namespace {
+            Namespace::Value => self.value.as_ref(),
+            Namespace::Type => self.type_.as_ref(),
+            Namespace::Module => self.module.as_ref(),
+        }
+    }
+
+    fn set(&mut self, namespace: Namespace, path: Vec<String>) {
+        match namespace {
+            Namespace::Value => self.value = Some(path),
+            Namespace::Type => self.type_ = Some(path),
+            Namespace::Module => self.module = Some(path),
+        }
+    }
+}
+
+enum Resolution {
+    Item(Vec<String>),
+    /// `Type::name`, a variant of an enum or an associated function of a
+    /// struct or enum, with the path of the type.
+    Member(Vec<String>, String),
+}
+
+enum PathError {
+    Unresolved,
+    /// The path of the first private item on the way.
+    Private(Vec<String>),
+    /// A name that more than one glob import brings into scope, with the
+    /// items it may refer to.
+    Ambiguous(String, Vec<Vec<String>>),
+}
+
+struct Resolver<'a> {
+    root: &'a ModuleItems,
+    /// The functions every module can call without defining them.
+    builtins: HashSet<String>,
+    /// The imports of each module resolved so far, by module path.
+    imports: HashMap<Vec<String>, Imports>,
+    /// The modules enclosing the statement being resolved.
+    scope: Vec<String>,
+    /// The local variables in scope, innermost last.
+    locals: Vec<HashSet<String>>,
+    /// The closures being resolved, innermost last, with the index in
+    /// `locals` of the scope of their parameters and the local variables
+    /// they capture so far.
+    closures: Vec<(usize, Vec<Identifier>)>,
+    /// The type parameters of the generic function being resolved, or
+    /// `Self` in traits and impls.
+    type_parameters: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver<'_> {
+    fn error(&mut self, span: Option<(usize, usize)>, message: String) {
+        self.diagnostics.push(Diagnostic { span, message });
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.locals.iter().any(|scope| scope.contains(name))
+    }
+
+    /// A closure captures the local variables it uses that are defined
+    /// outside it, and so do the closures it is in when they are defined
+    /// outside them too.
+    fn capture(&mut self, id: &Identifier) {
+        let depth = match self
+            .locals
+            .iter()
+            .rposition(|scope| scope.contains(&id.value))
+        {
+            Some(depth) => depth,
+            None => return,
+        };
+
+        for (scope, captures) in &mut self.closures {
+            if *scope > depth && !captures.contains(id) {
+                captures.push(id.clone());
+            }
+        }
+    }
+
+    fn bind_local(&mut self, id: &Identifier) {
+        if let Some(scope) = self.locals.last_mut() {
+            scope.insert(id.value.clone());
+        }
+    }
+
+    /// Private items of the module at `path` can be named from the current
+    /// module.
+    fn can_see_private(&self, path: &[String]) -> bool {
+        self.scope.starts_with(path)
+    }
+
+    /// The item `name` refers to in the current module.
+    fn lookup(&self, name: &str, namespace: Namespace) -> Result<Vec<String>, PathError> {
+        for depth in (0..=self.scope.len()).rev() {
+            let path = &self.scope[..depth];
+            let item = |module: &[String]| {
+                let mut item = module.to_vec();
+                item.push(name.to_string());
+                item
+            };
+
+            if self
+                .root
+                .module(path)
+                .and_then(|m| m.get(name, namespace))
+                .is_some()
+            {
+                return Ok(item(path));
+            }
+
+            let imports = match self.imports.get(path) {
+                Some(imports) => imports,
+                None => continue,
+            };
+
+            if let Some(imported) = imports.names.get(name).and_then(|b| b.get(namespace)) {
+                return Ok(imported.clone());
+            }
+
+            // Names imported by a glob must be imported by only one of them.
+            let mut candidates: Vec<Vec<String>> = vec![];
+            for glob in &imports.globs {
+                let visible = match self.root.module(glob).and_then(|m| m.get(name, namespace)) {
+                    Some(public) => public || self.can_see_private(glob),
+                    None => false,
+                };
+
+                if visible && !candidates.contains(&item(glob)) {
+                    candidates.push(item(glob));
+                }
+            }
+
+            match candidates.len() {
+                0 => {}
+                1 => return Ok(candidates.pop().unwrap()),
+                _ => return Err(PathError::Ambiguous(name.to_string(), candidates)),
+            }
+        }
+
+        Err(PathError::Unresolved)
+    }
+
+    fn resolve_path(&self, path: &[String], namespace: Namespace) -> Result<Resolution, PathError> {
+        let (first, rest) = match path.split_first() {
+            Some((first, rest)) if !rest.is_empty() => (first, rest),
+            Some((name, _)) => return self.lookup(name, namespace).map(Resolution::Item),
+            None => return Err(PathError::Unresolved),
+        };
+
+        let mut item = match self.lookup(first, Namespace::Module) {
+            Ok(module) => module,
+            Err(PathError::Unresolved) => {
+                return match (rest, namespace) {
+                    ([member], Namespace::Value) => self
+                        .lookup(first, Namespace::Type)
+                        .map(|type_| Resolution::Member(type_, member.clone())),
+                    _ => Err(PathError::Unresolved),
+                }
+            }
+            Err(error) => return Err(error),
+        };
+
+        for (index, segment) in rest.iter().enumerate() {
+            let module = self.root.module(&item).ok_or(PathError::Unresolved)?;
+            let is_member = index + 2 == rest.len()
+                && namespace == Namespace::Value
+                && module.types.contains_key(segment)
+                && !module.modules.contains_key(segment);
+
+            let segment_namespace = if is_member {
+                Namespace::Type
+            } else if index + 1 < rest.len() {
+                Namespace::Module
+            } else {
+                namespace
+            };
+
+            let public = module
+                .get(segment, segment_namespace)
+                .ok_or(PathError::Unresolved)?;
+            let private = !public && !self.can_see_private(&item);
+
+            item.push(segment.clone());
+
+            if private {
+                return Err(PathError::Private(item));
+            }
+
+            if is_member {
+                return Ok(Resolution::Member(item, rest[index + 1].clone()));
+            }
+        }
+
+        Ok(Resolution::Item(item))
+    }
+
+    fn import(&mut self, use_: &UseStatement) {
+        let path: Vec<_> = use_.path.iter().map(|s| s.value.clone()).collect();
+        let display = display_path(&path);
+
+        match &use_.import {
+            Import::Glob => match self.resolve_path(&path, Namespace::Module) {
+                Ok(Resolution::Item(module)) => self
+                    .imports
+                    .entry(self.scope.clone())
+                    .or_default()
+                    .globs
+                    .push(module),
+                Err(PathError::Private(item)) => {
+                    self.error(Some(use_.span), private_message(&item))
+                }
+                Err(PathError::Ambiguous(name, items)) => {
+                    self.error(Some(use_.span), ambiguous_message(&name, &items))
+                }
+                _ => self.error(Some(use_.span), format!("unresolved import {}::*", display)),
+            },
+            Import::Item(alias) => {
+                let name = match alias {
+                    Some(alias) => alias.value.clone(),
+                    None => path.last().unwrap().clone(),
+                };
+
+                let mut binding = Binding::default();
+                let mut error = None;
+                let mut found = false;
+
+                for &namespace in &NAMESPACES {
+                    match self.resolve_path(&path, namespace) {
+                        Ok(Resolution::Item(item)) => {
+                            binding.set(namespace, item);
+                            found = true;
+                        }
+                        Err(PathError::Private(item)) => error = Some(private_message(&item)),
+                        Err(PathError::Ambiguous(name, items)) => {
+                            error = Some(ambiguous_message(&name, &items))
+                        }
+                        _ => {}
+                    }
+                }
+
+                match error {
+                    Some(message) if !found => self.error(Some(use_.span), message),
+                    _ if !found => {
+                        self.error(Some(use_.span), format!("unresolved import {}", display))
+                    }
+                    _ => {
+                        self.imports
+                            .entry(self.scope.clone())
+                            .or_default()
+                            .names
+                            .insert(name, binding);
+                    }
+                }
+            }
+        }
+    }
+
+    fn module(&mut self, module: &mut ModuleStatement) {
+        // Imports come first, functions can use names imported after them.
+        for statement in &module.statements {
+            if let Statement::Use(use_) = statement {
+                self.import(use_);
+            }
+        }
+
+        for statement in &mut module.statements {
+            self.module_statement(statement);
+        }
+    }
+
+    fn module_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Module(m) => {
+                self.scope.push(m.id.value.clone());
+                self.module(m);
+                self.scope.pop();
+            }
+            Statement::ModuleDeclaration(_) | Statement::Use(_) => {}
+            Statement::ExternFunctionDeclaration(function) => {
+                for (_id, type_) in &mut function.parameters {
+                    self.type_(type_);
+                }
+                self.type_(&mut function.return_type);
+            }
+            Statement::FunctionDeclaration(function) => {
+                for (_id, type_) in &mut function.parameters {
+                    self.type_(type_);
+                }
+                self.type_(&mut function.return_type);
+            }
+            Statement::FunctionDefinition(function) => {
+                for bound in function
+                    .type_parameters
+                    .iter_mut()
+                    .flat_map(|parameter| &mut parameter.bounds)
+                {
+                    self.type_name(bound);
+                }
+
+                let type_parameters = function
+                    .type_parameters
+                    .iter()
+                    .map(|parameter| parameter.id.value.clone())
+                    .collect();
+
+                self.function(function, type_parameters);
+            }
+            Statement::TraitDefinition(trait_) => {
+                self.define(&mut trait_.id);
+
+                self.type_parameters = vec!["Self".to_string()];
+                for method in &mut trait_.methods {
+                    for (_id, type_) in &mut method.parameters {
+                        self.type_(type_);
+                    }
+                    self.type_(&mut method.return_type);
+                }
+                self.type_parameters.clear();
+            }
+            Statement::Impl(impl_) => {
+                if let Some(trait_) = &mut impl_.trait_ {
+                    self.type_name(trait_);
+                }
+                self.type_(&mut impl_.type_);
+
+                for function in &mut impl_.functions {
+                    self.function(function, vec!["Self".to_string()]);
+                }
+
+                self.type_parameters = vec!["Self".to_string()];
+                for function in &mut impl_.declarations {
+                    for (_id, type_) in &mut function.parameters {
+                        self.type_(type_);
+                    }
+                    self.type_(&mut function.return_type);
+                }
+                self.type_parameters.clear();
+            }
+            Statement::StructDefinition(struct_) => {
+                self.define(&mut struct_.id);
+                for (_id, type_) in &mut struct_.fields {
+                    self.type_(type_);
+                }
+            }
+            Statement::EnumDefinition(enum_) => {
+                self.define(&mut enum_.id);
+                for type_ in enum_.variants.iter_mut().flat_map(|(_id, types)| types) {
+                    self.type_(type_);
+                }
+            }
+            Statement::TypeAlias(alias) => {
+                self.define(&mut alias.id);
+                self.type_(&mut alias.type_);
+            }
+            Statement::Newtype(newtype) => {
+                self.define(&mut newtype.id);
+                self.type_(&mut newtype.type_);
+            }
+            Statement::VariableDefinition(_)
+            | Statement::TupleDefinition(_)
+            | Statement::Return(_)
+            | Statement::Assignment(_) => self.block_statement(statement),
+        }
+    }
+
+    fn function(
+        &mut self,
+        function: &mut FunctionDefinitionStatement,
+        type_parameters: Vec<String>,
+    ) {
+        self.type_parameters = type_parameters;
+
+        for (_id, type_) in &mut function.parameters {
+            self.type_(type_);
+        }
+        self.type_(&mut function.return_type);
+
+        let parameters = function
+            .parameters
+            .iter()
+            .map(|(id, _type)| id.value.clone())
+            .collect();
+
+        self.locals.push(parameters);
+        self.expression(&mut function.body);
+        self.locals.pop();
+        self.type_parameters.clear();
+    }
+
+    /// Types and traits are named by their path, like every other item.
+    fn define(&self, id: &mut Identifier) {
+        let mut path = self.scope.clone();
+        path.push(id.value.clone());
+
+        id.value = display_path(&path);
+    }
+
+    fn block_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::VariableDefinition(variable) => {
+                self.type_(&mut variable.type_);
+                self.expression(&mut variable.initialize_expression);
+                self.bind_local(&variable.id);
+            }
+            Statement::TupleDefinition(tuple) => {
+                if let Some(type_) = &mut tuple.type_ {
+                    self.type_(type_);
+                }
+                self.expression(&mut tuple.initialize_expression);
+                for id in &tuple.ids {
+                    self.bind_local(id);
+                }
+            }
+            Statement::Return(return_) => self.expression(&mut return_.expression),
+            Statement::Assignment(assignment) => {
+                self.expression(&mut assignment.target);
+                self.expression(&mut assignment.expression);
+            }
+            // Typechecking rejects items inside blocks.
+            _ => {}
+        }
+    }
+
+    fn type_(&mut self, type_: &mut Type) {
+        match type_ {
+            Type::Named(id) => self.type_name(id),
+            Type::Function {
+                parameters,
+                return_type,
+            } => {
+                for parameter in parameters {
+                    self.type_(parameter);
+                }
+                self.type_(return_type);
+            }
+            Type::Array { element_type, .. } => self.type_(element_type),
+            Type::Tuple(types) => {
+                for type_ in types {
+                    self.type_(type_);
+                }
+            }
+            Type::Slice(element_type) => self.type_(element_type),
+            Type::Pointer { pointee, .. } => self.type_(pointee),
+            Type::Boolean
+            | Type::Char
+            | Type::Int(_)
+            | Type::UInt(_)
+            | Type::Float(_)
+            | Type::None => {}
+        }
+    }
+
+    fn type_name(&mut self, id: &mut Identifier) {
+        if self.type_parameters.contains(&id.value) {
+            return;
+        }
+
+        let path: Vec<_> = id.value.split("::").map(str::to_string).collect();
+
+        match self.resolve_path(&path, Namespace::Type) {
+            Ok(Resolution::Item(item)) => id.value = display_path(&item),
+            Err(PathError::Private(item)) => self.error(id.span, private_message(&item)),
+            Err(PathError::Ambiguous(name, items)) => {
+                self.error(id.span, ambiguous_message(&name, &items))
+            }
+            _ if self.is_type_parameter_member(&path) => {}
+            _ => self.error(id.span, format!("unknown type {}", id.value)),
+        }
+    }
+
+    /// Whether `path` names a member of a type parameter, like `T::new`,
+    /// which only resolves once the generic function is instantiated.
+    fn is_type_parameter_member(&self, path: &[String]) -> bool {
+        path.len() > 1 && self.type_parameters.contains(&path[0])
+    }
+
+    fn value_name(&mut self, id: &mut Identifier) {
+        if self.is_local(&id.value) {
+            self.capture(id);
+            return;
+        }
+
+        let item = match self.lookup(&id.value, Namespace::Value) {
+            Ok(item) => item,
+            Err(PathError::Ambiguous(name, items)) => {
+                return self.error(id.span, ambiguous_message(&name, &items));
+            }
+            Err(_) if self.builtins.contains(&id.value) => return,
+            Err(_) => return self.error(id.span, format!("unresolved name {}", id.value)),
+        };
+
+        // Items of the root module keep their name, which a local
+        // variable may hide where the item is imported under another.
+        if let [name] = item.as_slice() {
+            if self.is_local(name) {
+                self.error(
+                    id.span,
+                    format!(
+                        "{} refers to {}, which is hidden by a local variable",
+                        id.value, name
+                    ),
+                );
+            }
+        }
+
+        id.value = display_path(&item);
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::Block(block) => {
+                self.locals.push(HashSet::new());
+                for statement in &mut block.statements {
+                    self.block_statement(statement);
+                }
+                if let Some(expression) = &mut block.return_expression {
+                    self.expression(expression);
+                }
+                self.locals.pop();
+            }
+            Expression::Unary(unary) => self.expression(&mut unary.expression),
+            Expression::Binary(binary) => {
+                self.expression(&mut binary.left);
+                self.expression(&mut binary.right);
+            }
+            Expression::If(if_) => {
+                self.expression(&mut if_.condition);
+                self.expression(&mut if_.true_path);
+                self.expression(&mut if_.false_path);
+            }
+            Expression::Match(match_) => {
+                self.expression(&mut match_.scrutinee);
+                for arm in &mut match_.arms {
+                    self.locals.push(HashSet::new());
+                    for pattern in &mut arm.patterns {
+                        self.pattern(pattern);
+                    }
+                    self.expression(&mut arm.expression);
+                    self.locals.pop();
+                }
+            }
+            Expression::Call(call) => {
+                self.expression(&mut call.callee);
+                for argument in &mut call.arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Access(access) => {
+                self.expression(&mut access.base);
+                self.expression(&mut access.offset);
+            }
+            Expression::Cast(cast) => {
+                self.expression(&mut cast.expression);
+                self.type_(&mut cast.type_);
+            }
+            Expression::Array(array) => match &mut **array {
+                ArrayExpression::List(elements) => {
+                    for element in elements {
+                        self.expression(element);
+                    }
+                }
+                ArrayExpression::Repeat { expression, .. } => self.expression(expression),
+            },
+            Expression::Tuple(tuple) => {
+                for element in &mut tuple.elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Struct(struct_) => {
+                self.type_name(&mut struct_.id);
+                for (_id, expression) in &mut struct_.fields {
+                    self.expression(expression);
+                }
+            }
+            Expression::Field(field) => self.expression(&mut field.base),
+            Expression::Path(path) => {
+                let segments: Vec<_> = path.segments.iter().map(|s| s.value.clone()).collect();
+
+                match self.resolve_path(&segments, Namespace::Value) {
+                    Ok(Resolution::Item(item)) => {
+                        *expression = Expression::Identifier(Identifier {
+                            value: display_path(&item),
+                            span: Some(path.span),
+                        })
+                    }
+                    Ok(Resolution::Member(type_, member)) => {
+                        path.segments = member_path(&type_, member)
+                    }
+                    Err(PathError::Private(item)) => {
+                        let span = Some(path.span);
+                        self.error(span, private_message(&item))
+                    }
+                    Err(PathError::Ambiguous(name, items)) => {
+                        let span = Some(path.span);
+                        self.error(span, ambiguous_message(&name, &items))
+                    }
+                    Err(PathError::Unresolved) if self.is_type_parameter_member(&segments) => {}
+                    Err(PathError::Unresolved) => {
+                        let span = Some(path.span);
+                        self.error(span, format!("unresolved path {}", segments.join("::")))
+                    }
+                }
+            }
+            Expression::Instantiation(instantiation) => {
+                self.expression(&mut instantiation.expression);
+                for type_ in &mut instantiation.type_arguments {
+                    self.type_(type_);
+                }
+            }
+            Expression::Closure(closure) => {
+                for (_id, type_) in &mut closure.parameters {
+                    self.type_(type_);
+                }
+
+                let parameters = closure
+                    .parameters
+                    .iter()
+                    .map(|(id, _type)| id.value.clone())
+                    .collect();
+
+                self.closures.push((self.locals.len(), vec![]));
+                self.locals.push(parameters);
+                self.expression(&mut closure.body);
+                self.locals.pop();
+                closure.captures = self.closures.pop().unwrap().1;
+            }
+            Expression::Identifier(id) => self.value_name(id),
+            Expression::Literal(_) => {}
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Binding(id) => self.bind_local(id),
+            Pattern::Variant(path, fields) => {
+                let segments: Vec<_> = path.iter().map(|s| s.value.clone()).collect();
+                let span = match (path.first(), path.last()) {
+                    (Some(first), Some(last)) => first.span.zip(last.span).map(|(f, l)| (f.0, l.1)),
+                    _ => None,
+                };
+
+                match self.resolve_path(&segments, Namespace::Value) {
+                    Ok(Resolution::Member(type_, member)) => *path = member_path(&type_, member),
+                    Ok(Resolution::Item(_)) => {}
+                    Err(PathError::Private(item)) => self.error(span, private_message(&item)),
+                    Err(PathError::Ambiguous(name, items)) => {
+                        self.error(span, ambiguous_message(&name, &items))
+                    }
+                    Err(PathError::Unresolved) => {
+                        self.error(span, format!("unresolved path {}", segments.join("::")))
+                    }
+                }
+
+                for field in fields {
+                    self.pattern(field);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range(_, _) => {}
+        }
+    }
+}
+
+fn display_path(path: &[String]) -> String {
+    path.join("::")
+}
+
+fn private_message(item: &[String]) -> String {
+    format!("item {} is private", display_path(item))
+}
+
+fn ambiguous_message(name: &str, items: &[Vec<String>]) -> String {
+    let items: Vec<_> = items.iter().map(|item| display_path(item)).collect();
+
+    format!(
+        "{} is ambiguous, it may refer to {}",
+        name,
+        items.join(" or ")
+    )
+}
+
+/// The form typechecking expects for enum variants and associated functions,
+/// `Type::name` with the type named by its path.
+fn member_path(type_: &[String], member: String) -> Vec<Identifier> {
+    vec![
+        Identifier::from(display_path(type_)),
+        Identifier::from(member),
+    ]
+}
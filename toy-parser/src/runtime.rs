@@ -0,0 +1,85 @@
+//! Functions every module can call without declaring them. `toyc` defines
+//! them in each object file it emits, on top of `malloc`, `free`, `write`
+//! and `exit` from libc.
+
+use crate::ast::{Identifier, IntType, Type, UIntType};
+use crate::environment_builder::Environment;
+use crate::typecheck::ir::ExternFunction;
+
+/// The runtime functions, a module's own definitions take precedence over
+/// them.
+///
+/// - `alloc(size, align)` returns memory for `size` bytes aligned to
+///   `align`, which must be a power of two. It panics when out of memory.
+/// - `free(pointer)` releases memory from `alloc` or `realloc`.
+/// - `realloc(pointer, size, align, new_size)` grows or shrinks memory from
+///   `alloc`, given the `size` and `align` it was allocated with.
+/// - `panic(message)` prints `message` to standard error and exits with
+///   status 101.
+/// - `exit(code)` exits with status `code`.
+pub fn functions() -> Vec<ExternFunction> {
+    let usize_ = || Type::UInt(UIntType::USize);
+    let pointer = || Type::Pointer {
+        mutable: true,
+        pointee: Box::new(Type::UInt(UIntType::U8)),
+    };
+    let unit = || Type::Tuple(vec![]);
+
+    let function = |id: &str, parameters: Vec<(&str, Type)>, return_type| ExternFunction {
+        id: Identifier::from(id.to_string()),
+        parameters: parameters
+            .into_iter()
+            .map(|(id, type_)| {
+                let id = Identifier::from(id.to_string());
+                (id, type_)
+            })
+            .collect(),
+        return_type,
+    };
+
+    vec![
+        function(
+            "alloc",
+            vec![("size", usize_()), ("align", usize_())],
+            pointer(),
+        ),
+        function("free", vec![("pointer", pointer())], unit()),
+        function(
+            "realloc",
+            vec![
+                ("pointer", pointer()),
+                ("size", usize_()),
+                ("align", usize_()),
+                ("new_size", usize_()),
+            ],
+            pointer(),
+        ),
+        function(
+            "panic",
+            vec![("message", Type::Slice(Box::new(Type::UInt(UIntType::U8))))],
+            unit(),
+        ),
+        function("exit", vec![("code", Type::Int(IntType::I32))], unit()),
+    ]
+}
+
+/// An environment holding the runtime functions, for modules to be built on.
+pub fn environment() -> Environment {
+    let mut env = Environment::new();
+
+    for function in functions() {
+        env.insert_extern(
+            function.id,
+            Type::Function {
+                parameters: function
+                    .parameters
+                    .into_iter()
+                    .map(|(_id, type_)| type_)
+                    .collect(),
+                return_type: Box::new(function.return_type),
+            },
+        );
+    }
+
+    env
+}
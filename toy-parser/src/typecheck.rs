@@ -1,34 +1,139 @@
-use crate::ast::{self, Type};
-use crate::environment_builder::{Environment, EnvironmentBuilder};
+use crate::ast::{self, FloatType, IntType, LiteralFloatType, LiteralIntType, Type, UIntType};
+use crate::environment_builder::{Environment, EnvironmentBuilder, Impl, TypeDefinition};
+use crate::generics;
+use crate::resolve::Diagnostic;
+use crate::runtime;
 use crate::visitor::AstVisitor;
 use std::rc::Rc;
 
 pub mod ir {
     use crate::ast::{self, Type};
+    use crate::resolve::Diagnostic;
     #[derive(Debug)]
     pub enum Statement {
-        Module(Module),
+        Module(Box<Module>),
+        VariableDefinition(Box<VariableDefinition>),
+        TupleDefinition(Box<TupleDefinition>),
+        Assignment(Box<Assignment>),
         None,
     }
 
+    #[derive(Debug)]
+    pub struct VariableDefinition {
+        pub id: ast::Identifier,
+        pub type_: Type,
+        pub initialize_expression: Expression,
+    }
+
+    #[derive(Debug)]
+    pub struct TupleDefinition {
+        pub ids: Vec<ast::Identifier>,
+        pub type_: Type,
+        pub initialize_expression: Expression,
+    }
+
+    #[derive(Debug)]
+    pub struct Assignment {
+        pub target: Expression,
+        pub expression: Expression,
+    }
+
     #[derive(Debug)]
     pub struct Module {
         pub id: ast::Identifier,
+        pub structs: Vec<StructDefinition>,
+        pub enums: Vec<EnumDefinition>,
+        pub newtypes: Vec<NewtypeDefinition>,
+        pub extern_functions: Vec<ExternFunction>,
         pub functions: Vec<Function>,
+        pub function_declarations: Vec<FunctionDeclaration>,
+        pub modules: Vec<Module>,
+        /// The functions of every closure of the program, in the root
+        /// module only.
+        pub closures: Vec<Closure>,
+        /// The warnings about the whole program, in the root module only.
+        pub warnings: Vec<Diagnostic>,
+        /// Errors found without stopping typechecking, the program must not
+        /// be compiled if there are any. In the root module only.
+        pub errors: Vec<Diagnostic>,
+    }
+
+    #[derive(Debug)]
+    pub struct StructDefinition {
+        pub id: ast::Identifier,
+        pub fields: Vec<(ast::Identifier, ast::Type)>,
+    }
+
+    #[derive(Debug)]
+    pub struct EnumDefinition {
+        pub id: ast::Identifier,
+        pub variants: Vec<(ast::Identifier, Vec<ast::Type>)>,
+    }
+
+    #[derive(Debug)]
+    pub struct NewtypeDefinition {
+        pub id: ast::Identifier,
+        pub type_: ast::Type,
+    }
+
+    #[derive(Debug)]
+    pub struct ExternFunction {
+        pub id: ast::Identifier,
+        pub parameters: Vec<(ast::Identifier, ast::Type)>,
+        pub return_type: ast::Type,
     }
 
     #[derive(Debug)]
     pub struct Function {
         pub id: ast::Identifier,
+        /// Whether other programs may call it: `pub` functions and methods,
+        /// and the methods of traits, never instances of generic functions.
+        pub public: bool,
+        pub parameters: Vec<(ast::Identifier, ast::Type)>,
+        pub return_type: ast::Type,
+        pub body: Expression,
+    }
+
+    /// The function of a closure, it takes a pointer to the values of its
+    /// captures before its parameters.
+    #[derive(Debug)]
+    pub struct Closure {
+        pub id: ast::Identifier,
+        pub captures: Vec<(ast::Identifier, ast::Type)>,
         pub parameters: Vec<(ast::Identifier, ast::Type)>,
         pub return_type: ast::Type,
         pub body: Expression,
     }
 
+    /// A function defined in another object file, called as `symbol`.
+    #[derive(Debug)]
+    pub struct FunctionDeclaration {
+        pub id: ast::Identifier,
+        pub parameters: Vec<(ast::Identifier, ast::Type)>,
+        pub return_type: ast::Type,
+        pub symbol: String,
+    }
+
     #[derive(Debug)]
     pub enum Expression {
         Block(Box<BlockExpression>),
+        Unary(Box<UnaryExpression>),
         Binary(Box<BinaryExpression>),
+        Match(Box<MatchExpression>),
+        Cast(Box<CastExpression>),
+        Array(Box<ArrayExpression>),
+        Access(Box<AccessExpression>),
+        Call(Box<CallExpression>),
+        Print(Box<PrintExpression>),
+        Offset(Box<OffsetExpression>),
+        Length(Box<LengthExpression>),
+        Tuple(Box<TupleExpression>),
+        Struct(Box<StructExpression>),
+        Field(Box<FieldExpression>),
+        Variant(Box<VariantExpression>),
+        Closure(Box<ClosureExpression>),
+        Newtype(Box<NewtypeExpression>),
+        Identifier(IdentifierExpression),
         Literal(ast::Literal),
         None,
     }
@@ -37,8 +142,24 @@ pub mod ir {
         pub fn type_(&self) -> Type {
             match self {
                 Expression::Literal(literal) => literal.type_(),
+                Expression::Unary(unary) => unary.type_.clone(),
                 Expression::Binary(bin_op) => bin_op.type_.clone(),
                 Expression::Block(block) => block.type_.clone(),
+                Expression::Match(match_) => match_.type_.clone(),
+                Expression::Cast(cast) => cast.type_.clone(),
+                Expression::Array(array) => array.type_.clone(),
+                Expression::Access(access) => access.type_.clone(),
+                Expression::Call(call) => call.type_.clone(),
+                Expression::Print(_) => Type::Int(ast::IntType::ISize),
+                Expression::Offset(offset) => offset.type_.clone(),
+                Expression::Length(_) => Type::UInt(ast::UIntType::USize),
+                Expression::Tuple(tuple) => tuple.type_.clone(),
+                Expression::Struct(struct_) => struct_.type_.clone(),
+                Expression::Field(field) => field.type_.clone(),
+                Expression::Variant(variant) => variant.type_.clone(),
+                Expression::Closure(closure) => closure.type_.clone(),
+                Expression::Newtype(newtype) => newtype.type_.clone(),
+                Expression::Identifier(id) => id.type_.clone(),
                 _ => todo!(),
             }
         }
@@ -47,9 +168,17 @@ pub mod ir {
     #[derive(Debug)]
     pub struct BlockExpression {
         pub type_: Type,
+        pub statements: Vec<Statement>,
         pub return_expression: Expression,
     }
 
+    #[derive(Debug)]
+    pub struct UnaryExpression {
+        pub type_: Type,
+        pub operator: ast::UnaryOperator,
+        pub expression: Expression,
+    }
+
     #[derive(Debug)]
     pub struct BinaryExpression {
         pub type_: Type,
@@ -57,105 +186,3027 @@ pub mod ir {
         pub lhs: Expression,
         pub rhs: Expression,
     }
+
+    #[derive(Debug)]
+    pub struct IdentifierExpression {
+        pub type_: Type,
+        pub id: ast::Identifier,
+    }
+
+    #[derive(Debug)]
+    pub struct MatchExpression {
+        pub type_: Type,
+        pub scrutinee: Expression,
+        pub arms: Vec<MatchArm>,
+    }
+
+    #[derive(Debug)]
+    pub struct MatchArm {
+        pub patterns: Vec<Pattern>,
+        pub bindings: Vec<Binding>,
+        pub expression: Expression,
+    }
+
+    /// A name a variant pattern binds to a field of the variant's payload.
+    #[derive(Debug)]
+    pub struct Binding {
+        pub id: ast::Identifier,
+        pub type_: Type,
+        pub variant: ast::Identifier,
+        pub field: usize,
+    }
+
+    #[derive(Debug)]
+    pub struct ArrayExpression {
+        pub type_: Type,
+        pub elements: ArrayElements,
+    }
+
+    #[derive(Debug)]
+    pub enum ArrayElements {
+        List(Vec<Expression>),
+        Repeat(Expression, u64),
+    }
+
+    #[derive(Debug)]
+    pub struct AccessExpression {
+        pub type_: Type,
+        pub base: Expression,
+        pub offset: Expression,
+    }
+
+    /// Writes `argument` to standard output, followed by a newline for
+    /// `println`. Evaluates to the result of writing `argument`.
+    #[derive(Debug)]
+    pub struct PrintExpression {
+        pub argument: Expression,
+        pub newline: bool,
+    }
+
+    /// `pointer` moved by `count` elements of its pointee type, which may
+    /// be negative.
+    #[derive(Debug)]
+    pub struct OffsetExpression {
+        pub type_: Type,
+        pub pointer: Expression,
+        pub count: Expression,
+    }
+
+    /// The number of elements of the slice `slice`.
+    #[derive(Debug)]
+    pub struct LengthExpression {
+        pub slice: Expression,
+    }
+
+    #[derive(Debug)]
+    pub struct CallExpression {
+        pub type_: Type,
+        pub callee: Expression,
+        pub arguments: Vec<Expression>,
+    }
+
+    #[derive(Debug)]
+    pub struct TupleExpression {
+        pub type_: Type,
+        pub elements: Vec<Expression>,
+    }
+
+    /// Fields are in the order of the struct definition.
+    #[derive(Debug)]
+    pub struct StructExpression {
+        pub type_: Type,
+        pub fields: Vec<Expression>,
+    }
+
+    #[derive(Debug)]
+    pub struct FieldExpression {
+        pub type_: Type,
+        pub base: Expression,
+        pub index: usize,
+    }
+
+    /// Constructs the variant with index `tag` of an enum.
+    #[derive(Debug)]
+    pub struct VariantExpression {
+        pub type_: Type,
+        pub variant: ast::Identifier,
+        pub tag: usize,
+        pub fields: Vec<Expression>,
+    }
+
+    /// The closure `function` with copies of the values of `captures`.
+    #[derive(Debug)]
+    pub struct ClosureExpression {
+        pub type_: Type,
+        pub function: ast::Identifier,
+        pub captures: Vec<IdentifierExpression>,
+    }
+
+    /// `expression` as a value of `type_`, which is a newtype and the type
+    /// of `expression` the type it wraps, or the other way around.
+    #[derive(Debug)]
+    pub struct NewtypeExpression {
+        pub type_: Type,
+        pub expression: Expression,
+    }
+
+    #[derive(Debug)]
+    pub struct CastExpression {
+        pub type_: Type,
+        pub expression: Expression,
+    }
+
+    /// Values are two's complement bit patterns with the width of the scrutinee,
+    /// or variant tags when the scrutinee is an enum.
+    ///
+    /// Patterns already covered by earlier arms are dropped during typechecking,
+    /// so every `Constant` of a match is distinct.
+    #[derive(Debug)]
+    pub enum Pattern {
+        Wildcard,
+        Constant(u128),
+        Range(u128, u128),
+    }
 }
 
+/// Typechecks a module whose names were resolved by `resolve::resolve_names`.
 pub fn typecheck_root_module(root_module: ast::ModuleStatement) -> ir::Module {
+    let mut root_module = root_module;
+    expand_type_aliases(&mut root_module);
+
     let mut env_builder = EnvironmentBuilder {};
-    let env = Environment::new();
-    let env = env_builder.visit_module_statement(env, &root_module);
+    let env = Environment::put(Rc::new(runtime::environment()));
+    let env = Rc::new(env_builder.visit_module_statement(env, &root_module));
+
+    let mut module = typecheck_module(root_module, Rc::clone(&env));
+
+    // Instances of generic functions are asked for by the functions calling
+    // them, including other instances.
+    while let Some((generic, type_arguments)) = env.next_instance() {
+        let definition = env.get_generic(&generic).unwrap();
+        let substitution =
+            generics::Substitution::new(&definition.type_parameters, &type_arguments);
+        let mut instance = substitution.function(definition);
+        instance.id.value = generics::instance_name(&definition.id.value, &type_arguments);
+        instance.public = false;
+        let instance = typecheck_function(instance, Rc::clone(&env));
+
+        // Instances are functions of the module defining the generic function.
+        let mut instance_module = &mut module;
+        for segment in generic
+            .value
+            .split("::")
+            .collect::<Vec<_>>()
+            .split_last()
+            .unwrap()
+            .1
+        {
+            instance_module = instance_module
+                .modules
+                .iter_mut()
+                .find(|m| m.id.value == *segment)
+                .unwrap();
+        }
+
+        instance_module.functions.push(instance);
+    }
+
+    module.closures = env.take_closures();
+    module.warnings = env.take_warnings();
+    module.errors = env.take_errors();
 
-    typecheck_module(root_module, Rc::new(env))
+    module
 }
 
-pub fn typecheck_module(module: ast::ModuleStatement, _env: Rc<Environment>) -> ir::Module {
+/// Replaces every type alias by the type it stands for, an alias is the
+/// same type as its target wherever it is used.
+fn expand_type_aliases(root_module: &mut ast::ModuleStatement) {
+    let mut aliases = vec![];
+    collect_type_aliases(root_module, &mut aliases);
+
+    let mut targets = vec![];
+    for (id, _target) in &aliases {
+        expand_type_alias(id, &aliases, &mut targets, &mut vec![]);
+    }
+
+    generics::Substitution::aliases(targets).module(root_module);
+}
+
+/// Adds the target of alias `id` to `targets` once the aliases it names are
+/// expanded, `path` is the chain of aliases whose target names `id`.
+fn expand_type_alias(
+    id: &ast::Identifier,
+    aliases: &[(ast::Identifier, Type)],
+    targets: &mut Vec<(ast::Identifier, Type)>,
+    path: &mut Vec<ast::Identifier>,
+) {
+    if targets.iter().any(|(alias, _target)| alias == id) {
+        return;
+    }
+
+    if let Some(start) = path.iter().position(|alias| alias == id) {
+        let cycle: Vec<_> = path[start..]
+            .iter()
+            .chain(Some(id))
+            .map(|alias| alias.value.as_str())
+            .collect();
+        panic!(
+            "type alias {} refers to itself: {}",
+            id.value,
+            cycle.join(" -> ")
+        );
+    }
+
+    let target = match aliases.iter().find(|(alias, _target)| alias == id) {
+        Some((_id, target)) => target,
+        None => return,
+    };
+
+    let mut names = vec![];
+    named_types(target, &mut names);
+
+    path.push(id.clone());
+    for name in names {
+        expand_type_alias(name, aliases, targets, path);
+    }
+    path.pop();
+
+    let target = generics::Substitution::aliases(targets.clone()).type_(target);
+    targets.push((id.clone(), target));
+}
+
+/// Adds the named types `type_` is made of to `names`.
+fn named_types<'a>(type_: &'a Type, names: &mut Vec<&'a ast::Identifier>) {
+    match type_ {
+        Type::Named(id) => names.push(id),
+        Type::Function {
+            parameters,
+            return_type,
+        } => {
+            for parameter in parameters {
+                named_types(parameter, names);
+            }
+            named_types(return_type, names);
+        }
+        Type::Array { element_type, .. } | Type::Slice(element_type) => {
+            named_types(element_type, names)
+        }
+        Type::Pointer { pointee, .. } => named_types(pointee, names),
+        Type::Tuple(types) => {
+            for type_ in types {
+                named_types(type_, names);
+            }
+        }
+        Type::Boolean | Type::Char | Type::Int(_) | Type::UInt(_) | Type::Float(_) | Type::None => {
+        }
+    }
+}
+
+fn collect_type_aliases(module: &ast::ModuleStatement, aliases: &mut Vec<(ast::Identifier, Type)>) {
+    for statement in &module.statements {
+        match statement {
+            ast::Statement::Module(m) => collect_type_aliases(m, aliases),
+            ast::Statement::TypeAlias(alias) => {
+                aliases.push((alias.id.clone(), alias.type_.clone()))
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn typecheck_module(module: ast::ModuleStatement, env: Rc<Environment>) -> ir::Module {
+    let mut structs = vec![];
+    let mut enums = vec![];
+    let mut newtypes = vec![];
+    let mut extern_functions = vec![];
     let mut functions = vec![];
+    let mut function_declarations = vec![];
+    let mut modules = vec![];
 
     for statement in module.statements {
         match statement {
-            ast::Statement::Module(_) => todo!(),
-            ast::Statement::ExternFunctionDeclaration(_) => todo!(),
-            ast::Statement::FunctionDefinition(function) => {
-                let function = ir::Function {
+            // Items are named by their path from the root module, so every
+            // module is typechecked in the environment of the root module.
+            ast::Statement::Module(m) => modules.push(typecheck_module(*m, Rc::clone(&env))),
+            ast::Statement::Use(_) => {}
+            ast::Statement::ModuleDeclaration(m) => {
+                panic!("the file of module {} was not loaded", m.id.value)
+            }
+            ast::Statement::ExternFunctionDeclaration(function) => {
+                for (id, type_) in &function.parameters {
+                    check_type(type_, &env);
+                    let position = format!("parameter {} of {}", id.value, function.id.value);
+                    check_c_type(type_, &position, &env);
+                }
+                check_type(&function.return_type, &env);
+                // Functions returning `()` return nothing in C.
+                if function.return_type != Type::Tuple(vec![]) {
+                    let position = format!("return type of {}", function.id.value);
+                    check_c_type(&function.return_type, &position, &env);
+                }
+
+                extern_functions.push(ir::ExternFunction {
                     id: function.id,
                     parameters: function.parameters,
                     return_type: function.return_type,
-                    body: typecheck_expression(function.body),
-                };
+                });
+            }
+            ast::Statement::StructDefinition(struct_) => {
+                structs.push(typecheck_struct_definition(*struct_, Rc::clone(&env)));
+            }
+            ast::Statement::EnumDefinition(enum_) => {
+                enums.push(typecheck_enum_definition(*enum_, Rc::clone(&env)));
+            }
+            // Its uses are already replaced by its target.
+            ast::Statement::TypeAlias(alias) => check_type(&alias.type_, &env),
+            ast::Statement::Newtype(newtype) => {
+                check_type(&newtype.type_, &env);
+
+                if newtype_refers_to(&newtype.type_, &newtype.id, &env, &mut vec![]) {
+                    panic!("newtype {} refers to itself", newtype.id.value);
+                }
+
+                newtypes.push(ir::NewtypeDefinition {
+                    id: newtype.id,
+                    type_: newtype.type_,
+                });
+            }
+            // Generic functions are typechecked against their bounds here, and
+            // for each instance once it is called.
+            ast::Statement::FunctionDefinition(function)
+                if !function.type_parameters.is_empty() =>
+            {
+                check_generic_function(*function, &env);
+            }
+            ast::Statement::FunctionDefinition(function) => {
+                let function = typecheck_function(*function, Rc::clone(&env));
                 functions.push(function);
             }
-            ast::Statement::VariableDefinition(_) => todo!(),
-            ast::Statement::Return(_) => todo!(),
+            ast::Statement::FunctionDeclaration(function) => {
+                for (_id, type_) in &function.parameters {
+                    check_type(type_, &env);
+                }
+                check_type(&function.return_type, &env);
+
+                function_declarations.push(ir::FunctionDeclaration {
+                    id: function.id,
+                    parameters: function.parameters,
+                    return_type: function.return_type,
+                    symbol: function.symbol,
+                });
+            }
+            ast::Statement::TraitDefinition(trait_) => typecheck_trait_definition(&trait_, &env),
+            ast::Statement::Impl(impl_) => {
+                let (methods, declarations) = typecheck_impl(*impl_, Rc::clone(&env));
+                functions.extend(methods);
+                function_declarations.extend(declarations);
+            }
+            // Constants are replaced by their value where they are used.
+            ast::Statement::VariableDefinition(variable) => typecheck_constant(&variable, &env),
+            ast::Statement::TupleDefinition(_) => {
+                panic!("Tuple definition outside of a function body is not allowed")
+            }
+            ast::Statement::Return(_) => panic!("Return outside of a function body is not allowed"),
+            ast::Statement::Assignment(_) => {
+                panic!("Assignment outside of a function body is not allowed")
+            }
         }
     }
 
     ir::Module {
         id: module.id,
+        structs,
+        enums,
+        newtypes,
+        extern_functions,
         functions,
+        function_declarations,
+        modules,
+        closures: vec![],
+        warnings: vec![],
+        errors: vec![],
     }
 }
 
-pub fn typecheck_expression(expr: ast::Expression) -> ir::Expression {
-    match expr {
-        ast::Expression::Literal(literal) => ir::Expression::Literal(literal),
-        ast::Expression::Binary(bin_expr) => {
-            ir::Expression::Binary(Box::new(typecheck_binary_expression(*bin_expr)))
+/// Constants are initialized with a literal, so their value is known without
+/// running the program and can be written to interface files.
+fn typecheck_constant(constant: &ast::VariableDefinitionStatement, env: &Rc<Environment>) {
+    check_type(&constant.type_, env);
+
+    let is_literal = match &constant.initialize_expression {
+        ast::Expression::Literal(literal) => !matches!(literal, ast::Literal::Null(_)),
+        ast::Expression::Unary(unary) => {
+            matches!(unary.operator, ast::UnaryOperator::Minus)
+                && matches!(
+                    unary.expression,
+                    ast::Expression::Literal(ast::Literal::Int(..) | ast::Literal::Float(..))
+                )
         }
-        ast::Expression::Block(block) => ir::Expression::Block(Box::new(typecheck_block(*block))),
-        _ => todo!(),
+        _ => false,
+    };
+    if !is_literal {
+        panic!(
+            "constant {} must be initialized with a literal",
+            constant.id.value
+        );
+    }
+
+    let value = typecheck_expression(constant.initialize_expression.clone(), Rc::clone(env));
+    let value = coerce_expression(value, &constant.type_);
+    if value.type_() != constant.type_ {
+        panic!(
+            "Initialize expression for {} differs in type. Expected: {:?}, Found: {:?}.",
+            constant.id.value,
+            constant.type_,
+            value.type_()
+        );
     }
 }
 
-pub fn typecheck_binary_expression(bin_expr: ast::BinaryExpression) -> ir::BinaryExpression {
-    let lhs = typecheck_expression(bin_expr.left);
-    let rhs = typecheck_expression(bin_expr.right);
+/// The value of the constant `id`, if it names one, with the type the
+/// constant is declared with.
+fn constant_value(id: &ast::Identifier, env: &Rc<Environment>) -> Option<ir::Expression> {
+    let value = env.get_constant(id)?.clone();
+    let type_ = env.get(id).unwrap().clone();
 
-    ir::BinaryExpression {
-        type_: lhs.type_(),
-        operator: bin_expr.operator,
-        lhs,
-        rhs,
+    Some(coerce_expression(
+        typecheck_expression(value, Rc::clone(env)),
+        &type_,
+    ))
+}
+
+pub fn typecheck_struct_definition(
+    struct_: ast::StructDefinitionStatement,
+    env: Rc<Environment>,
+) -> ir::StructDefinition {
+    for (index, (id, type_)) in struct_.fields.iter().enumerate() {
+        if struct_.fields[..index].iter().any(|(other, _)| other == id) {
+            panic!(
+                "field {} of struct {} is defined more than once",
+                id.value, struct_.id.value
+            );
+        }
+
+        check_type(type_, &env);
+
+        if contains_type(type_, &struct_.id, &env, &mut vec![]) {
+            panic!(
+                "struct {} contains itself and would have infinite size",
+                struct_.id.value
+            );
+        }
+    }
+
+    ir::StructDefinition {
+        id: struct_.id,
+        fields: struct_.fields,
     }
 }
 
-pub fn typecheck_block(block: ast::BlockExpression) -> ir::BlockExpression {
-    let return_expression = match block.return_expression {
-        Some(expr) => typecheck_expression(expr),
-        _ => todo!(),
+pub fn typecheck_trait_definition(trait_: &ast::TraitDefinitionStatement, env: &Environment) {
+    // `Self` stands for every type implementing the trait, any type will do
+    // to check the others.
+    let substitution = generics::Substitution::self_type(&Type::Tuple(vec![]));
+
+    for (index, method) in trait_.methods.iter().enumerate() {
+        if trait_.methods[..index]
+            .iter()
+            .any(|other| other.id == method.id)
+        {
+            panic!(
+                "method {} of trait {} is defined more than once",
+                method.id.value, trait_.id.value
+            );
+        }
+
+        match method.parameters.first() {
+            Some((id, _type)) if id.value == "self" => {}
+            _ => panic!(
+                "method {} of trait {} must take self as its first parameter",
+                method.id.value, trait_.id.value
+            ),
+        }
+
+        for (_id, type_) in &method.parameters {
+            check_type(&substitution.type_(type_), env);
+        }
+        check_type(&substitution.type_(&method.return_type), env);
+    }
+}
+
+/// Typechecks the methods of an impl as functions named like
+/// `<i32 as Add>::add`, checking they are those of the trait.
+pub fn typecheck_impl(
+    impl_: ast::ImplStatement,
+    env: Rc<Environment>,
+) -> (Vec<ir::Function>, Vec<ir::FunctionDeclaration>) {
+    let trait_id = match &impl_.trait_ {
+        Some(trait_id) => trait_id,
+        None => return typecheck_inherent_impl(impl_, env),
+    };
+    let trait_ = match env.get_trait(trait_id) {
+        Some(trait_) => trait_.clone(),
+        None => panic!("unknown trait {}", trait_id.value),
     };
 
-    ir::BlockExpression {
-        type_: return_expression.type_().clone(),
-        return_expression,
+    check_type(&impl_.type_, &env);
+
+    let impls = env.impls_for(&impl_.type_);
+    if impls
+        .iter()
+        .filter(|i| i.trait_.as_ref() == Some(trait_id))
+        .count()
+        > 1
+    {
+        panic!(
+            "conflicting implementations of trait {} for type {}",
+            trait_.id.value, impl_.type_
+        );
+    }
+
+    let signatures = impl_.signatures();
+    for method in &trait_.methods {
+        if !signatures.iter().any(|(id, _, _)| **id == method.id) {
+            panic!(
+                "impl of trait {} for type {} is missing method {}",
+                trait_.id.value, impl_.type_, method.id.value
+            );
+        }
+    }
+
+    let substitution = generics::Substitution::self_type(&impl_.type_);
+    let signature = |parameters: &[(ast::Identifier, Type)], return_type: &Type| Type::Function {
+        parameters: parameters
+            .iter()
+            .map(|(_id, type_)| substitution.type_(type_))
+            .collect(),
+        return_type: Box::new(substitution.type_(return_type)),
+    };
+
+    for (index, (id, parameters, return_type)) in signatures.iter().enumerate() {
+        if signatures[..index].iter().any(|(other, _, _)| other == id) {
+            panic!(
+                "method {} of the impl of trait {} for type {} is defined more than once",
+                id.value, trait_.id.value, impl_.type_
+            );
+        }
+
+        let method = match trait_.methods.iter().find(|m| m.id == **id) {
+            Some(method) => method,
+            None => panic!(
+                "method {} is not a member of trait {}",
+                id.value, trait_.id.value
+            ),
+        };
+
+        let expected = signature(&method.parameters, &method.return_type);
+        let found = signature(parameters, return_type);
+        if found != expected {
+            panic!(
+                "method {} of the impl of trait {} for type {} has type {}, the trait expects {}",
+                id.value, trait_.id.value, impl_.type_, found, expected
+            );
+        }
     }
+
+    // Other programs call the methods of traits through their interface.
+    let functions = impl_
+        .functions
+        .iter()
+        .map(|function| {
+            if !function.type_parameters.is_empty() {
+                panic!("method {} of an impl can not be generic", function.id.value);
+            }
+
+            let mut function = substitution.function(function);
+            function.id.value = generics::method_name(&impl_.type_, Some(trait_id), &function.id);
+            function.public = true;
+
+            typecheck_function(function, Rc::clone(&env))
+        })
+        .collect();
+
+    let declarations = impl_
+        .declarations
+        .iter()
+        .map(|function| {
+            method_declaration(
+                function,
+                &substitution,
+                generics::method_name(&impl_.type_, Some(trait_id), &function.id),
+                &env,
+            )
+        })
+        .collect();
+
+    (functions, declarations)
 }
 
-// struct TypeChecker {}
-//
-//
-// impl AstVisitor<Rc<Environment>, Type> for TypeChecker {
-//     type Return = Type;
-//     type Environment = Rc<Environment>;
-//
-//     fn visit_module_statement(
-//         &mut self,
-//         env: Self::Environment,
-//         m: &ModuleStatement,
-//     ) -> Self::Return {
-//         for statement in &m.statements {
-//             statement.accept(Rc::clone(&env), self);
-//         }
-//         Type::None
-//     }
-//
-//     fn visit_statement(&mut self, env: Self::Environment, s: &Statement) -> Self::Return {
-//         use Statement::*;
-//         match s {
-//             Module(m) => m.accept(env, self),
-//             ExternFunctionDeclaration(s) => s.accept(env, self),
-//             FunctionDefinition(s) => s.accept(env, self),
-//             VariableDefinition(s) => s.accept(env, self),
-//             Return(s) => s.accept(env, self),
-//         }
+/// Typechecks the methods and associated functions of a struct or enum as
+/// functions named like `<Point>::len`.
+fn typecheck_inherent_impl(
+    impl_: ast::ImplStatement,
+    env: Rc<Environment>,
+) -> (Vec<ir::Function>, Vec<ir::FunctionDeclaration>) {
+    match &impl_.type_ {
+        Type::Named(id) if env.get_type(id).is_some() => {}
+        Type::Named(id) => panic!("unknown type {}", id.value),
+        other => panic!(
+            "impl without a trait is only allowed for structs, enums and newtypes, found {}",
+            other
+        ),
+    }
+
+    let substitution = generics::Substitution::self_type(&impl_.type_);
+    let impls = env.impls_for(&impl_.type_);
+
+    let signatures = impl_.signatures();
+    for (index, (id, _parameters, _return_type)) in signatures.iter().enumerate() {
+        // Every impl without a trait adds to the same functions of the type.
+        let definitions = impls
+            .iter()
+            .filter(|i| i.trait_.is_none() && i.methods.contains_key(id))
+            .count();
+        if definitions > 1 || signatures[..index].iter().any(|(other, _, _)| other == id) {
+            panic!(
+                "function {} of type {} is defined more than once",
+                id.value, impl_.type_
+            );
+        }
+    }
+
+    let functions = impl_
+        .functions
+        .iter()
+        .map(|function| {
+            if !function.type_parameters.is_empty() {
+                panic!("method {} of an impl can not be generic", function.id.value);
+            }
+
+            let mut function = substitution.function(function);
+            function.id.value = generics::method_name(&impl_.type_, None, &function.id);
+
+            typecheck_function(function, Rc::clone(&env))
+        })
+        .collect();
+
+    let declarations = impl_
+        .declarations
+        .iter()
+        .map(|function| {
+            method_declaration(
+                function,
+                &substitution,
+                generics::method_name(&impl_.type_, None, &function.id),
+                &env,
+            )
+        })
+        .collect();
+
+    (functions, declarations)
+}
+
+/// A method of an impl in the interface of another program, compiled to the
+/// function named `id` there.
+fn method_declaration(
+    function: &ast::FunctionDeclarationStatement,
+    substitution: &generics::Substitution,
+    id: String,
+    env: &Environment,
+) -> ir::FunctionDeclaration {
+    let parameters: Vec<_> = function
+        .parameters
+        .iter()
+        .map(|(id, type_)| (id.clone(), substitution.type_(type_)))
+        .collect();
+    let return_type = substitution.type_(&function.return_type);
+
+    for (_id, type_) in &parameters {
+        check_type(type_, env);
+    }
+    check_type(&return_type, env);
+
+    ir::FunctionDeclaration {
+        id: ast::Identifier::from(id),
+        parameters,
+        return_type,
+        symbol: function.symbol.clone(),
+    }
+}
+
+/// `Type::function`, a function of an impl of a struct or enum without a
+/// trait.
+fn associated_function(
+    path: &[ast::Identifier],
+    env: &Environment,
+) -> Option<ir::IdentifierExpression> {
+    let (type_, name) = match path {
+        [type_, name] => (Type::Named(type_.clone()), name),
+        _ => return None,
+    };
+
+    env.impls_for(&type_)
+        .iter()
+        .filter(|i| i.trait_.is_none())
+        .find_map(|i| i.methods.get(name))
+        .map(|method| ir::IdentifierExpression {
+            type_: method.type_.clone(),
+            id: method.function.clone(),
+        })
+}
+
+pub fn typecheck_enum_definition(
+    enum_: ast::EnumDefinitionStatement,
+    env: Rc<Environment>,
+) -> ir::EnumDefinition {
+    for (index, (id, types)) in enum_.variants.iter().enumerate() {
+        if enum_.variants[..index].iter().any(|(other, _)| other == id) {
+            panic!(
+                "variant {} of enum {} is defined more than once",
+                id.value, enum_.id.value
+            );
+        }
+
+        for type_ in types {
+            check_type(type_, &env);
+
+            if contains_type(type_, &enum_.id, &env, &mut vec![]) {
+                panic!(
+                    "enum {} contains itself and would have infinite size",
+                    enum_.id.value
+                );
+            }
+        }
+    }
+
+    ir::EnumDefinition {
+        id: enum_.id,
+        variants: enum_.variants,
+    }
+}
+
+/// Panics if `type_` refers to a named type that is not defined.
+fn check_type(type_: &Type, env: &Environment) {
+    match type_ {
+        Type::Named(id) if env.get_type(id).is_none() => panic!("unknown type {}", id.value),
+        Type::Array { element_type, .. } => check_type(element_type, env),
+        Type::Tuple(types) => {
+            for type_ in types {
+                check_type(type_, env);
+            }
+        }
+        Type::Slice(element_type) => check_type(element_type, env),
+        Type::Pointer { pointee, .. } => check_type(pointee, env),
+        Type::Function {
+            parameters,
+            return_type,
+        } => {
+            for parameter in parameters {
+                check_type(parameter, env);
+            }
+            check_type(return_type, env);
+        }
+        _ => (),
+    }
+}
+
+/// Panics if `type_`, the type of `position` in an extern function, can not
+/// be passed to or returned from C.
+///
+/// Aggregates are passed in integer registers, see `llvm_c_abi_type` in
+/// `toyc`, so those containing floats, which C passes in floating-point
+/// registers, and those larger than two registers, which C passes in memory,
+/// are not supported.
+fn check_c_type(type_: &Type, position: &str, env: &Environment) {
+    if is_zero_sized(type_, env) {
+        panic!(
+            "{}: {} has no size and can not be passed to C",
+            position, type_
+        );
+    }
+    if contains_closure(type_, env) {
+        panic!(
+            "{}: {} contains a closure and can not be passed to C",
+            position, type_
+        );
+    }
+
+    if let Type::Named(_) | Type::Tuple(_) | Type::Slice(_) = without_newtypes(type_, env) {
+        if holds_float(type_, env) {
+            panic!(
+                "{}: {} contains floats and can not be passed to C by value",
+                position, type_
+            );
+        }
+        if c_layout(type_, env).0 > 16 {
+            panic!(
+                "{}: {} is larger than 16 bytes and can not be passed to C by value",
+                position, type_
+            );
+        }
+    }
+}
+
+fn round_up(size: u64, alignment: u64) -> u64 {
+    size.div_ceil(alignment) * alignment
+}
+
+/// `type_` with the newtypes wrapping it replaced by what they wrap, as it is
+/// compiled.
+fn without_newtypes<'a>(type_: &'a Type, env: &'a Environment) -> &'a Type {
+    match type_ {
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Newtype(type_)) => without_newtypes(type_, env),
+            _ => type_,
+        },
+        _ => type_,
+    }
+}
+
+/// Whether values of `type_` hold floats outside of enum payloads, which are
+/// stored as integers.
+fn holds_float(type_: &Type, env: &Environment) -> bool {
+    match type_ {
+        Type::Float(_) => true,
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Struct(fields)) => {
+                fields.iter().any(|(_id, type_)| holds_float(type_, env))
+            }
+            Some(TypeDefinition::Newtype(type_)) => holds_float(type_, env),
+            Some(TypeDefinition::Enum(_) | TypeDefinition::Parameter) | None => false,
+        },
+        Type::Array { element_type, .. } => holds_float(element_type, env),
+        Type::Tuple(types) => types.iter().any(|type_| holds_float(type_, env)),
+        _ => false,
+    }
+}
+
+/// The size and alignment in bytes of values of `type_` on a 64-bit target,
+/// laid out like the backend lays them out.
+fn c_layout(type_: &Type, env: &Environment) -> (u64, u64) {
+    let scalar = |bits: u32| {
+        let size = u64::from(bits).div_ceil(8);
+        (size, size.min(8))
+    };
+    let record = |types: &mut dyn Iterator<Item = &Type>| {
+        let (mut size, mut alignment) = (0, 1);
+        for type_ in types {
+            let (field_size, field_alignment) = c_layout(type_, env);
+            size = round_up(size, field_alignment) + field_size;
+            alignment = alignment.max(field_alignment);
+        }
+        (round_up(size, alignment), alignment)
+    };
+
+    match type_ {
+        Type::Boolean => (1, 1),
+        Type::Char => (4, 4),
+        Type::Int(type_) => scalar(type_.bits()),
+        Type::UInt(type_) => scalar(type_.bits()),
+        Type::Float(type_) => scalar(type_.bits()),
+        Type::Pointer { .. } => (8, 8),
+        Type::Function { .. } | Type::Slice(_) => (16, 8),
+        Type::Array { element_type, size } => {
+            let (element_size, alignment) = c_layout(element_type, env);
+            (element_size * size, alignment)
+        }
+        Type::Tuple(types) => record(&mut types.iter()),
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Struct(fields)) => {
+                record(&mut fields.iter().map(|(_id, type_)| type_))
+            }
+            Some(TypeDefinition::Newtype(type_)) => c_layout(type_, env),
+            // A tag followed by the largest payload, as aligned as the most
+            // aligned payload.
+            Some(TypeDefinition::Enum(variants)) => {
+                let tag = match variants.len() {
+                    0..=0x100 => 1,
+                    0x101..=0x1_0000 => 2,
+                    _ => 4,
+                };
+                let (payload_size, payload_alignment) = variants
+                    .iter()
+                    .map(|(_id, types)| record(&mut types.iter()))
+                    .fold(
+                        (0, 1),
+                        |(size, alignment), (payload_size, payload_alignment)| {
+                            (size.max(payload_size), alignment.max(payload_alignment))
+                        },
+                    );
+
+                if payload_size == 0 {
+                    (tag, tag)
+                } else {
+                    let alignment = payload_alignment.max(tag);
+                    let offset = round_up(tag, payload_alignment);
+                    let size = offset + round_up(payload_size, payload_alignment);
+                    (round_up(size, alignment), alignment)
+                }
+            }
+            Some(TypeDefinition::Parameter) | None => (0, 1),
+        },
+        Type::None => (0, 1),
+    }
+}
+
+/// Whether values of `type_` hold a closure, which C has no way to call.
+/// Pointers are opaque to C, so what they point to does not matter.
+fn contains_closure(type_: &Type, env: &Environment) -> bool {
+    match type_ {
+        Type::Function { .. } => true,
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Struct(fields)) => fields
+                .iter()
+                .any(|(_id, type_)| contains_closure(type_, env)),
+            Some(TypeDefinition::Enum(variants)) => variants
+                .iter()
+                .flat_map(|(_id, types)| types)
+                .any(|type_| contains_closure(type_, env)),
+            Some(TypeDefinition::Newtype(type_)) => contains_closure(type_, env),
+            Some(TypeDefinition::Parameter) | None => false,
+        },
+        Type::Array { element_type, .. } => contains_closure(element_type, env),
+        Type::Tuple(types) => types.iter().any(|type_| contains_closure(type_, env)),
+        _ => false,
+    }
+}
+
+/// Whether values of `type_` take no memory. Enums always store a tag.
+fn is_zero_sized(type_: &Type, env: &Environment) -> bool {
+    match type_ {
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Struct(fields)) => {
+                fields.iter().all(|(_id, type_)| is_zero_sized(type_, env))
+            }
+            Some(TypeDefinition::Newtype(type_)) => is_zero_sized(type_, env),
+            Some(TypeDefinition::Enum(_) | TypeDefinition::Parameter) | None => false,
+        },
+        Type::Array { element_type, size } => *size == 0 || is_zero_sized(element_type, env),
+        Type::Tuple(types) => types.iter().all(|type_| is_zero_sized(type_, env)),
+        _ => false,
+    }
+}
+
+/// Whether values of `type_` store a value of the named type `target` inline.
+fn contains_type(
+    type_: &Type,
+    target: &ast::Identifier,
+    env: &Environment,
+    visited: &mut Vec<ast::Identifier>,
+) -> bool {
+    match type_ {
+        Type::Named(id) if id == target => true,
+        Type::Named(id) if !visited.contains(id) => {
+            visited.push(id.clone());
+
+            match env.get_type(id) {
+                Some(TypeDefinition::Struct(fields)) => fields
+                    .iter()
+                    .any(|(_id, type_)| contains_type(type_, target, env, visited)),
+                Some(TypeDefinition::Enum(variants)) => variants
+                    .iter()
+                    .flat_map(|(_id, types)| types)
+                    .any(|type_| contains_type(type_, target, env, visited)),
+                Some(TypeDefinition::Newtype(type_)) => contains_type(type_, target, env, visited),
+                Some(TypeDefinition::Parameter) | None => false,
+            }
+        }
+        Type::Array { element_type, .. } => contains_type(element_type, target, env, visited),
+        Type::Tuple(types) => types
+            .iter()
+            .any(|type_| contains_type(type_, target, env, visited)),
+        _ => false,
+    }
+}
+
+/// Whether `type_` names the newtype `target`, directly or through the
+/// newtypes it names. Newtypes are compiled as the type they wrap, which
+/// would then have no end, even behind a pointer.
+fn newtype_refers_to(
+    type_: &Type,
+    target: &ast::Identifier,
+    env: &Environment,
+    visited: &mut Vec<ast::Identifier>,
+) -> bool {
+    match type_ {
+        Type::Named(id) if id == target => true,
+        Type::Named(id) if !visited.contains(id) => {
+            visited.push(id.clone());
+
+            match env.get_type(id) {
+                Some(TypeDefinition::Newtype(type_)) => {
+                    newtype_refers_to(type_, target, env, visited)
+                }
+                _ => false,
+            }
+        }
+        Type::Function {
+            parameters,
+            return_type,
+        } => parameters
+            .iter()
+            .chain(std::iter::once(&**return_type))
+            .any(|type_| newtype_refers_to(type_, target, env, visited)),
+        Type::Array { element_type, .. } | Type::Slice(element_type) => {
+            newtype_refers_to(element_type, target, env, visited)
+        }
+        Type::Pointer { pointee, .. } => newtype_refers_to(pointee, target, env, visited),
+        Type::Tuple(types) => types
+            .iter()
+            .any(|type_| newtype_refers_to(type_, target, env, visited)),
+        _ => false,
+    }
+}
+
+/// Tuple elements are fields named by their position.
+fn struct_fields(type_: &Type, env: &Environment) -> Option<Vec<(ast::Identifier, Type)>> {
+    match type_ {
+        Type::Tuple(types) => Some(
+            types
+                .iter()
+                .enumerate()
+                .map(|(index, type_)| {
+                    let id = ast::Identifier::from(index.to_string());
+                    (id, type_.clone())
+                })
+                .collect(),
+        ),
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Struct(fields)) => Some(fields.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The type the newtype `type_` wraps.
+fn newtype_of(type_: &Type, env: &Environment) -> Option<Type> {
+    match type_ {
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Newtype(type_)) => Some(type_.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn enum_variants(type_: &Type, env: &Environment) -> Option<Vec<(ast::Identifier, Vec<Type>)>> {
+    match type_ {
+        Type::Named(id) => match env.get_type(id) {
+            Some(TypeDefinition::Enum(variants)) => Some(variants.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn display_path(path: &[ast::Identifier]) -> String {
+    path.iter()
+        .map(|segment| segment.value.as_str())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn display_ids(ids: &[ast::Identifier]) -> String {
+    ids.iter()
+        .map(|id| id.value.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Resolves `Enum::Variant` to the enum type, the variant's tag and the
+/// types of its fields.
+/// Paths name either an enum variant, `Enum::Variant`, or an item of a module,
+/// `module::item`.
+fn is_variant_path(path: &[ast::Identifier], env: &Environment) -> bool {
+    match path {
+        [enum_, _variant] => matches!(env.get_type(enum_), Some(TypeDefinition::Enum(_))),
+        _ => false,
+    }
+}
+
+/// Items of nested modules are in the environment under their qualified name.
+fn resolve_item(path: &[ast::Identifier], env: &Environment) -> ast::Identifier {
+    let id = ast::Identifier::from(display_path(path));
+
+    match env.get(&id) {
+        Some(_) => id,
+        None => panic!("unresolved path {}", id.value),
+    }
+}
+
+fn resolve_variant(path: &[ast::Identifier], env: &Environment) -> (Type, usize, Vec<Type>) {
+    let type_ = match path {
+        [enum_, _variant] => Type::Named(enum_.clone()),
+        _ => panic!("unresolved path {}", display_path(path)),
+    };
+
+    let variants = match enum_variants(&type_, env) {
+        Some(variants) => variants,
+        None => panic!("unresolved path {}", display_path(path)),
+    };
+
+    match variants.iter().position(|(id, _)| *id == path[1]) {
+        Some(tag) => (type_, tag, variants[tag].1.clone()),
+        None => panic!(
+            "enum {} has no variant named {}",
+            path[0].value, path[1].value
+        ),
+    }
+}
+
+pub fn typecheck_function(
+    function: ast::FunctionDefinitionStatement,
+    env: Rc<Environment>,
+) -> ir::Function {
+    check_type(&function.return_type, &env);
+
+    let mut function_env = Environment::put(env);
+    for (id, type_) in &function.parameters {
+        check_type(type_, &function_env);
+        function_env.insert(id.clone(), type_.clone());
+    }
+
+    let body = typecheck_expression(function.body, Rc::new(function_env));
+    let body = coerce_expression(body, &function.return_type);
+
+    if body.type_() != function.return_type {
+        panic!(
+            concat!(
+                "In function named {}: expected return type {:?}",
+                " differs from function's body return type {:?}"
+            ),
+            function.id.value,
+            function.return_type,
+            body.type_(),
+        );
+    }
+
+    ir::Function {
+        id: function.id,
+        public: function.public,
+        parameters: function.parameters,
+        return_type: function.return_type,
+        body,
+    }
+}
+
+/// Typechecks generic function `function` once, its type parameters standing
+/// for types that only have the methods of their bounds, so its body is
+/// well-typed for every type argument satisfying them.
+fn check_generic_function(function: ast::FunctionDefinitionStatement, env: &Rc<Environment>) {
+    let mut generic_env = Environment::generic_body(Rc::clone(env));
+
+    for parameter in &function.type_parameters {
+        let type_ = Type::Named(parameter.id.clone());
+        generic_env.insert_type(parameter.id.clone(), TypeDefinition::Parameter);
+
+        for bound in &parameter.bounds {
+            match env.get_trait(bound) {
+                Some(trait_) => generic_env.insert_impl(Impl::bound(&type_, trait_)),
+                None => panic!("unknown trait {}", bound.value),
+            }
+        }
+    }
+
+    typecheck_function(function, Rc::new(generic_env));
+}
+
+/// Typechecks the body of a closure as a function of the program, its type
+/// is that of a function returning what its body evaluates to.
+pub fn typecheck_closure(
+    closure: ast::ClosureExpression,
+    env: Rc<Environment>,
+) -> ir::ClosureExpression {
+    let captures: Vec<_> = closure
+        .captures
+        .into_iter()
+        .map(|id| match env.get(&id) {
+            Some(type_) => ir::IdentifierExpression {
+                type_: type_.clone(),
+                id,
+            },
+            None => panic!("Missing id: {:?}", id),
+        })
+        .collect();
+
+    // Captures are copies, assigning them would not change the variables.
+    let mut closure_env = Environment::put(Rc::clone(&env));
+    for capture in &captures {
+        closure_env.insert(capture.id.clone(), capture.type_.clone());
+    }
+    for (id, type_) in &closure.parameters {
+        check_type(type_, &closure_env);
+        closure_env.insert(id.clone(), type_.clone());
+    }
+
+    let body = typecheck_expression(closure.body, Rc::new(closure_env));
+    // Unsuffixed integer literals are `i32` when nothing else gives them a
+    // type, as when inferring type arguments.
+    let body = match body.type_() {
+        Type::None => coerce_expression(body, &Type::Int(IntType::I32)),
+        _ => body,
+    };
+    let return_type = body.type_();
+
+    let function = env.next_closure_id();
+    env.insert_closure(ir::Closure {
+        id: function.clone(),
+        captures: captures
+            .iter()
+            .map(|capture| (capture.id.clone(), capture.type_.clone()))
+            .collect(),
+        parameters: closure.parameters.clone(),
+        return_type: return_type.clone(),
+        body,
+    });
+
+    ir::ClosureExpression {
+        type_: Type::Function {
+            parameters: closure
+                .parameters
+                .into_iter()
+                .map(|(_id, type_)| type_)
+                .collect(),
+            return_type: Box::new(return_type),
+        },
+        function,
+        captures,
+    }
+}
+
+pub fn typecheck_expression(expr: ast::Expression, env: Rc<Environment>) -> ir::Expression {
+    match expr {
+        ast::Expression::Literal(literal) => {
+            check_suffixed_literal(&literal, false);
+            ir::Expression::Literal(literal)
+        }
+        ast::Expression::Identifier(id) => match env.get(&id) {
+            Some(_) if env.is_extern(&id) => {
+                panic!("extern function {} can only be called", id.value)
+            }
+            Some(_) if env.get_constant(&id).is_some() => constant_value(&id, &env).unwrap(),
+            Some(type_) => ir::Expression::Identifier(ir::IdentifierExpression {
+                type_: type_.clone(),
+                id,
+            }),
+            None if env.get_generic(&id).is_some() => {
+                panic!("generic function {} can only be called", id.value)
+            }
+            None if newtype_of(&Type::Named(id.clone()), &env).is_some() => {
+                panic!("newtype {} can only be called", id.value)
+            }
+            None => panic!("Missing id: {:?}", id),
+        },
+        ast::Expression::Unary(unary) => {
+            ir::Expression::Unary(Box::new(typecheck_unary_expression(*unary, env)))
+        }
+        ast::Expression::Binary(bin_expr) => {
+            ir::Expression::Binary(Box::new(typecheck_binary_expression(*bin_expr, env)))
+        }
+        ast::Expression::Block(block) => {
+            ir::Expression::Block(Box::new(typecheck_block(*block, env)))
+        }
+        ast::Expression::Match(match_) => {
+            ir::Expression::Match(Box::new(typecheck_match(*match_, env)))
+        }
+        ast::Expression::Cast(cast) => ir::Expression::Cast(Box::new(typecheck_cast(*cast, env))),
+        ast::Expression::Array(array) => {
+            ir::Expression::Array(Box::new(typecheck_array(*array, env)))
+        }
+        ast::Expression::Access(access) => {
+            ir::Expression::Access(Box::new(typecheck_access(*access, env)))
+        }
+        ast::Expression::Call(call) => match &call.callee {
+            ast::Expression::Path(path) if associated_function(&path.segments, &env).is_some() => {
+                let callee = associated_function(&path.segments, &env).unwrap();
+                let arguments = call
+                    .arguments
+                    .into_iter()
+                    .map(|argument| typecheck_expression(argument, Rc::clone(&env)))
+                    .collect();
+
+                ir::Expression::Call(Box::new(call_value(
+                    ir::Expression::Identifier(callee),
+                    arguments,
+                )))
+            }
+            ast::Expression::Path(path) if is_variant_path(&path.segments, &env) => {
+                ir::Expression::Variant(Box::new(typecheck_variant(
+                    &path.segments,
+                    call.arguments,
+                    env,
+                )))
+            }
+            ast::Expression::Path(path) => {
+                let callee = ast::Expression::Identifier(resolve_item(&path.segments, &env));
+                let call = ast::CallExpression {
+                    callee,
+                    arguments: call.arguments,
+                    span: call.span,
+                };
+                ir::Expression::Call(Box::new(typecheck_call(call, env)))
+            }
+            ast::Expression::Identifier(id)
+                if env.get(id).is_none() && env.get_generic(id).is_some() =>
+            {
+                let id = id.clone();
+                ir::Expression::Call(Box::new(typecheck_generic_call(
+                    id,
+                    None,
+                    call.arguments,
+                    call.span,
+                    env,
+                )))
+            }
+            ast::Expression::Instantiation(_) => {
+                let instantiation = match call.callee {
+                    ast::Expression::Instantiation(instantiation) => *instantiation,
+                    _ => unreachable!(),
+                };
+                let id = match instantiation.expression {
+                    ast::Expression::Identifier(id) => id,
+                    ast::Expression::Path(path) => {
+                        ast::Identifier::from(display_path(&path.segments))
+                    }
+                    _ => unreachable!(),
+                };
+
+                ir::Expression::Call(Box::new(typecheck_generic_call(
+                    id,
+                    Some(instantiation.type_arguments),
+                    call.arguments,
+                    call.span,
+                    env,
+                )))
+            }
+            ast::Expression::Identifier(id)
+                if (id.value == "print" || id.value == "println") && env.get(id).is_none() =>
+            {
+                let newline = id.value == "println";
+                ir::Expression::Print(Box::new(typecheck_print(newline, call.arguments, env)))
+            }
+            ast::Expression::Identifier(id) if id.value == "offset" && env.get(id).is_none() => {
+                ir::Expression::Offset(Box::new(typecheck_offset(call.arguments, env)))
+            }
+            ast::Expression::Identifier(id) if id.value == "len" && env.get(id).is_none() => {
+                ir::Expression::Length(Box::new(typecheck_length(call.arguments, env)))
+            }
+            ast::Expression::Identifier(id)
+                if env.get(id).is_none()
+                    && newtype_of(&Type::Named(id.clone()), &env).is_some() =>
+            {
+                let id = id.clone();
+                ir::Expression::Newtype(Box::new(typecheck_newtype(id, call.arguments, env)))
+            }
+            ast::Expression::Field(_) => {
+                let field = match call.callee {
+                    ast::Expression::Field(field) => *field,
+                    _ => unreachable!(),
+                };
+
+                ir::Expression::Call(Box::new(typecheck_method_call(field, call.arguments, env)))
+            }
+            _ => ir::Expression::Call(Box::new(typecheck_call(*call, env))),
+        },
+        ast::Expression::Path(path) if associated_function(&path.segments, &env).is_some() => {
+            ir::Expression::Identifier(associated_function(&path.segments, &env).unwrap())
+        }
+        ast::Expression::Path(path) if is_variant_path(&path.segments, &env) => {
+            ir::Expression::Variant(Box::new(typecheck_variant(&path.segments, vec![], env)))
+        }
+        ast::Expression::Path(path) => typecheck_expression(
+            ast::Expression::Identifier(resolve_item(&path.segments, &env)),
+            env,
+        ),
+        ast::Expression::Tuple(tuple) => {
+            ir::Expression::Tuple(Box::new(typecheck_tuple(*tuple, env)))
+        }
+        ast::Expression::Struct(struct_) => {
+            ir::Expression::Struct(Box::new(typecheck_struct(*struct_, env)))
+        }
+        ast::Expression::Field(field) => typecheck_field(*field, env),
+        ast::Expression::Closure(closure) => {
+            ir::Expression::Closure(Box::new(typecheck_closure(*closure, env)))
+        }
+        ast::Expression::Instantiation(instantiation) => match instantiation.expression {
+            ast::Expression::Identifier(id) => {
+                panic!("generic function {} can only be called", id.value)
+            }
+            ast::Expression::Path(path) => panic!(
+                "generic function {} can only be called",
+                display_path(&path.segments)
+            ),
+            _ => unreachable!(),
+        },
+        _ => todo!(),
+    }
+}
+
+/// Gives the unsuffixed integer literals of `expr` the type its context expects.
+///
+/// Expressions made only of unsuffixed literals have type `Type::None` until
+/// they are used somewhere a concrete type is known.
+pub fn coerce_expression(expr: ir::Expression, type_: &Type) -> ir::Expression {
+    // Unsuffixed float literals are already `f64`, keep them unsuffixed so
+    // they can still become `f32` in an enclosing context.
+    if let Type::Float(float_type @ FloatType::F32) = type_ {
+        if is_unsuffixed_float(&expr) {
+            return coerce_float_literals(expr, float_type);
+        }
+    }
+
+    let has_float_literals = contains_float(type_) && contains_unsuffixed_float(&expr);
+    if !is_untyped(&expr.type_()) && !has_float_literals || is_untyped(type_) {
+        return expr;
+    }
+
+    match expr {
+        ir::Expression::Literal(ast::Literal::Int(value, LiteralIntType::Unsufixed)) => {
+            coerce_int_literal(value, false, type_)
+        }
+        ir::Expression::Literal(ast::Literal::Null(None)) => match type_ {
+            Type::Pointer { .. } => {
+                ir::Expression::Literal(ast::Literal::Null(Some(type_.clone())))
+            }
+            _ => panic!("null can only be used as a pointer, found {:?}", type_),
+        },
+        ir::Expression::Unary(unary) => {
+            let unary = *unary;
+            let expression = match (unary.operator, unary.expression) {
+                (
+                    ast::UnaryOperator::Minus,
+                    ir::Expression::Literal(ast::Literal::Int(value, LiteralIntType::Unsufixed)),
+                ) => coerce_int_literal(value, true, type_),
+                (_, expression) => coerce_expression(expression, type_),
+            };
+
+            if let (ast::UnaryOperator::Minus, Type::UInt(_)) = (unary.operator, type_) {
+                panic!("can not negate unsigned type {:?}", type_);
+            }
+
+            ir::Expression::Unary(Box::new(ir::UnaryExpression {
+                type_: type_.clone(),
+                operator: unary.operator,
+                expression,
+            }))
+        }
+        ir::Expression::Binary(bin_expr) => {
+            let bin_expr = *bin_expr;
+            ir::Expression::Binary(Box::new(ir::BinaryExpression {
+                type_: type_.clone(),
+                operator: bin_expr.operator,
+                lhs: coerce_expression(bin_expr.lhs, type_),
+                rhs: coerce_expression(bin_expr.rhs, type_),
+            }))
+        }
+        ir::Expression::Tuple(tuple) => {
+            let types = match type_ {
+                Type::Tuple(types) if types.len() == tuple.elements.len() => types,
+                _ => return ir::Expression::Tuple(tuple),
+            };
+
+            let elements: Vec<ir::Expression> = tuple
+                .elements
+                .into_iter()
+                .zip(types)
+                .map(|(element, type_)| coerce_expression(element, type_))
+                .collect();
+
+            ir::Expression::Tuple(Box::new(ir::TupleExpression {
+                type_: Type::Tuple(elements.iter().map(|element| element.type_()).collect()),
+                elements,
+            }))
+        }
+        ir::Expression::Block(block) => {
+            let block = *block;
+            ir::Expression::Block(Box::new(ir::BlockExpression {
+                type_: type_.clone(),
+                statements: block.statements,
+                return_expression: coerce_expression(block.return_expression, type_),
+            }))
+        }
+        ir::Expression::Array(array) => {
+            let (element_type, size) = match type_ {
+                Type::Array { element_type, size } => (element_type, *size),
+                _ => return ir::Expression::Array(array),
+            };
+
+            let length = match &array.elements {
+                ir::ArrayElements::List(elements) => elements.len() as u64,
+                ir::ArrayElements::Repeat(_, length) => *length,
+            };
+            if length != size {
+                panic!("array of {} elements can not be used as {}", length, type_);
+            }
+
+            let elements = match array.elements {
+                ir::ArrayElements::List(elements) => ir::ArrayElements::List(
+                    elements
+                        .into_iter()
+                        .map(|element| coerce_expression(element, element_type))
+                        .collect(),
+                ),
+                ir::ArrayElements::Repeat(element, size) => {
+                    ir::ArrayElements::Repeat(coerce_expression(element, element_type), size)
+                }
+            };
+
+            ir::Expression::Array(Box::new(ir::ArrayExpression {
+                type_: type_.clone(),
+                elements,
+            }))
+        }
+        ir::Expression::Access(access) => {
+            let access = *access;
+            let base_type = match access.base.type_() {
+                Type::Array { size, .. } => Type::Array {
+                    element_type: Box::new(type_.clone()),
+                    size,
+                },
+                _ => {
+                    return ir::Expression::Access(Box::new(access));
+                }
+            };
+
+            ir::Expression::Access(Box::new(ir::AccessExpression {
+                type_: type_.clone(),
+                base: coerce_expression(access.base, &base_type),
+                offset: access.offset,
+            }))
+        }
+        ir::Expression::Match(match_) => {
+            let match_ = *match_;
+            ir::Expression::Match(Box::new(ir::MatchExpression {
+                type_: type_.clone(),
+                scrutinee: match_.scrutinee,
+                arms: match_
+                    .arms
+                    .into_iter()
+                    .map(|arm| ir::MatchArm {
+                        patterns: arm.patterns,
+                        bindings: arm.bindings,
+                        expression: coerce_expression(arm.expression, type_),
+                    })
+                    .collect(),
+            }))
+        }
+        expr => expr,
+    }
+}
+
+fn coerce_int_literal(value: u128, negated: bool, type_: &Type) -> ir::Expression {
+    let literal_type = match type_ {
+        Type::Int(t) => LiteralIntType::Signed(t.clone()),
+        Type::UInt(t) => LiteralIntType::Unsigned(t.clone()),
+        _ => panic!("integer literal {} can not be used as {:?}", value, type_),
+    };
+    literal_bits(negated, value, type_);
+    ir::Expression::Literal(ast::Literal::Int(value, literal_type))
+}
+
+/// Whether `expr` is arithmetic on unsuffixed float literals only, which
+/// default to `f64` but may be used as `f32`.
+fn is_unsuffixed_float(expr: &ir::Expression) -> bool {
+    match expr {
+        ir::Expression::Literal(ast::Literal::Float(_, LiteralFloatType::Unsufixed)) => true,
+        ir::Expression::Unary(unary) => {
+            matches!(unary.operator, ast::UnaryOperator::Minus)
+                && is_unsuffixed_float(&unary.expression)
+        }
+        ir::Expression::Binary(bin_expr) => {
+            matches!(bin_expr.type_, Type::Float(_))
+                && is_unsuffixed_float(&bin_expr.lhs)
+                && is_unsuffixed_float(&bin_expr.rhs)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the elements of an aggregate or the result of a block or match
+/// are unsuffixed float literals.
+fn contains_unsuffixed_float(expr: &ir::Expression) -> bool {
+    match expr {
+        ir::Expression::Tuple(tuple) => tuple.elements.iter().any(contains_unsuffixed_float),
+        ir::Expression::Array(array) => match &array.elements {
+            ir::ArrayElements::List(elements) => elements.iter().any(contains_unsuffixed_float),
+            ir::ArrayElements::Repeat(element, _) => contains_unsuffixed_float(element),
+        },
+        ir::Expression::Block(block) => contains_unsuffixed_float(&block.return_expression),
+        ir::Expression::Match(match_) => match_
+            .arms
+            .iter()
+            .any(|arm| contains_unsuffixed_float(&arm.expression)),
+        expr => is_unsuffixed_float(expr),
+    }
+}
+
+fn contains_float(type_: &Type) -> bool {
+    match type_ {
+        Type::Float(_) => true,
+        Type::Array { element_type, .. } => contains_float(element_type),
+        Type::Tuple(types) => types.iter().any(contains_float),
+        _ => false,
+    }
+}
+
+fn coerce_float_literals(expr: ir::Expression, float_type: &FloatType) -> ir::Expression {
+    match expr {
+        ir::Expression::Literal(ast::Literal::Float(value, _)) => {
+            let literal =
+                ast::Literal::Float(value, LiteralFloatType::Suffixed(float_type.clone()));
+            check_suffixed_literal(&literal, false);
+            ir::Expression::Literal(literal)
+        }
+        ir::Expression::Unary(unary) => {
+            let unary = *unary;
+            ir::Expression::Unary(Box::new(ir::UnaryExpression {
+                type_: Type::Float(float_type.clone()),
+                operator: unary.operator,
+                expression: coerce_float_literals(unary.expression, float_type),
+            }))
+        }
+        ir::Expression::Binary(bin_expr) => {
+            let bin_expr = *bin_expr;
+            ir::Expression::Binary(Box::new(ir::BinaryExpression {
+                type_: Type::Float(float_type.clone()),
+                operator: bin_expr.operator,
+                lhs: coerce_float_literals(bin_expr.lhs, float_type),
+                rhs: coerce_float_literals(bin_expr.rhs, float_type),
+            }))
+        }
+        expr => expr,
+    }
+}
+
+/// Whether `type_` still depends on unsuffixed integer literals.
+fn is_untyped(type_: &Type) -> bool {
+    match type_ {
+        Type::None => true,
+        Type::Array { element_type, .. } => is_untyped(element_type),
+        Type::Tuple(types) => types.iter().any(is_untyped),
+        _ => false,
+    }
+}
+
+fn is_integer(type_: &Type) -> bool {
+    matches!(type_, Type::Int(_) | Type::UInt(_))
+}
+
+fn is_float(type_: &Type) -> bool {
+    matches!(type_, Type::Float(_))
+}
+
+pub fn typecheck_unary_expression(
+    unary: ast::UnaryExpression,
+    env: Rc<Environment>,
+) -> ir::UnaryExpression {
+    if let ast::UnaryOperator::Deref
+    | ast::UnaryOperator::AddressOf
+    | ast::UnaryOperator::MutAddressOf = unary.operator
+    {
+        return typecheck_pointer_operation(unary, env);
+    }
+
+    // A negated literal is checked as a whole, `-128i8` fits but `128i8` does not.
+    let expression = match (unary.operator, unary.expression) {
+        (ast::UnaryOperator::Minus, ast::Expression::Literal(literal)) => {
+            check_suffixed_literal(&literal, true);
+            ir::Expression::Literal(literal)
+        }
+        (_, expression) => typecheck_expression(expression, env),
+    };
+    let type_ = expression.type_();
+
+    let valid = match unary.operator {
+        ast::UnaryOperator::Minus => matches!(type_, Type::Int(_) | Type::Float(_) | Type::None),
+        ast::UnaryOperator::BitNot => is_integer(&type_) || type_ == Type::None,
+        ast::UnaryOperator::Not => type_ == Type::Boolean,
+        _ => todo!(),
+    };
+
+    if !valid {
+        panic!(
+            "operator {:?} can not be applied to {:?}",
+            unary.operator, type_
+        );
+    }
+
+    ir::UnaryExpression {
+        type_,
+        operator: unary.operator,
+        expression,
+    }
+}
+
+/// `*p` reads the pointee of `p`, `&place` and `&mut place` take the
+/// address of a variable, field, element or pointee.
+fn typecheck_pointer_operation(
+    unary: ast::UnaryExpression,
+    env: Rc<Environment>,
+) -> ir::UnaryExpression {
+    let mutable = match unary.operator {
+        ast::UnaryOperator::Deref => {
+            let expression = typecheck_expression(unary.expression, env);
+            let type_ = match expression.type_() {
+                Type::Pointer { pointee, .. } => *pointee,
+                other => panic!("can not dereference {:?}", other),
+            };
+
+            return ir::UnaryExpression {
+                type_,
+                operator: unary.operator,
+                expression,
+            };
+        }
+        ast::UnaryOperator::MutAddressOf => true,
+        _ => false,
+    };
+
+    if mutable {
+        check_assignable(&unary.expression, &env);
+    } else {
+        check_place(&unary.expression);
+    }
+
+    let expression = typecheck_expression(unary.expression, env);
+    if mutable {
+        check_pointer_mutability(&expression);
+    }
+
+    let pointee = match expression.type_() {
+        Type::Function { .. } => panic!("can not take the address of a function"),
+        pointee => pointee,
+    };
+
+    ir::UnaryExpression {
+        type_: Type::Pointer {
+            mutable,
+            pointee: Box::new(pointee),
+        },
+        operator: unary.operator,
+        expression,
+    }
+}
+
+fn check_place(expression: &ast::Expression) {
+    match expression {
+        ast::Expression::Identifier(_) => {}
+        ast::Expression::Access(access) => check_place(&access.base),
+        ast::Expression::Field(field) => check_place(&field.base),
+        ast::Expression::Unary(unary) if matches!(unary.operator, ast::UnaryOperator::Deref) => {}
+        _ => panic!("can only take the address of a variable, field, element or pointee"),
+    }
+}
+
+/// Panics if writing to the place `target` writes through a `*const` pointer
+/// or into a slice, whose elements are read-only.
+fn check_pointer_mutability(target: &ir::Expression) {
+    match target {
+        ir::Expression::Access(access) if matches!(access.base.type_(), Type::Slice(_)) => {
+            panic!("can not write to an element of {:?}", access.base.type_());
+        }
+        ir::Expression::Access(access) => check_pointer_mutability(&access.base),
+        ir::Expression::Field(field) => check_pointer_mutability(&field.base),
+        ir::Expression::Unary(unary) if matches!(unary.operator, ast::UnaryOperator::Deref) => {
+            if let Type::Pointer { mutable: false, .. } = unary.expression.type_() {
+                panic!("can not write through a *const pointer");
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn typecheck_binary_expression(
+    bin_expr: ast::BinaryExpression,
+    env: Rc<Environment>,
+) -> ir::BinaryExpression {
+    use ast::BinaryOperator::*;
+
+    let lhs = typecheck_expression(bin_expr.left, Rc::clone(&env));
+    let rhs = typecheck_expression(bin_expr.right, env);
+
+    let (lhs, rhs) = match bin_expr.operator {
+        ShiftLeft | ShiftRight => {
+            let rhs = coerce_expression(rhs, &Type::UInt(UIntType::U32));
+            if !matches!(rhs.type_(), Type::UInt(_)) {
+                panic!("shift amount must be unsigned, found {:?}", rhs.type_());
+            }
+            (lhs, rhs)
+        }
+        GreaterThan | GreaterEqualThan | Equal | NotEqual | LessEqualThan | LessThan => {
+            let lhs = coerce_expression(lhs, &rhs.type_());
+            let rhs = coerce_expression(rhs, &lhs.type_());
+            let lhs = coerce_expression(lhs, &Type::Int(IntType::I32));
+            let rhs = coerce_expression(rhs, &Type::Int(IntType::I32));
+            (lhs, rhs)
+        }
+        _ => {
+            let lhs = coerce_expression(lhs, &rhs.type_());
+            let rhs = coerce_expression(rhs, &lhs.type_());
+            (lhs, rhs)
+        }
+    };
+
+    let operator = bin_expr.operator;
+    let (lhs_type, rhs_type) = (lhs.type_(), rhs.type_());
+
+    let is_null =
+        |expr: &ir::Expression| matches!(expr, ir::Expression::Literal(ast::Literal::Null(_)));
+    if !matches!(operator, Equal | NotEqual) && (is_null(&lhs) || is_null(&rhs)) {
+        panic!("operator {:?} can not be applied to null", operator);
+    }
+
+    let check_operands = |valid: &dyn Fn(&Type) -> bool, expected: &str| {
+        for type_ in &[&lhs_type, &rhs_type] {
+            if !valid(type_) {
+                panic!(
+                    "operator {:?} requires {} operands, found {:?}",
+                    operator, expected, type_
+                );
+            }
+        }
+    };
+
+    let check_same_type = || {
+        if lhs_type != rhs_type {
+            panic!(
+                "operands of {:?} differ in type: {:?} and {:?}; use an `as` cast to convert one of them",
+                operator, lhs_type, rhs_type
+            );
+        }
+    };
+
+    let type_ = match operator {
+        ShiftLeft | ShiftRight => {
+            check_operands(&|t| is_integer(t) || *t == Type::None, "integer");
+            lhs_type.clone()
+        }
+        Plus | Minus | Multiplication | Division | Remainder => {
+            check_operands(
+                &|t| is_integer(t) || is_float(t) || *t == Type::None,
+                "integer or float",
+            );
+            check_same_type();
+            lhs_type.clone()
+        }
+        BitAnd | BitOr | BitXor => {
+            check_operands(&|t| is_integer(t) || *t == Type::None, "integer");
+            check_same_type();
+            lhs_type.clone()
+        }
+        GreaterThan | GreaterEqualThan | LessEqualThan | LessThan => {
+            check_operands(
+                &|t| is_integer(t) || is_float(t) || *t == Type::Char,
+                "integer, float or char",
+            );
+            check_same_type();
+            Type::Boolean
+        }
+        Equal | NotEqual => {
+            check_operands(
+                &|t| {
+                    is_integer(t)
+                        || is_float(t)
+                        || matches!(t, Type::Boolean | Type::Char | Type::Pointer { .. })
+                },
+                "integer, float, bool, char or pointer",
+            );
+            check_same_type();
+            Type::Boolean
+        }
+        And | Or => {
+            check_operands(&|t| *t == Type::Boolean, "bool");
+            Type::Boolean
+        }
+    };
+
+    ir::BinaryExpression {
+        type_,
+        operator,
+        lhs,
+        rhs,
+    }
+}
+
+pub fn typecheck_block(block: ast::BlockExpression, env: Rc<Environment>) -> ir::BlockExpression {
+    let mut env = env;
+    let mut statements = vec![];
+
+    for statement in block.statements {
+        match statement {
+            ast::Statement::VariableDefinition(variable) => {
+                if variable.public {
+                    panic!("Variable {} inside block can not be pub", variable.id.value);
+                }
+
+                check_type(&variable.type_, &env);
+
+                let initialize_expression =
+                    typecheck_expression(variable.initialize_expression.clone(), Rc::clone(&env));
+                let initialize_expression =
+                    coerce_expression(initialize_expression, &variable.type_);
+
+                if initialize_expression.type_() != variable.type_ {
+                    panic!(
+                        "Initialize expression for {} differs in type. Expected: {:?}, Found: {:?}.",
+                        variable.id.value,
+                        variable.type_,
+                        initialize_expression.type_()
+                    );
+                }
+
+                let mut block_env = Environment::put(Rc::clone(&env));
+                block_env.insert_assignable(variable.id.clone(), variable.type_.clone());
+                env = Rc::new(block_env);
+
+                statements.push(ir::Statement::VariableDefinition(Box::new(
+                    ir::VariableDefinition {
+                        id: variable.id,
+                        type_: variable.type_,
+                        initialize_expression,
+                    },
+                )));
+            }
+            ast::Statement::TupleDefinition(tuple) => {
+                let mut tuple = *tuple;
+                let names = display_ids(&tuple.ids);
+
+                for (index, id) in tuple.ids.iter().enumerate() {
+                    if tuple.ids[..index].contains(id) {
+                        panic!("{} is bound more than once in the same pattern", id.value);
+                    }
+                }
+
+                if let Some(type_) = &tuple.type_ {
+                    check_type(type_, &env);
+                }
+
+                let initialize_expression =
+                    typecheck_expression(tuple.initialize_expression.clone(), Rc::clone(&env));
+                let type_ = tuple
+                    .type_
+                    .clone()
+                    .unwrap_or_else(|| initialize_expression.type_());
+                let initialize_expression = coerce_expression(initialize_expression, &type_);
+
+                if is_untyped(&type_) {
+                    panic!(
+                        "can not infer the type of ({}), add a type annotation",
+                        names
+                    );
+                }
+
+                if initialize_expression.type_() != type_ {
+                    panic!(
+                        "Initialize expression for ({}) differs in type. Expected: {:?}, Found: {:?}.",
+                        names,
+                        type_,
+                        initialize_expression.type_()
+                    );
+                }
+
+                match &type_ {
+                    Type::Tuple(types) if types.len() == tuple.ids.len() => (),
+                    _ => panic!("can not destructure {:?} into ({})", type_, names),
+                }
+
+                tuple.type_ = Some(type_.clone());
+
+                let block_env = Environment::put(Rc::clone(&env));
+                env = Rc::new(
+                    EnvironmentBuilder {}.visit_tuple_definition_statement(block_env, &tuple),
+                );
+
+                statements.push(ir::Statement::TupleDefinition(Box::new(
+                    ir::TupleDefinition {
+                        ids: tuple.ids,
+                        type_,
+                        initialize_expression,
+                    },
+                )));
+            }
+            ast::Statement::Assignment(assignment) => {
+                statements.push(ir::Statement::Assignment(Box::new(typecheck_assignment(
+                    *assignment,
+                    Rc::clone(&env),
+                ))));
+            }
+            ast::Statement::FunctionDefinition(_) => {
+                panic!("Function definition inside block is not allowed");
+            }
+            ast::Statement::FunctionDeclaration(_) => {
+                panic!("Function declaration inside block is not allowed");
+            }
+            ast::Statement::Module(_) | ast::Statement::ModuleDeclaration(_) => {
+                panic!("Module definition inside block is not allowed");
+            }
+            ast::Statement::Use(_) => {
+                panic!("Use declaration inside block is not allowed");
+            }
+            ast::Statement::TraitDefinition(_) => {
+                panic!("Trait definition inside block is not allowed");
+            }
+            ast::Statement::Impl(_) => {
+                panic!("Impl inside block is not allowed");
+            }
+            ast::Statement::TypeAlias(_) | ast::Statement::Newtype(_) => {
+                panic!("Type definition inside block is not allowed");
+            }
+            _ => todo!(),
+        }
+    }
+
+    let return_expression = match block.return_expression {
+        Some(expr) => typecheck_expression(expr, env),
+        _ => todo!(),
+    };
+
+    ir::BlockExpression {
+        type_: return_expression.type_(),
+        statements,
+        return_expression,
+    }
+}
+
+pub fn typecheck_assignment(
+    assignment: ast::AssignmentStatement,
+    env: Rc<Environment>,
+) -> ir::Assignment {
+    check_assignable(&assignment.target, &env);
+
+    let target = typecheck_expression(assignment.target, Rc::clone(&env));
+    check_pointer_mutability(&target);
+    let expression = typecheck_expression(assignment.expression, env);
+    let expression = coerce_expression(expression, &target.type_());
+
+    if expression.type_() != target.type_() {
+        panic!(
+            "can not assign {:?} to a place of type {:?}",
+            expression.type_(),
+            target.type_()
+        );
+    }
+
+    ir::Assignment { target, expression }
+}
+
+/// Only variables defined with `let`, elements of them and pointees of
+/// pointers can be assigned.
+fn check_assignable(target: &ast::Expression, env: &Environment) {
+    match target {
+        ast::Expression::Identifier(id) => {
+            if env.get(id).is_some() && !env.is_assignable(id) {
+                panic!("can not assign to {}, it is not a variable", id.value);
+            }
+        }
+        ast::Expression::Access(access) => check_assignable(&access.base, env),
+        ast::Expression::Field(field) => check_assignable(&field.base, env),
+        ast::Expression::Unary(unary) if matches!(unary.operator, ast::UnaryOperator::Deref) => {}
+        _ => panic!("invalid assignment target"),
+    }
+}
+
+/// `print` and `println` write a `str` to standard output unless a function
+/// of that name is in scope.
+pub fn typecheck_print(
+    newline: bool,
+    arguments: Vec<ast::Expression>,
+    env: Rc<Environment>,
+) -> ir::PrintExpression {
+    let name = if newline { "println" } else { "print" };
+    let str_type = Type::Slice(Box::new(Type::UInt(UIntType::U8)));
+
+    let mut arguments = arguments;
+    if arguments.len() != 1 {
+        panic!("{} expects 1 argument, found {}", name, arguments.len());
+    }
+
+    let argument = typecheck_expression(arguments.remove(0), env);
+    if argument.type_() != str_type {
+        panic!("{} expects a str, found {:?}", name, argument.type_());
+    }
+
+    ir::PrintExpression { argument, newline }
+}
+
+/// `offset(pointer, count)` moves a pointer by `count` elements unless a
+/// function named `offset` is in scope.
+pub fn typecheck_offset(
+    arguments: Vec<ast::Expression>,
+    env: Rc<Environment>,
+) -> ir::OffsetExpression {
+    if arguments.len() != 2 {
+        panic!("offset expects 2 arguments, found {}", arguments.len());
+    }
+
+    let mut arguments = arguments.into_iter();
+    let pointer = typecheck_expression(arguments.next().unwrap(), Rc::clone(&env));
+    let count = typecheck_expression(arguments.next().unwrap(), env);
+    let count = coerce_expression(count, &Type::Int(IntType::ISize));
+
+    if !matches!(pointer.type_(), Type::Pointer { .. }) {
+        panic!("offset expects a pointer, found {:?}", pointer.type_());
+    }
+    if count.type_() != Type::Int(IntType::ISize) {
+        panic!("offset expects an isize count, found {:?}", count.type_());
+    }
+
+    ir::OffsetExpression {
+        type_: pointer.type_(),
+        pointer,
+        count,
+    }
+}
+
+/// `len(slice)` is the number of elements of a slice unless a function named
+/// `len` is in scope.
+pub fn typecheck_length(
+    arguments: Vec<ast::Expression>,
+    env: Rc<Environment>,
+) -> ir::LengthExpression {
+    let mut arguments = arguments;
+    if arguments.len() != 1 {
+        panic!("len expects 1 argument, found {}", arguments.len());
+    }
+
+    let slice = typecheck_expression(arguments.remove(0), env);
+    if !matches!(slice.type_(), Type::Slice(_)) {
+        panic!("len expects a slice, found {:?}", slice.type_());
+    }
+
+    ir::LengthExpression { slice }
+}
+
+pub fn typecheck_call(call: ast::CallExpression, env: Rc<Environment>) -> ir::CallExpression {
+    let callee = match call.callee {
+        ast::Expression::Identifier(id) if env.is_extern(&id) => {
+            ir::Expression::Identifier(ir::IdentifierExpression {
+                type_: env.get(&id).unwrap().clone(),
+                id,
+            })
+        }
+        callee => typecheck_expression(callee, Rc::clone(&env)),
+    };
+
+    let arguments = call
+        .arguments
+        .into_iter()
+        .map(|argument| typecheck_expression(argument, Rc::clone(&env)))
+        .collect();
+
+    call_value(callee, arguments)
+}
+
+/// `receiver.method(arguments)`, a call to the method of the type of
+/// `receiver` or of a trait it implements, with `receiver` as `self`. Without
+/// such a method it calls the function in field `method` of a struct.
+pub fn typecheck_method_call(
+    field: ast::FieldExpression,
+    arguments: Vec<ast::Expression>,
+    env: Rc<Environment>,
+) -> ir::CallExpression {
+    let ast::FieldExpression { base, field } = field;
+    let receiver = typecheck_expression(base, Rc::clone(&env));
+    // Unsuffixed integer literals are `i32` when nothing else gives them a
+    // type, as when inferring type arguments.
+    let receiver = match receiver.type_() {
+        Type::None => coerce_expression(receiver, &Type::Int(IntType::I32)),
+        _ => receiver,
+    };
+    let receiver_type = receiver.type_();
+
+    // Methods of impls without a trait come first, those of traits are
+    // only found when the type has none of that name.
+    let impls = env.impls_for(&receiver_type);
+    let mut methods: Vec<_> = impls
+        .iter()
+        .filter(|i| i.trait_.is_none())
+        .filter_map(|i| Some((&i.trait_, i.methods.get(&field)?)))
+        .take(1)
+        .collect();
+    if methods.is_empty() {
+        methods = impls
+            .iter()
+            .filter_map(|i| Some((&i.trait_, i.methods.get(&field)?)))
+            .collect();
+        // Conflicting impls are reported with the impls.
+        methods.dedup_by_key(|(trait_, _method)| *trait_);
+    }
+
+    let mut arguments: Vec<_> = arguments
+        .into_iter()
+        .map(|argument| typecheck_expression(argument, Rc::clone(&env)))
+        .collect();
+
+    let callee = match methods.as_slice() {
+        [] if !has_field(&receiver_type, &field, &env) => {
+            panic!("type {} has no method {}", receiver_type, field.value)
+        }
+        [] => {
+            let field = field_of(receiver, field, &env);
+            return call_value(ir::Expression::Field(Box::new(field)), arguments);
+        }
+        [(_trait, method)] if !method.receiver => panic!(
+            "{} is an associated function of type {}, call it as {}::{}",
+            field.value, receiver_type, receiver_type, field.value
+        ),
+        [(_trait, method)] => ir::Expression::Identifier(ir::IdentifierExpression {
+            type_: method.type_.clone(),
+            id: method.function.clone(),
+        }),
+        _ => {
+            let traits: Vec<_> = methods
+                .iter()
+                .flat_map(|(trait_, _method)| trait_.as_ref())
+                .map(|trait_| trait_.value.as_str())
+                .collect();
+            panic!(
+                "method {} of type {} is ambiguous, it is in traits {}",
+                field.value,
+                receiver_type,
+                traits.join(", ")
+            )
+        }
+    };
+
+    arguments.insert(0, receiver);
+
+    call_value(callee, arguments)
+}
+
+/// Calls `callee`, a value of function type, with typechecked `arguments`.
+fn call_value(callee: ir::Expression, arguments: Vec<ir::Expression>) -> ir::CallExpression {
+    let (parameters, return_type) = match callee.type_() {
+        Type::Function {
+            parameters,
+            return_type,
+        } => (parameters, *return_type),
+        other => panic!("can not call a value of type {:?}", other),
+    };
+
+    if parameters.len() != arguments.len() {
+        panic!(
+            "expected {} arguments, found {}",
+            parameters.len(),
+            arguments.len()
+        );
+    }
+
+    ir::CallExpression {
+        type_: return_type,
+        callee,
+        arguments: check_arguments(arguments, &parameters),
+    }
+}
+
+/// Calls generic function `id`, with `type_arguments` or with the type
+/// arguments inferred from the arguments, through the instance for them.
+pub fn typecheck_generic_call(
+    id: ast::Identifier,
+    type_arguments: Option<Vec<Type>>,
+    arguments: Vec<ast::Expression>,
+    span: (usize, usize),
+    env: Rc<Environment>,
+) -> ir::CallExpression {
+    let definition = match env.get_generic(&id) {
+        Some(definition) => definition.clone(),
+        None => panic!("{} is not a generic function", id.value),
+    };
+
+    if definition.parameters.len() != arguments.len() {
+        panic!(
+            "expected {} arguments, found {}",
+            definition.parameters.len(),
+            arguments.len()
+        );
+    }
+
+    let arguments: Vec<_> = arguments
+        .into_iter()
+        .map(|argument| typecheck_expression(argument, Rc::clone(&env)))
+        .collect();
+
+    let type_arguments = match type_arguments {
+        Some(type_arguments) if type_arguments.len() != definition.type_parameters.len() => {
+            panic!(
+                "function {} expects {} type arguments, found {}",
+                id.value,
+                definition.type_parameters.len(),
+                type_arguments.len()
+            )
+        }
+        Some(type_arguments) => type_arguments,
+        None => {
+            let types: Vec<_> = arguments.iter().map(ir::Expression::type_).collect();
+            generics::infer(&definition, &types)
+        }
+    };
+
+    for type_ in &type_arguments {
+        check_type(type_, &env);
+    }
+
+    for (parameter, type_) in definition.type_parameters.iter().zip(&type_arguments) {
+        for bound in &parameter.bounds {
+            if !env.implements(type_, bound) {
+                panic!(
+                    "type {} does not implement trait {}, required by type parameter {} of {}",
+                    type_, bound.value, parameter.id.value, id.value
+                );
+            }
+        }
+    }
+
+    let substitution = generics::Substitution::new(&definition.type_parameters, &type_arguments);
+    let parameters: Vec<_> = definition
+        .parameters
+        .iter()
+        .map(|(_id, type_)| substitution.type_(type_))
+        .collect();
+    let return_type = substitution.type_(&definition.return_type);
+
+    let arguments = check_arguments(arguments, &parameters);
+
+    ir::CallExpression {
+        type_: return_type.clone(),
+        callee: ir::Expression::Identifier(ir::IdentifierExpression {
+            type_: Type::Function {
+                parameters,
+                return_type: Box::new(return_type),
+            },
+            id: env.instantiate(&id, type_arguments, span),
+        }),
+        arguments,
+    }
+}
+
+/// Gives the arguments of a call the types of the parameters, or panics.
+fn check_arguments(arguments: Vec<ir::Expression>, parameters: &[Type]) -> Vec<ir::Expression> {
+    arguments
+        .into_iter()
+        .zip(parameters)
+        .enumerate()
+        .map(|(index, (argument, parameter))| {
+            let argument = coerce_expression(argument, parameter);
+
+            if argument.type_() != *parameter {
+                panic!(
+                    "argument {} expected {:?}, found {:?}",
+                    index + 1,
+                    parameter,
+                    argument.type_()
+                );
+            }
+
+            argument
+        })
+        .collect()
+}
+
+/// `Newtype(argument)`, the value of `argument` as a value of the newtype.
+pub fn typecheck_newtype(
+    id: ast::Identifier,
+    arguments: Vec<ast::Expression>,
+    env: Rc<Environment>,
+) -> ir::NewtypeExpression {
+    let type_ = Type::Named(id);
+    let wrapped = newtype_of(&type_, &env).unwrap();
+
+    if arguments.len() != 1 {
+        panic!(
+            "newtype {} expects 1 argument, found {}",
+            type_,
+            arguments.len()
+        );
+    }
+
+    let arguments: Vec<_> = arguments
+        .into_iter()
+        .map(|argument| typecheck_expression(argument, Rc::clone(&env)))
+        .collect();
+    let expression = check_arguments(arguments, &[wrapped]).remove(0);
+
+    ir::NewtypeExpression { type_, expression }
+}
+
+pub fn typecheck_variant(
+    path: &[ast::Identifier],
+    arguments: Vec<ast::Expression>,
+    env: Rc<Environment>,
+) -> ir::VariantExpression {
+    let (type_, tag, field_types) = resolve_variant(path, &env);
+
+    if field_types.len() != arguments.len() {
+        panic!(
+            "variant {} expects {} fields, found {}",
+            display_path(path),
+            field_types.len(),
+            arguments.len()
+        );
+    }
+
+    let fields = arguments
+        .into_iter()
+        .zip(&field_types)
+        .map(|(argument, field_type)| {
+            let argument = typecheck_expression(argument, Rc::clone(&env));
+            let argument = coerce_expression(argument, field_type);
+
+            if argument.type_() != *field_type {
+                panic!(
+                    "variant {} expects {:?}, found {:?}",
+                    display_path(path),
+                    field_type,
+                    argument.type_()
+                );
+            }
+
+            argument
+        })
+        .collect();
+
+    ir::VariantExpression {
+        type_,
+        variant: path[1].clone(),
+        tag,
+        fields,
+    }
+}
+
+pub fn typecheck_tuple(tuple: ast::TupleExpression, env: Rc<Environment>) -> ir::TupleExpression {
+    let elements: Vec<ir::Expression> = tuple
+        .elements
+        .into_iter()
+        .map(|element| typecheck_expression(element, Rc::clone(&env)))
+        .collect();
+
+    ir::TupleExpression {
+        type_: Type::Tuple(elements.iter().map(|element| element.type_()).collect()),
+        elements,
+    }
+}
+
+pub fn typecheck_struct(
+    struct_: ast::StructExpression,
+    env: Rc<Environment>,
+) -> ir::StructExpression {
+    let struct_id = struct_.id;
+    let type_ = Type::Named(struct_id.clone());
+    let definition = match struct_fields(&type_, &env) {
+        Some(fields) => fields,
+        None => panic!("{} is not a struct", struct_id.value),
+    };
+
+    let mut initializers: Vec<Option<ast::Expression>> = vec![None; definition.len()];
+
+    for (id, expression) in struct_.fields {
+        let index = match definition.iter().position(|(field, _)| *field == id) {
+            Some(index) => index,
+            None => panic!("struct {} has no field named {}", struct_id.value, id.value),
+        };
+
+        if initializers[index].replace(expression).is_some() {
+            panic!("field {} is initialized more than once", id.value);
+        }
+    }
+
+    let fields = definition
+        .iter()
+        .zip(initializers)
+        .map(|((id, field_type), initializer)| {
+            let expression = match initializer {
+                Some(expression) => typecheck_expression(expression, Rc::clone(&env)),
+                None => panic!(
+                    "missing field {} in initializer of {}",
+                    id.value, struct_id.value
+                ),
+            };
+            let expression = coerce_expression(expression, field_type);
+
+            if expression.type_() != *field_type {
+                panic!(
+                    "field {} of {} expects {:?}, found {:?}",
+                    id.value,
+                    struct_id.value,
+                    field_type,
+                    expression.type_()
+                );
+            }
+
+            expression
+        })
+        .collect();
+
+    ir::StructExpression { type_, fields }
+}
+
+/// `base.0` unwraps a newtype.
+pub fn typecheck_field(field: ast::FieldExpression, env: Rc<Environment>) -> ir::Expression {
+    let base = typecheck_expression(field.base, Rc::clone(&env));
+
+    match newtype_of(&base.type_(), &env) {
+        Some(type_) if field.field.value == "0" => {
+            ir::Expression::Newtype(Box::new(ir::NewtypeExpression {
+                type_,
+                expression: base,
+            }))
+        }
+        _ => ir::Expression::Field(Box::new(field_of(base, field.field, &env))),
+    }
+}
+
+fn has_field(type_: &Type, field: &ast::Identifier, env: &Environment) -> bool {
+    struct_fields(type_, env).is_some_and(|fields| fields.iter().any(|(id, _)| id == field))
+}
+
+fn field_of(
+    base: ir::Expression,
+    field: ast::Identifier,
+    env: &Environment,
+) -> ir::FieldExpression {
+    let fields = match struct_fields(&base.type_(), env) {
+        Some(fields) => fields,
+        None => panic!("{:?} has no field named {}", base.type_(), field.value),
+    };
+
+    let (index, type_) = match fields.iter().position(|(id, _)| *id == field) {
+        Some(index) => (index, fields[index].1.clone()),
+        None => panic!("{:?} has no field named {}", base.type_(), field.value),
+    };
+
+    ir::FieldExpression { type_, base, index }
+}
+
+pub fn typecheck_array(array: ast::ArrayExpression, env: Rc<Environment>) -> ir::ArrayExpression {
+    match array {
+        ast::ArrayExpression::List(elements) => {
+            let elements: Vec<ir::Expression> = elements
+                .into_iter()
+                .map(|element| typecheck_expression(element, Rc::clone(&env)))
+                .collect();
+
+            // Elements that are all untyped, like `[[1, 2], [3, 4]]`, keep the
+            // type of the first one until the array is coerced.
+            let element_type = elements
+                .iter()
+                .map(|element| element.type_())
+                .find(|type_| !is_untyped(type_))
+                .or_else(|| elements.first().map(|element| element.type_()))
+                .unwrap_or(Type::None);
+
+            let elements: Vec<ir::Expression> = elements
+                .into_iter()
+                .map(|element| coerce_expression(element, &element_type))
+                .collect();
+
+            for element in &elements {
+                if element.type_() != element_type {
+                    panic!(
+                        "array elements differ in type, expected {:?}, found {:?}",
+                        element_type,
+                        element.type_()
+                    );
+                }
+            }
+
+            ir::ArrayExpression {
+                type_: Type::Array {
+                    element_type: Box::new(element_type),
+                    size: elements.len() as u64,
+                },
+                elements: ir::ArrayElements::List(elements),
+            }
+        }
+        ast::ArrayExpression::Repeat { expression, size } => {
+            let expression = typecheck_expression(expression, env);
+
+            ir::ArrayExpression {
+                type_: Type::Array {
+                    element_type: Box::new(expression.type_()),
+                    size,
+                },
+                elements: ir::ArrayElements::Repeat(expression, size),
+            }
+        }
+    }
+}
+
+pub fn typecheck_access(
+    access: ast::AccessExpression,
+    env: Rc<Environment>,
+) -> ir::AccessExpression {
+    let base = typecheck_expression(access.base, Rc::clone(&env));
+    let offset = typecheck_expression(access.offset, env);
+    let offset = coerce_expression(offset, &Type::UInt(UIntType::USize));
+
+    if !is_integer(&offset.type_()) {
+        panic!("array index must be an integer, found {:?}", offset.type_());
+    }
+
+    let type_ = match base.type_() {
+        Type::Array { element_type, .. } | Type::Slice(element_type) => *element_type,
+        other => panic!("can not index into {:?}", other),
+    };
+
+    ir::AccessExpression {
+        type_,
+        base,
+        offset,
+    }
+}
+
+pub fn typecheck_match(match_: ast::MatchExpression, env: Rc<Environment>) -> ir::MatchExpression {
+    let scrutinee = typecheck_expression(match_.scrutinee, Rc::clone(&env));
+    let scrutinee = coerce_expression(scrutinee, &Type::Int(IntType::I32));
+    let scrutinee_type = scrutinee.type_();
+
+    let domain = match &scrutinee_type {
+        Type::Boolean => (0, 1),
+        Type::Char => (0, char::MAX as u128),
+        Type::Int(t) => (0, width_mask(t.bits())),
+        Type::UInt(t) => (0, width_mask(t.bits())),
+        other => match enum_variants(other, &env) {
+            Some(variants) => (0, variants.len() as u128 - 1),
+            None => panic!(
+                "match scrutinee must be an integer, bool, char or enum, found {:?}",
+                other
+            ),
+        },
+    };
+
+    let mut coverage = Coverage::new();
+
+    // Surrogates are not chars, so patterns do not need to cover them.
+    if scrutinee_type == Type::Char {
+        coverage.insert((0xd800, 0xdfff));
+    }
+    let mut arms = vec![];
+
+    for (index, arm) in match_.arms.into_iter().enumerate() {
+        let mut patterns = vec![];
+        let mut bindings = vec![];
+
+        for pattern in &arm.patterns {
+            let pattern = typecheck_pattern(pattern, &scrutinee_type, &env, &mut bindings);
+            let interval = match pattern {
+                ir::Pattern::Wildcard => domain,
+                ir::Pattern::Constant(value) => {
+                    let value = ordered(value, &scrutinee_type);
+                    (value, value)
+                }
+                ir::Pattern::Range(start, end) => (
+                    ordered(start, &scrutinee_type),
+                    ordered(end, &scrutinee_type),
+                ),
+            };
+
+            if interval.0 > interval.1 {
+                panic!("match range pattern lower bound exceeds its upper bound");
+            }
+
+            if !coverage.contains(interval) {
+                coverage.insert(interval);
+                patterns.push(pattern);
+            }
+        }
+
+        if !bindings.is_empty() && arm.patterns.len() > 1 {
+            panic!("patterns that bind names can not be combined with |");
+        }
+
+        let arm_env = if bindings.is_empty() {
+            Rc::clone(&env)
+        } else {
+            let mut arm_env = Environment::put(Rc::clone(&env));
+            for binding in &bindings {
+                arm_env.insert(binding.id.clone(), binding.type_.clone());
+            }
+            Rc::new(arm_env)
+        };
+
+        let expression = typecheck_expression(arm.expression, arm_env);
+
+        if patterns.is_empty() {
+            env.warn(Diagnostic {
+                span: Some(arm.span),
+                message: format!("match arm {} is unreachable", index),
+            });
+        } else {
+            arms.push(ir::MatchArm {
+                patterns,
+                bindings,
+                expression,
+            });
+        }
+    }
+
+    if let Some(missing) = coverage.first_gap(domain) {
+        let missing = match (&scrutinee_type, enum_variants(&scrutinee_type, &env)) {
+            (Type::Named(id), Some(variants)) => {
+                format!("{}::{}", id.value, variants[missing as usize].0.value)
+            }
+            _ => display_ordered(missing, &scrutinee_type),
+        };
+
+        panic!("non-exhaustive match: {} not covered", missing);
+    }
+
+    let type_ = arms
+        .iter()
+        .map(|arm| arm.expression.type_())
+        .find(|type_| *type_ != Type::None)
+        .unwrap_or(Type::None);
+
+    let arms: Vec<ir::MatchArm> = arms
+        .into_iter()
+        .map(|arm| ir::MatchArm {
+            patterns: arm.patterns,
+            bindings: arm.bindings,
+            expression: coerce_expression(arm.expression, &type_),
+        })
+        .collect();
+
+    for arm in &arms {
+        if arm.expression.type_() != type_ {
+            panic!(
+                "match arms differ in type, expected {:?}, found {:?}",
+                type_,
+                arm.expression.type_()
+            );
+        }
+    }
+
+    ir::MatchExpression {
+        type_,
+        scrutinee,
+        arms,
+    }
+}
+
+pub fn typecheck_cast(cast: ast::CastExpression, env: Rc<Environment>) -> ir::CastExpression {
+    let expression = typecheck_expression(cast.expression, env);
+    let expression = match cast.type_ {
+        Type::Pointer { .. } => coerce_expression(expression, &cast.type_),
+        _ => coerce_expression(expression, &Type::Int(IntType::I32)),
+    };
+
+    if !is_valid_cast(&expression.type_(), &cast.type_) {
+        panic!(
+            "invalid cast from {:?} to {:?}",
+            expression.type_(),
+            cast.type_
+        );
+    }
+
+    ir::CastExpression {
+        type_: cast.type_,
+        expression,
+    }
+}
+
+/// Casts `as` accepts: between any two integer types, from `bool` and `char`
+/// to integers, and from `u8` and `u32` to `char`.
+pub fn is_valid_cast(from: &Type, to: &Type) -> bool {
+    matches!(
+        (from, to),
+        (
+            Type::Int(_) | Type::UInt(_) | Type::Boolean | Type::Char,
+            Type::Int(_) | Type::UInt(_)
+        ) | (Type::Boolean, Type::Boolean)
+            | (Type::UInt(UIntType::U8 | UIntType::U32), Type::Char)
+            | (Type::Char, Type::Char)
+            | (
+                Type::Int(_) | Type::UInt(_) | Type::Float(_),
+                Type::Float(_)
+            )
+            | (Type::Float(_), Type::Int(_) | Type::UInt(_))
+            | (
+                Type::Int(_) | Type::UInt(_) | Type::Pointer { .. },
+                Type::Pointer { .. }
+            )
+            | (Type::Pointer { .. }, Type::Int(_) | Type::UInt(_))
+    )
+}
+
+fn typecheck_pattern(
+    pattern: &ast::Pattern,
+    scrutinee_type: &Type,
+    env: &Environment,
+    bindings: &mut Vec<ir::Binding>,
+) -> ir::Pattern {
+    match pattern {
+        ast::Pattern::Wildcard => ir::Pattern::Wildcard,
+        ast::Pattern::Binding(id) => {
+            panic!("{} can only be bound inside a variant pattern", id.value)
+        }
+        ast::Pattern::Variant(path, fields) => {
+            let (type_, tag, field_types) = resolve_variant(path, env);
+
+            if type_ != *scrutinee_type {
+                panic!(
+                    "match pattern of type {:?} differs from scrutinee type {:?}",
+                    type_, scrutinee_type
+                );
+            }
+
+            if fields.len() != field_types.len() {
+                panic!(
+                    "variant {} has {} fields, found {} in pattern",
+                    display_path(path),
+                    field_types.len(),
+                    fields.len()
+                );
+            }
+
+            for (field, (pattern, type_)) in fields.iter().zip(field_types).enumerate() {
+                if let ast::Pattern::Binding(id) = pattern {
+                    if bindings.iter().any(|binding| binding.id == *id) {
+                        panic!("{} is bound more than once in the same pattern", id.value);
+                    }
+
+                    bindings.push(ir::Binding {
+                        id: id.clone(),
+                        type_,
+                        variant: path[1].clone(),
+                        field,
+                    });
+                }
+            }
+
+            ir::Pattern::Constant(tag as u128)
+        }
+        ast::Pattern::Literal(literal) => {
+            ir::Pattern::Constant(typecheck_literal_pattern(literal, scrutinee_type))
+        }
+        ast::Pattern::Range(start, end) => match scrutinee_type {
+            Type::Int(_) | Type::UInt(_) | Type::Char => ir::Pattern::Range(
+                typecheck_literal_pattern(start, scrutinee_type),
+                typecheck_literal_pattern(end, scrutinee_type),
+            ),
+            _ => panic!("range patterns require an integer or char scrutinee"),
+        },
+    }
+}
+
+fn typecheck_literal_pattern(pattern: &ast::LiteralPattern, scrutinee_type: &Type) -> u128 {
+    let literal_type = pattern.literal.type_();
+    let is_scalar = matches!(
+        scrutinee_type,
+        Type::Boolean | Type::Char | Type::Int(_) | Type::UInt(_)
+    );
+    if !is_scalar || literal_type != Type::None && literal_type != *scrutinee_type {
+        panic!(
+            "match pattern of type {:?} differs from scrutinee type {:?}",
+            literal_type, scrutinee_type
+        );
+    }
+
+    match &pattern.literal {
+        ast::Literal::Boolean(value) => *value as u128,
+        ast::Literal::Int(value, _) => literal_bits(pattern.negated, *value, scrutinee_type),
+        ast::Literal::Char(value) => *value as u128,
+        ast::Literal::Null(_) => panic!("null can not be used as a pattern"),
+        ast::Literal::Float(..) | ast::Literal::Str(_) => unreachable!(),
+    }
+}
+
+fn width_mask(bits: u32) -> u128 {
+    u128::MAX >> (128 - bits)
+}
+
+/// Panics if a literal with a type suffix does not fit its type.
+/// Unsuffixed literals are checked once their type is known.
+fn check_suffixed_literal(literal: &ast::Literal, negated: bool) {
+    match literal {
+        ast::Literal::Int(value, LiteralIntType::Signed(_) | LiteralIntType::Unsigned(_)) => {
+            literal_bits(negated, *value, &literal.type_());
+        }
+        ast::Literal::Float(value, LiteralFloatType::Suffixed(FloatType::F32))
+            if (*value as f32).is_infinite() =>
+        {
+            panic!("float literal {} does not fit f32", value)
+        }
+        _ => {}
+    }
+}
+
+/// Two's complement bit pattern of an integer literal, checking it fits `type_`.
+fn literal_bits(negated: bool, value: u128, type_: &Type) -> u128 {
+    let sign = if negated { "-" } else { "" };
+    let (bits, max) = match type_ {
+        Type::Int(t) if negated => (t.bits(), 1 << (t.bits() - 1)),
+        Type::Int(t) => (t.bits(), width_mask(t.bits() - 1)),
+        Type::UInt(t) if !negated || value == 0 => (t.bits(), width_mask(t.bits())),
+        _ => panic!("literal {}{} can not be used as {:?}", sign, value, type_),
+    };
+
+    if value > max {
+        panic!("literal {}{} out of range for {:?}", sign, value, type_);
+    }
+
+    if negated {
+        value.wrapping_neg() & width_mask(bits)
+    } else {
+        value
+    }
+}
+
+/// Maps a bit pattern to a value space where unsigned comparison matches the
+/// ordering of `type_`, by flipping the sign bit of signed integers.
+fn ordered(value: u128, type_: &Type) -> u128 {
+    match type_ {
+        Type::Int(t) => value ^ (1 << (t.bits() - 1)),
+        _ => value,
+    }
+}
+
+fn display_ordered(value: u128, type_: &Type) -> String {
+    match type_ {
+        Type::Boolean => (value == 1).to_string(),
+        Type::Char => format!("{:?}", std::char::from_u32(value as u32).unwrap()),
+        Type::Int(t) => {
+            let shift = 128 - t.bits();
+            ((ordered(value, type_) << shift) as i128 >> shift).to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Sorted disjoint intervals of the values matched so far by a match expression.
+struct Coverage {
+    intervals: Vec<(u128, u128)>,
+}
+
+impl Coverage {
+    fn new() -> Self {
+        Coverage { intervals: vec![] }
+    }
+
+    fn contains(&self, (start, end): (u128, u128)) -> bool {
+        self.intervals
+            .iter()
+            .any(|&(covered_start, covered_end)| covered_start <= start && end <= covered_end)
+    }
+
+    fn insert(&mut self, interval: (u128, u128)) {
+        self.intervals.push(interval);
+        self.intervals.sort_unstable();
+
+        let mut merged: Vec<(u128, u128)> = vec![];
+        for (start, end) in self.intervals.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.1 == u128::MAX || last.1 + 1 >= start => {
+                    last.1 = last.1.max(end)
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+        self.intervals = merged;
+    }
+
+    fn first_gap(&self, (start, end): (u128, u128)) -> Option<u128> {
+        let mut next = start;
+        for &(covered_start, covered_end) in &self.intervals {
+            if covered_start > next {
+                return Some(next);
+            }
+            if covered_end >= end {
+                return None;
+            }
+            next = next.max(covered_end + 1);
+        }
+        Some(next)
+    }
+}
+
+// struct TypeChecker {}
+//
+//
+// impl AstVisitor<Rc<Environment>, Type> for TypeChecker {
+//     type Return = Type;
+//     type Environment = Rc<Environment>;
+//
+//     fn visit_module_statement(
+//         &mut self,
+//         env: Self::Environment,
+//         m: &ModuleStatement,
+//     ) -> Self::Return {
+//         for statement in &m.statements {
+//             statement.accept(Rc::clone(&env), self);
+//         }
+//         Type::None
+//     }
+//
+//     fn visit_statement(&mut self, env: Self::Environment, s: &Statement) -> Self::Return {
+//         use Statement::*;
+//         match s {
+//             Module(m) => m.accept(env, self),
+//             ExternFunctionDeclaration(s) => s.accept(env, self),
+//             FunctionDefinition(s) => s.accept(env, self),
+//             VariableDefinition(s) => s.accept(env, self),
+//             Return(s) => s.accept(env, self),
+//         }
 //     }
 //
 //     fn visit_extern_function_declaration_statement(
@@ -564,3 +3615,1935 @@ pub fn typecheck_block(block: ast::BlockExpression) -> ir::BlockExpression {
 //         type_checker.visit_module_statement(Rc::new(env), &module);
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment_builder::INSTANCE_DEPTH_LIMIT;
+    use crate::parser::ModuleParser;
+    use crate::resolve;
+
+    fn typecheck(code: &str) -> ir::Module {
+        let mut module = ModuleParser::new().parse(0, code).unwrap();
+
+        if let Err(diagnostics) = resolve::resolve_names(&mut module) {
+            panic!("{}", diagnostics[0].message);
+        }
+
+        typecheck_root_module(module)
+    }
+
+    #[test]
+    fn match_on_integers() {
+        let module = typecheck(
+            "
+            fn classify(x: u8): u8 => match x {
+                0 => 10,
+                1 | 2 => 20,
+                3..=9 => 30,
+                _ => 40,
+            };
+            fn sign(x: i8): i32 => match x {
+                -128..=-1 => 0,
+                0 => 1,
+                1..=127 => 2,
+            };
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Match(match_) => {
+                assert_eq!(match_.arms.len(), 4);
+                assert!(matches!(
+                    match_.arms[1].patterns[..],
+                    [ir::Pattern::Constant(1), ir::Pattern::Constant(2)]
+                ));
+            }
+            other => panic!("expected match, found {:?}", other),
+        }
+
+        match &module.functions[1].body {
+            ir::Expression::Match(match_) => {
+                assert!(matches!(
+                    match_.arms[0].patterns[..],
+                    [ir::Pattern::Range(0x80, 0xff)]
+                ));
+            }
+            other => panic!("expected match, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn match_on_booleans() {
+        typecheck(
+            "
+            fn to_int(b: bool): u32 => match b {
+                true => 1,
+                false => 0,
+            };
+            ",
+        );
+    }
+
+    #[test]
+    fn match_drops_unreachable_arms() {
+        let module = typecheck(
+            "
+            fn f(x: u8): u8 => match x {
+                0..=200 => 1,
+                5 | 201 => 2,
+                _ => 3,
+                7 => 4,
+            };
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Match(match_) => {
+                assert_eq!(match_.arms.len(), 3);
+                assert!(matches!(
+                    match_.arms[1].patterns[..],
+                    [ir::Pattern::Constant(201)]
+                ));
+            }
+            other => panic!("expected match, found {:?}", other),
+        }
+
+        assert_eq!(module.warnings.len(), 1);
+        assert_eq!(module.warnings[0].span, Some((142, 148)));
+        assert_eq!(module.warnings[0].message, "match arm 3 is unreachable");
+    }
+
+    #[test]
+    #[should_panic(expected = "non-exhaustive match: 10 not covered")]
+    fn match_not_exhaustive() {
+        typecheck(
+            "
+            fn f(x: u8): u8 => match x {
+                0..=9 => 1,
+                11..=255 => 2,
+            };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "non-exhaustive match: -128 not covered")]
+    fn match_signed_not_exhaustive() {
+        typecheck(
+            "
+            fn f(x: i8): u8 => match x {
+                -127..=127 => 1,
+            };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "non-exhaustive match: false not covered")]
+    fn match_bool_not_exhaustive() {
+        typecheck(
+            "
+            fn f(b: bool): u8 => match b {
+                true => 1,
+            };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "literal 256 out of range for UInt(U8)")]
+    fn match_pattern_out_of_range() {
+        typecheck(
+            "
+            fn f(x: u8): u8 => match x {
+                256 => 1,
+                _ => 2,
+            };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "literal 1 can not be used as Boolean")]
+    fn match_integer_pattern_on_boolean() {
+        typecheck(
+            "
+            fn f(b: bool): u8 => match b {
+                1 => 1,
+                _ => 2,
+            };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "match arms differ in type")]
+    fn match_arms_differ_in_type() {
+        typecheck(
+            "
+            fn f(x: u8): u8 => match x {
+                0 => true,
+                _ => 2_u8,
+            };
+            ",
+        );
+    }
+
+    #[test]
+    fn cast_between_integers() {
+        let module = typecheck(
+            "
+            fn widen(x: i8): u64 => x as u64;
+            fn narrow(x: usize): u8 => x as i64 as u8;
+            fn from_bool(b: bool): i32 => b as i32;
+            fn from_literal(): u8 => 5 as u8;
+            ",
+        );
+
+        match &module.functions[1].body {
+            ir::Expression::Cast(cast) => {
+                assert_eq!(cast.type_, Type::UInt(ast::UIntType::U8));
+                assert!(matches!(cast.expression, ir::Expression::Cast(_)));
+            }
+            other => panic!("expected cast, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cast from Int(I32) to Boolean")]
+    fn cast_int_to_bool() {
+        typecheck("fn f(x: i32): bool => x as bool;");
+    }
+
+    #[test]
+    fn bitwise_and_shift_operators() {
+        let module = typecheck(
+            "
+            fn mask(x: u32, y: u32): u32 => x & 255_u32 | y ^ ~x % 7;
+            fn shift(x: i64, n: u8): i64 => x << 2 >> n;
+            fn negate(x: i8): i8 => -128 + -x;
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Binary(bin_expr) => {
+                assert!(matches!(bin_expr.operator, ast::BinaryOperator::BitOr));
+            }
+            other => panic!("expected binary expression, found {:?}", other),
+        }
+
+        match &module.functions[1].body {
+            ir::Expression::Binary(bin_expr) => {
+                assert!(matches!(bin_expr.operator, ast::BinaryOperator::ShiftRight));
+                assert_eq!(bin_expr.type_, Type::Int(IntType::I64));
+            }
+            other => panic!("expected binary expression, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "shift amount must be unsigned, found Int(I32)")]
+    fn shift_by_signed_amount() {
+        typecheck("fn f(x: u32, n: i32): u32 => x << n;");
+    }
+
+    #[test]
+    #[should_panic(expected = "operator BitAnd requires integer operands, found Boolean")]
+    fn bitwise_on_bool() {
+        typecheck("fn f(a: bool, b: bool): bool => a & b;");
+    }
+
+    #[test]
+    #[should_panic(expected = "operator Minus can not be applied to UInt(U8)")]
+    fn negate_unsigned() {
+        typecheck("fn f(x: u8): u8 => -x;");
+    }
+
+    #[test]
+    #[should_panic(expected = "literal -129 out of range for Int(I8)")]
+    fn negative_literal_out_of_range() {
+        typecheck("fn f(): i8 => -129;");
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let module = typecheck(
+            "
+            fn compare(a: u16, b: u16, c: bool): bool => a != b && (a < 7 || a >= b) && c == true;
+            fn literals(): bool => 1 != 2;
+            fn logic(a: bool, b: bool): bool => !a or not b and a != b;
+            ",
+        );
+
+        match &module.functions[1].body {
+            ir::Expression::Binary(bin_expr) => {
+                assert_eq!(bin_expr.type_, Type::Boolean);
+                assert_eq!(bin_expr.lhs.type_(), Type::Int(IntType::I32));
+            }
+            other => panic!("expected binary expression, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "operands of LessThan differ in type: Int(I8) and Int(I32); use an `as` cast"
+    )]
+    fn compare_mismatched_widths() {
+        typecheck("fn f(a: i8, b: i32): bool => a < b;");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "operator GreaterThan requires integer, float or char operands, found Boolean"
+    )]
+    fn order_booleans() {
+        typecheck("fn f(a: bool, b: bool): bool => a > b;");
+    }
+
+    #[test]
+    #[should_panic(expected = "operands of Plus differ in type: UInt(U8) and UInt(U64)")]
+    fn add_mismatched_widths() {
+        typecheck("fn f(a: u8, b: u64): u64 => a + b;");
+    }
+
+    #[test]
+    fn arrays() {
+        let module = typecheck(
+            "
+            fn sum(values: [u8; 3]): u8 => values[0] + values[1_u32] + values[2];
+            fn build(x: i64): i64 => {
+                let list: [i64; 4] = [1, 2, x, 4];
+                let zeros: [[i64; 4]; 2] = [[0; 4]; 2];
+                zeros[1][3] = list[2];
+                zeros[1][3]
+            };
+            ",
+        );
+
+        match &module.functions[1].body {
+            ir::Expression::Block(block) => {
+                assert_eq!(block.statements.len(), 3);
+                assert!(matches!(block.statements[2], ir::Statement::Assignment(_)));
+            }
+            other => panic!("expected block, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn arrays_of_untyped_composites() {
+        let module = typecheck(
+            "
+            fn f(): i32 => {
+                let m: [[i32; 2]; 2] = [[1, 2], [3, 4]];
+                let pairs: [(u8, i64); 2] = [(1, 2), (3, 4)];
+                m[1][0] + pairs[0].1 as i32
+            };
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Block(block) => match &block.statements[0] {
+                ir::Statement::VariableDefinition(m) => match &m.initialize_expression {
+                    ir::Expression::Array(array) => match &array.elements {
+                        ir::ArrayElements::List(rows) => assert_eq!(
+                            rows[1].type_(),
+                            Type::Array {
+                                element_type: Box::new(Type::Int(IntType::I32)),
+                                size: 2
+                            }
+                        ),
+                        other => panic!("expected list, found {:?}", other),
+                    },
+                    other => panic!("expected array, found {:?}", other),
+                },
+                other => panic!("expected variable, found {:?}", other),
+            },
+            other => panic!("expected block, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "array elements differ in type, expected Array")]
+    fn nested_array_rows_differ_in_length() {
+        typecheck("fn f(): i32 => { let m: [[i32; 2]; 2] = [[1, 2], [3, 4, 5]]; m[0][0] };");
+    }
+
+    #[test]
+    #[should_panic(expected = "array elements differ in type, expected Int(I32), found Boolean")]
+    fn array_elements_differ_in_type() {
+        typecheck("fn f(): i32 => [1_i32, true][0];");
+    }
+
+    #[test]
+    #[should_panic(expected = "array of 4 elements can not be used as [u8; 2]")]
+    fn array_literal_too_long() {
+        typecheck("fn g(): [u8; 2] => [1, 2, 3, 4];");
+    }
+
+    #[test]
+    #[should_panic(expected = "array of 1 elements can not be used as [i32; 2]")]
+    fn array_literal_too_short() {
+        typecheck("fn f(): i32 => { let a: [i32; 2] = [1]; a[0] };");
+    }
+
+    #[test]
+    #[should_panic(expected = "array of 5 elements can not be used as [i32; 2]")]
+    fn array_repeat_literal_size_mismatch() {
+        typecheck("fn f(): [i32; 2] => [7; 5];");
+    }
+
+    #[test]
+    fn indexed_array_literal_takes_expected_type() {
+        let module = typecheck("fn f(): i32 => [1, 2, 3][0];");
+
+        match &module.functions[0].body {
+            ir::Expression::Access(access) => assert_eq!(
+                access.base.type_(),
+                Type::Array {
+                    element_type: Box::new(Type::Int(IntType::I32)),
+                    size: 3
+                }
+            ),
+            other => panic!("expected access, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "can not index into UInt(U8)")]
+    fn index_into_integer() {
+        typecheck("fn f(x: u8): u8 => x[0];");
+    }
+
+    #[test]
+    #[should_panic(expected = "can not assign to values, it is not a variable")]
+    fn assign_to_parameter() {
+        typecheck(
+            "
+            fn f(values: [u8; 2]): u8 => {
+                values[0] = 1;
+                values[0]
+            };
+            ",
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let module = typecheck(
+            "
+            struct Point { x: i32, y: i32 }
+            struct Line { from: Point, to: Point, }
+            extern fn length(line: Line): u32;
+
+            fn origin(): Point => Point { y: 0, x: 0 };
+            fn shift(line: Line, dx: i32): Line => {
+                let moved: Line = Line { from: line.from, to: origin() };
+                moved.to.x = moved.from.x + dx;
+                moved
+            };
+            fn quadrant(p: Point): u32 => match p.x > 0 {
+                true => length(Line { from: p, to: origin() }),
+                false => 0,
+            };
+            ",
+        );
+
+        assert_eq!(module.structs.len(), 2);
+        assert_eq!(module.extern_functions.len(), 1);
+
+        match &module.functions[0].body {
+            ir::Expression::Struct(struct_) => assert_eq!(struct_.fields.len(), 2),
+            other => panic!("expected struct, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "missing field y in initializer of Point")]
+    fn struct_missing_field() {
+        typecheck(
+            "
+            struct Point { x: i32, y: i32 }
+            fn f(): Point => Point { x: 1 };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Named(Identifier { value: \"Point\" }) has no field named z")]
+    fn struct_unknown_field() {
+        typecheck(
+            "
+            struct Point { x: i32, y: i32 }
+            fn f(p: Point): i32 => p.z;
+            ",
+        );
+    }
+
+    #[test]
+    fn enums() {
+        let module = typecheck(
+            "
+            struct Point { x: i32, y: i32 }
+            enum Shape { Empty, Circle(Point, u32), Rect(Point, Point), }
+
+            fn unit(): Shape => Shape::Circle(Point { x: 0, y: 0 }, 1);
+            fn width(s: Shape): i32 => match s {
+                Shape::Rect(from, to) => to.x - from.x,
+                Shape::Circle(_, r) => 2 * r as i32,
+                Shape::Empty => 0,
+            };
+            ",
+        );
+
+        assert_eq!(module.enums.len(), 1);
+
+        match &module.functions[0].body {
+            ir::Expression::Variant(variant) => {
+                assert_eq!(variant.tag, 1);
+                assert_eq!(variant.fields.len(), 2);
+            }
+            other => panic!("expected variant, found {:?}", other),
+        }
+
+        match &module.functions[1].body {
+            ir::Expression::Match(match_) => {
+                let bindings = &match_.arms[0].bindings;
+                assert_eq!(bindings.len(), 2);
+                assert_eq!(bindings[1].id.value, "to");
+                assert_eq!(bindings[1].field, 1);
+                assert!(matches!(
+                    match_.arms[0].patterns[0],
+                    ir::Pattern::Constant(2)
+                ));
+                assert_eq!(match_.arms[1].bindings.len(), 1);
+            }
+            other => panic!("expected match, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "non-exhaustive match: Shape::Circle not covered")]
+    fn non_exhaustive_enum_match() {
+        typecheck(
+            "
+            enum Shape { Empty, Circle(u32) }
+            fn f(s: Shape): u32 => match s { Shape::Empty => 0 };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "variant Shape::Circle expects 1 fields, found 0")]
+    fn variant_missing_payload() {
+        typecheck(
+            "
+            enum Shape { Empty, Circle(u32) }
+            fn f(): Shape => Shape::Circle;
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "enum List contains itself and would have infinite size")]
+    fn recursive_enum() {
+        typecheck(
+            "
+            enum List { Nil, Cons(u32, List) }
+            ",
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        let module = typecheck(
+            "
+            fn divmod(a: u32, b: u32): (u32, u32) => (a / b, a % b);
+            fn f(): u32 => {
+                let (q, r) = divmod(7, 2);
+                let t: (u8, (bool,)) = (1, (true,));
+                t.0 = 2;
+                q + r + t.0 as u32
+            };
+            ",
+        );
+
+        match &module.functions[1].body {
+            ir::Expression::Block(block) => match &block.statements[..] {
+                [ir::Statement::TupleDefinition(tuple), ir::Statement::VariableDefinition(variable), _] =>
+                {
+                    assert_eq!(tuple.ids.len(), 2);
+                    assert_eq!(
+                        tuple.type_,
+                        Type::Tuple(vec![Type::UInt(UIntType::U32), Type::UInt(UIntType::U32)])
+                    );
+                    assert_eq!(variable.initialize_expression.type_(), variable.type_);
+                }
+                other => panic!("unexpected statements {:?}", other),
+            },
+            other => panic!("expected block, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "can not destructure")]
+    fn destructure_wrong_arity() {
+        typecheck("fn f(t: (u8, u8)): u8 => { let (a, b, c) = t; a };");
+    }
+
+    #[test]
+    #[should_panic(expected = "can not infer the type of (a, b), add a type annotation")]
+    fn destructure_untyped_literals() {
+        typecheck("fn f(): u8 => { let (a, b) = (1, 2); a };");
+    }
+
+    #[test]
+    #[should_panic(expected = "has no field named 2")]
+    fn tuple_index_out_of_range() {
+        typecheck("fn f(t: (u8, u8)): u8 => t.2;");
+    }
+
+    #[test]
+    fn strings_and_print() {
+        let module = typecheck(
+            r#"
+            extern fn log(message: &[u8]): u32;
+            fn greet(name: str): isize => {
+                let written: isize = print("hello, \"");
+                let sent: u32 = log(name);
+                written + println(name)
+            };
+            "#,
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Block(block) => match &block.statements[0] {
+                ir::Statement::VariableDefinition(variable) => {
+                    match &variable.initialize_expression {
+                        ir::Expression::Print(print) => {
+                            assert!(!print.newline);
+                            match &print.argument {
+                                ir::Expression::Literal(ast::Literal::Str(value)) => {
+                                    assert_eq!(value, "hello, \"")
+                                }
+                                other => panic!("expected string literal, found {:?}", other),
+                            }
+                        }
+                        other => panic!("expected print, found {:?}", other),
+                    }
+                }
+                other => panic!("expected variable definition, found {:?}", other),
+            },
+            other => panic!("expected block, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "println expects a str, found UInt(U8)")]
+    fn print_non_string() {
+        typecheck("fn f(x: u8): isize => println(x);");
+    }
+
+    #[test]
+    fn index_and_length_of_slices() {
+        let module = typecheck(
+            "
+            fn last(s: str): u8 => s[len(s) - 1];
+            fn first(s: &[u8]): *const u8 => &s[0];
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Access(access) => {
+                assert_eq!(access.type_, Type::UInt(UIntType::U8));
+                assert_eq!(access.offset.type_(), Type::UInt(UIntType::USize));
+            }
+            other => panic!("expected access, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "can not write to an element of Slice(UInt(U8))")]
+    fn slice_elements_are_read_only() {
+        typecheck(
+            "
+            fn f(s: str): u8 => {
+                let t: str = s;
+                t[0] = 1;
+                t[0]
+            };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "len expects a slice, found Array")]
+    fn length_of_array() {
+        typecheck("fn f(a: [u8; 2]): usize => len(a);");
+    }
+
+    #[test]
+    fn invalid_escape_is_a_parse_error() {
+        let error = ModuleParser::new()
+            .parse(0, r#"fn f(): str => "tab\q";"#)
+            .unwrap_err();
+
+        match error {
+            lalrpop_util::ParseError::User { error } => assert_eq!(error.span, (19, 21)),
+            other => panic!("expected escape error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn chars() {
+        let module = typecheck(
+            r"
+            fn kind(c: char): u8 => match c {
+                'a'..='z' | '\u{e0}'..='\u{ff}' => 1,
+                '\n' | '\'' => 2,
+                _ => 0,
+            };
+            fn next(c: char): char => (c as u32 + 1) as char;
+            fn is_digit(c: char): bool => c >= '0' && c <= '9' && c != '\0';
+            fn from_byte(b: u8): char => b as char;
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Match(match_) => {
+                assert!(matches!(
+                    match_.arms[0].patterns[1],
+                    ir::Pattern::Range(0xe0, 0xff)
+                ));
+                assert!(matches!(
+                    match_.arms[1].patterns[1],
+                    ir::Pattern::Constant(0x27)
+                ));
+            }
+            other => panic!("expected match, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "non-exhaustive match: '\\0' not covered")]
+    fn non_exhaustive_char_match() {
+        typecheck("fn f(c: char): u8 => match c { '\\u{1}'..='\\u{10ffff}' => 1 };");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cast from UInt(U16) to Char")]
+    fn cast_u16_to_char() {
+        typecheck("fn f(x: u16): char => x as char;");
+    }
+
+    #[test]
+    fn surrogate_char_is_a_parse_error() {
+        let error = ModuleParser::new()
+            .parse(0, r"fn f(): char => '\u{d800}';")
+            .unwrap_err();
+
+        match error {
+            lalrpop_util::ParseError::User { error } => {
+                assert_eq!(error.span, (17, 25));
+                assert!(error.message.contains("surrogate"));
+            }
+            other => panic!("expected escape error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn too_large_int_literals_are_parse_errors() {
+        for (code, span) in [
+            (
+                "fn f(): u8 => 340282366920938463463374607431768211456;",
+                (14, 53),
+            ),
+            (
+                "fn f(): u8 => 0x1_0000_0000_0000_0000_0000_0000_0000_0000;",
+                (16, 57),
+            ),
+            (
+                "fn f(): u8 => 0o10000000000000000000000000000000000000000000;",
+                (16, 60),
+            ),
+        ] {
+            match ModuleParser::new().parse(0, code).unwrap_err() {
+                lalrpop_util::ParseError::User { error } => {
+                    assert_eq!(error.span, span);
+                    assert_eq!(error.message, "integer literal is too large");
+                }
+                other => panic!("expected out of range error, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn too_large_array_sizes_are_parse_errors() {
+        for (code, span) in [
+            (
+                "fn f(a: [i32; 99999999999999999999999]): i32 => 0;",
+                (14, 37),
+            ),
+            (
+                "fn f(): [u8; 1] => [0; 18_446_744_073_709_551_616];",
+                (23, 49),
+            ),
+        ] {
+            match ModuleParser::new().parse(0, code).unwrap_err() {
+                lalrpop_util::ParseError::User { error } => {
+                    assert_eq!(error.span, span);
+                    assert_eq!(error.message, "array size is too large");
+                }
+                other => panic!("expected out of range error, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn radix_literals() {
+        let module = typecheck(
+            "
+            fn mask(): u32 => 0xdead_BEEF & 0o7_7_7 | 0b1010_0101;
+            fn register(): u8 => 0xffu8;
+            fn small(): i8 => -0x80i8;
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Binary(or) => match (&or.lhs, &or.rhs) {
+                (
+                    ir::Expression::Binary(and),
+                    ir::Expression::Literal(ast::Literal::Int(rhs, _)),
+                ) => {
+                    assert!(matches!(
+                        and.lhs,
+                        ir::Expression::Literal(ast::Literal::Int(0xdead_beef, _))
+                    ));
+                    assert!(matches!(
+                        and.rhs,
+                        ir::Expression::Literal(ast::Literal::Int(0o777, _))
+                    ));
+                    assert_eq!(*rhs, 0b1010_0101);
+                }
+                other => panic!("unexpected operands {:?}", other),
+            },
+            other => panic!("expected binary expression, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wide_integers() {
+        let module = typecheck(
+            "
+            fn max(): u128 => 340282366920938463463374607431768211455;
+            fn min(): i128 => -170141183460469231731687303715884105728i128;
+            fn widen(x: i64): i128 => x as i128 * 0x1_0000_0000_0000_0000;
+            ",
+        );
+
+        assert_eq!(module.functions[0].return_type, Type::UInt(UIntType::U128));
+        assert_eq!(module.functions[2].body.type_(), Type::Int(IntType::I128));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "literal 170141183460469231731687303715884105728 out of range for Int(I128)"
+    )]
+    fn i128_literal_out_of_range() {
+        typecheck("fn f(): i128 => 170141183460469231731687303715884105728;");
+    }
+
+    #[test]
+    #[should_panic(expected = "literal 256 out of range for UInt(U8)")]
+    fn hex_literal_out_of_range() {
+        typecheck("fn f(): u8 => 0x1_00;");
+    }
+
+    #[test]
+    #[should_panic(expected = "literal 128 out of range for Int(I8)")]
+    fn suffixed_literal_out_of_range() {
+        typecheck("fn f(): i8 => 0b1000_0000i8;");
+    }
+
+    #[test]
+    fn floats() {
+        let module = typecheck(
+            "
+            fn half(x: f32): f32 => x * 0.5 - -1_000.0;
+            fn exponent(): f64 => 1e-3 + 2.5E+2 + 2f64;
+            fn pair(): (f32, f64) => (1.0 / 3.0, 0.1);
+            fn splat(): [f32; 4] => [0.5; 4];
+            fn convert(x: i32): bool => x as f64 as f32 >= 1.5f32;
+            fn truncate(x: f64): u8 => x as u8;
+            ",
+        );
+
+        assert_eq!(
+            module.functions[0].body.type_(),
+            Type::Float(FloatType::F32)
+        );
+        assert_eq!(
+            module.functions[3].body.type_(),
+            Type::Array {
+                element_type: Box::new(Type::Float(FloatType::F32)),
+                size: 4
+            }
+        );
+        assert_eq!(
+            module.functions[2].body.type_(),
+            Type::Tuple(vec![
+                Type::Float(FloatType::F32),
+                Type::Float(FloatType::F64)
+            ])
+        );
+
+        match &module.functions[1].body {
+            ir::Expression::Binary(add) => assert!(matches!(
+                add.rhs,
+                ir::Expression::Literal(ast::Literal::Float(
+                    value,
+                    LiteralFloatType::Suffixed(FloatType::F64)
+                )) if value == 2.0
+            )),
+            other => panic!("expected binary expression, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "integer literal 1 can not be used as Float(F64)")]
+    fn int_literal_as_float() {
+        typecheck("fn f(): f64 => 1;");
+    }
+
+    #[test]
+    #[should_panic(expected = "operands of Plus differ in type: Float(F32) and Float(F64)")]
+    fn mixed_float_widths() {
+        typecheck("fn f(x: f32, y: f64): f32 => x + y;");
+    }
+
+    #[test]
+    #[should_panic(expected = "operator BitAnd requires integer operands, found Float(F64)")]
+    fn float_bitwise() {
+        typecheck("fn f(x: f64): f64 => x & 1.0;");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cast from Boolean to Float(F64)")]
+    fn bool_as_float() {
+        typecheck("fn f(x: bool): f64 => x as f64;");
+    }
+
+    #[test]
+    fn pointers() {
+        let module = typecheck(
+            "
+            extern fn malloc(n: usize): *mut u8;
+            struct Node { value: i32, next: *const Node }
+            fn second(node: *const Node): i32 => (*(*node).next).value;
+            fn square(p: *mut i32): i32 => {
+                *p = *p * *p;
+                *offset(p, -1) = 0;
+                *p
+            };
+            fn local(): *const i32 => {
+                let x: i32 = 1;
+                let p: *mut i32 = &mut x;
+                &x
+            };
+            fn is_null(p: *mut u8): bool => p != null;
+            fn allocate(): *mut i64 => malloc(8) as *mut i64;
+            fn address(p: *const u8): usize => p as usize;
+            ",
+        );
+
+        assert_eq!(
+            module.structs[0].fields[1].1,
+            Type::Pointer {
+                mutable: false,
+                pointee: Box::new(Type::Named(ast::Identifier::from("Node".to_string()))),
+            }
+        );
+        assert_eq!(module.functions[0].body.type_(), Type::Int(IntType::I32));
+    }
+
+    #[test]
+    #[should_panic(expected = "can not write through a *const pointer")]
+    fn write_through_const_pointer() {
+        typecheck(
+            "
+            struct Point { x: i32, y: i32 }
+            fn f(p: *const Point): i32 => {
+                (*p).x = 1;
+                0
+            };
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "can not dereference Int(I32)")]
+    fn dereference_integer() {
+        typecheck("fn f(x: i32): i32 => *x;");
+    }
+
+    #[test]
+    #[should_panic(expected = "null can only be used as a pointer, found Int(I32)")]
+    fn null_as_integer() {
+        typecheck("fn f(): i32 => null;");
+    }
+
+    #[test]
+    #[should_panic(expected = "can only take the address of a variable, field, element or pointee")]
+    fn address_of_temporary() {
+        typecheck("fn f(): *const i32 => &1;");
+    }
+
+    #[test]
+    #[should_panic(expected = "can not assign to x, it is not a variable")]
+    fn mutable_address_of_parameter() {
+        typecheck("fn f(x: i32): *mut i32 => &mut x;");
+    }
+
+    #[test]
+    fn runtime_functions_are_in_scope() {
+        let module = typecheck(
+            "
+            fn grow(p: *mut u8): *mut u8 => realloc(p, 8, 8, 16);
+            fn release(p: *mut u8): () => free(p);
+            fn fail(): () => panic(\"unreachable\");
+            fn alloc(size: usize): usize => size;
+            fn shadowed(): usize => alloc(4);
+            ",
+        );
+
+        assert!(module.extern_functions.is_empty());
+        assert_eq!(module.functions[1].body.type_(), Type::Tuple(vec![]));
+        assert_eq!(
+            module.functions[4].body.type_(),
+            Type::UInt(UIntType::USize)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 1 arguments, found 2")]
+    fn runtime_exit_arguments() {
+        typecheck("fn f(): () => exit(1, 2);");
+    }
+
+    #[test]
+    fn modules() {
+        let module = typecheck(
+            "
+            mod shapes {
+                pub struct Point { x: i32, y: i32 }
+                pub enum Shape { Dot(Point), Empty }
+
+                pub fn origin(): Point => Point { x: 0, y: 0 };
+
+                pub mod area {
+                    fn of(s: Shape): i32 => match s { Shape::Dot(_) => 0, Shape::Empty => 0 };
+                    pub fn origin(): i32 => of(Shape::Empty);
+                }
+            }
+
+            struct Point { x: bool }
+
+            fn origin(): Point => Point { x: false };
+            fn x(p: shapes::Point): i32 => p.x;
+            fn y(): i32 => shapes::origin().y + shapes::area::origin();
+            fn dot(): shapes::Shape => shapes::Shape::Dot(shapes::Point { x: 1, y: 2 });
+            ",
+        );
+
+        let shapes = &module.modules[0];
+        assert_eq!(shapes.id.value, "shapes");
+        assert_eq!(shapes.structs[0].id.value, "shapes::Point");
+        assert_eq!(shapes.enums[0].id.value, "shapes::Shape");
+        assert_eq!(shapes.modules[0].functions[1].id.value, "origin");
+        assert_eq!(
+            shapes.functions[0].body.type_(),
+            Type::Named(ast::Identifier::from("shapes::Point".to_string()))
+        );
+        assert_eq!(
+            module.functions[0].body.type_(),
+            Type::Named(ast::Identifier::from("Point".to_string()))
+        );
+        assert_eq!(module.functions[2].body.type_(), Type::Int(IntType::I32));
+    }
+
+    #[test]
+    fn generic_functions() {
+        let module = typecheck(
+            "
+            trait Ord { fn gt(self, other: Self): bool; }
+            impl Ord for i32 { fn gt(self, other: i32): bool => self > other; }
+            impl Ord for u8 { fn gt(self, other: u8): bool => self > other; }
+            fn max<T: Ord>(a: T, b: T): T => match a.gt(b) { true => a, false => b };
+            fn f(): i32 => max(1, 2) + max::<i32>(3, max(4, 5));
+            fn g(x: u8): u8 => max(x, 1);
+            fn h(x: *const u8): u8 => max(*x, max::<u8>(1, 2));
+            ",
+        );
+
+        let ids: Vec<_> = module
+            .functions
+            .iter()
+            .map(|f| f.id.value.as_str())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "<i32 as Ord>::gt",
+                "<u8 as Ord>::gt",
+                "f",
+                "g",
+                "h",
+                "max::<i32>",
+                "max::<u8>"
+            ]
+        );
+        assert_eq!(module.functions[5].return_type, Type::Int(IntType::I32));
+        assert_eq!(module.functions[6].return_type, Type::UInt(UIntType::U8));
+    }
+
+    #[test]
+    fn generic_functions_in_modules() {
+        let module = typecheck(
+            "
+            mod pairs {
+                pub struct Pair { a: i32, b: i32 }
+                pub fn id<T>(x: T): T => x;
+                pub fn twice<T>(x: T): (T, T) => (id(x), id::<T>(x));
+            }
+
+            fn f(): (pairs::Pair, pairs::Pair) => pairs::twice(pairs::Pair { a: 1, b: 2 });
+            ",
+        );
+
+        let ids: Vec<_> = module.modules[0]
+            .functions
+            .iter()
+            .map(|f| f.id.value.as_str())
+            .collect();
+        assert_eq!(ids, ["twice::<pairs::Pair>", "id::<pairs::Pair>"]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "operator Plus requires integer or float operands, found Named(Identifier { value: \"T\" })"
+    )]
+    fn operators_on_type_parameters() {
+        typecheck("fn add<T>(a: T, b: T): T => a + b;");
+    }
+
+    #[test]
+    #[should_panic(expected = "type T has no method neg")]
+    fn methods_of_type_parameters_come_from_bounds() {
+        typecheck(
+            "
+            trait Add { fn add(self, other: Self): Self; }
+            trait Neg { fn neg(self): Self; }
+            impl Add for i32 { fn add(self, other: i32): i32 => self + other; }
+            impl Neg for i32 { fn neg(self): i32 => 0 - self; }
+            fn sub<T: Add>(a: T, b: T): T => a.add(b.neg());
+            fn f(): i32 => sub(2, 1);
+            ",
+        );
+    }
+
+    #[test]
+    fn generic_bodies_are_checked_without_instances() {
+        let module = typecheck(
+            "
+            trait Add { fn add(self, other: Self): Self; }
+            fn sum<T: Add>(a: T, b: T, c: T): T => a.add(b).add(c);
+            fn apply<T, U>(f: fn(T): U, x: T): U => f(x);
+            fn triple<T: Add>(x: T): fn(): T => || sum(x, x, x);
+            ",
+        );
+
+        assert!(module.functions.is_empty());
+        assert!(module.closures.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "unresolved name nonsense")]
+    fn uncalled_generic_with_unknown_name() {
+        typecheck("fn h<T>(x: T): T => nonsense(x);");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 1 arguments, found 2")]
+    fn uncalled_generic_with_wrong_arity() {
+        typecheck(
+            "
+            fn id<T>(x: T): T => x;
+            fn h<T>(x: T): T => id(x, x);
+            ",
+        );
+    }
+
+    #[test]
+    fn polymorphic_recursion_is_limited() {
+        let module = typecheck(
+            "
+            fn f<T>(x: T): i32 => f::<(T,)>((x,));
+            fn g(): i32 => f(1_i32);
+            ",
+        );
+
+        assert_eq!(module.errors.len(), 1);
+        assert_eq!(module.errors[0].span, Some((35, 50)));
+        assert_eq!(
+            module.errors[0].message,
+            "instantiating f recursively exceeds the limit of 64 nested instances"
+        );
+        assert_eq!(module.functions.len(), 1 + INSTANCE_DEPTH_LIMIT);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not infer type parameter T of zero")]
+    fn uninferred_type_parameter() {
+        typecheck(
+            "
+            fn zero<T>(): i32 => 0;
+            fn f(): i32 => zero();
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "function pick expects 2 type arguments, found 1")]
+    fn type_argument_count() {
+        typecheck(
+            "
+            fn pick<T, U>(a: T, b: U): T => a;
+            fn f(): i32 => pick::<i32>(1, 2);
+            ",
+        );
+    }
+
+    #[test]
+    fn traits() {
+        let module = typecheck(
+            "
+            trait Add { fn add(self, other: Self): Self; }
+            struct Point { x: i32, y: i32 }
+            impl Add for i32 { fn add(self, other: i32): i32 => self + other; }
+            impl Add for Point {
+                fn add(self, other: Self): Self => Point { x: self.x.add(other.x), y: 0 };
+            }
+            fn sum<T: Add>(a: T, b: T): T => a.add(b);
+            fn f(p: Point): i32 => sum(p, p).x + 1.add(2);
+            ",
+        );
+
+        let ids: Vec<_> = module
+            .functions
+            .iter()
+            .map(|f| f.id.value.as_str())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "<i32 as Add>::add",
+                "<Point as Add>::add",
+                "f",
+                "sum::<Point>"
+            ]
+        );
+        assert_eq!(
+            module.functions[1].parameters[0].1,
+            Type::Named(ast::Identifier::from("Point".to_string()))
+        );
+    }
+
+    #[test]
+    fn traits_in_modules() {
+        let module = typecheck(
+            "
+            mod num {
+                pub trait Double { fn double(self): Self; }
+                impl Double for u8 { fn double(self): u8 => self * 2; }
+            }
+            fn f(x: u8): u8 => x.double();
+            ",
+        );
+
+        assert_eq!(
+            module.modules[0].functions[0].id.value,
+            "<u8 as num::Double>::double"
+        );
+    }
+
+    #[test]
+    fn declared_methods() {
+        let module = typecheck(
+            "
+            trait Area { fn area(self): i32; }
+            struct Rect { w: i32, h: i32 }
+            impl Area for Rect { fn area(self): i32 = \"geo::<Rect as Area>::area\"; }
+            impl Rect { pub fn new(w: i32, h: i32): Self = \"geo::<Rect>::new\"; }
+            fn f(): i32 => Rect::new(2, 3).area();
+            ",
+        );
+
+        let declarations: Vec<_> = module
+            .function_declarations
+            .iter()
+            .map(|f| (f.id.value.as_str(), f.symbol.as_str()))
+            .collect();
+        assert_eq!(
+            declarations,
+            [
+                ("<Rect as Area>::area", "geo::<Rect as Area>::area"),
+                ("<Rect>::new", "geo::<Rect>::new")
+            ]
+        );
+        assert_eq!(
+            module.function_declarations[1].return_type,
+            Type::Named(ast::Identifier::from("Rect".to_string()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicting implementations of trait Zero for type i32")]
+    fn conflicting_impls() {
+        typecheck(
+            "
+            trait Zero { fn zero(self): Self; }
+            impl Zero for i32 { fn zero(self): i32 => 0; }
+            impl Zero for i32 { fn zero(self): i32 => 1; }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "impl of trait Add for type bool is missing method add")]
+    fn missing_method() {
+        typecheck(
+            "
+            trait Add { fn add(self, other: Self): Self; }
+            impl Add for bool {}
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "method add of the impl of trait Add for type u8 has type fn(u8, i32): u8, the trait expects fn(u8, u8): u8"
+    )]
+    fn method_signature() {
+        typecheck(
+            "
+            trait Add { fn add(self, other: Self): Self; }
+            impl Add for u8 { fn add(self, other: i32): u8 => self; }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "type bool does not implement trait Add, required by type parameter T of sum"
+    )]
+    fn unsatisfied_bound() {
+        typecheck(
+            "
+            trait Add { fn add(self, other: Self): Self; }
+            impl Add for i32 { fn add(self, other: i32): i32 => self + other; }
+            fn sum<T: Add>(a: T, b: T): T => a.add(b);
+            fn f(): bool => sum(true, false);
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "type bool has no method add")]
+    fn unknown_method() {
+        typecheck("fn f(b: bool): bool => b.add(b);");
+    }
+
+    #[test]
+    fn inherent_impls() {
+        let module = typecheck(
+            "
+            trait Len { fn len(self): u32; }
+            mod geo {
+                pub struct Point { x: i32, y: i32 }
+                impl Point {
+                    pub fn new(x: i32, y: i32): Self => Self { x: x, y: y };
+                    pub fn len(self): u32 => (self.x + self.y) as u32;
+                }
+                impl Point { pub fn origin(): Point => Self::new(0, 0); }
+            }
+            impl Len for geo::Point { fn len(self): u32 => 0; }
+            fn f(): u32 => geo::Point::new(1, 2).len() + geo::Point::len(geo::Point::origin());
+            ",
+        );
+
+        let ids: Vec<_> = module.modules[0]
+            .functions
+            .iter()
+            .map(|f| f.id.value.as_str())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "<geo::Point>::new",
+                "<geo::Point>::len",
+                "<geo::Point>::origin"
+            ]
+        );
+
+        // Methods of the type are found before those of its traits.
+        let callee = match &module.functions[1].body {
+            ir::Expression::Binary(binary) => match &binary.lhs {
+                ir::Expression::Call(call) => &call.callee,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert!(
+            matches!(callee, ir::Expression::Identifier(id) if id.id.value == "geo::<geo::Point>::len")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "new is an associated function of type Point, call it as Point::new")]
+    fn associated_function_called_as_method() {
+        typecheck(
+            "
+            struct Point { x: i32 }
+            impl Point { fn new(): Point => Point { x: 0 }; }
+            fn f(p: Point): Point => p.new();
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "function len of type Point is defined more than once")]
+    fn inherent_function_defined_twice() {
+        typecheck(
+            "
+            struct Point { x: i32 }
+            impl Point { fn len(self): i32 => 0; }
+            impl Point { fn len(self): i32 => 1; }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "impl without a trait is only allowed for structs, enums and newtypes, found i32"
+    )]
+    fn inherent_impl_of_primitive() {
+        typecheck("impl i32 { fn zero(): i32 => 0; }");
+    }
+
+    #[test]
+    fn function_values() {
+        let module = typecheck(
+            "
+            struct Op { apply: fn(i32, i32): i32 }
+            fn add(a: i32, b: i32): i32 => a + b;
+            fn pick(): fn(i32, i32): i32 => add;
+            fn f(op: Op): i32 => {
+                let g: fn(i32, i32): i32 = pick();
+                g(1, 2) + op.apply(3, 4)
+            };
+            ",
+        );
+
+        assert_eq!(
+            module.functions[1].return_type,
+            Type::Function {
+                parameters: vec![Type::Int(IntType::I32), Type::Int(IntType::I32)],
+                return_type: Box::new(Type::Int(IntType::I32)),
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "extern function abs can only be called")]
+    fn extern_functions_are_not_values() {
+        typecheck(
+            "
+            extern fn abs(x: i32): i32;
+            fn f(): fn(i32): i32 => abs;
+            ",
+        );
+    }
+
+    #[test]
+    fn closures() {
+        let module = typecheck(
+            "
+            fn apply<T>(f: fn(T): T, x: T): T => f(x);
+            fn twice(f: fn(i32): i32, x: i32): i32 => f(f(x));
+            fn adder(n: i32): fn(i32): i32 => |x: i32| x + n;
+            fn f(k: i32): i32 => {
+                let add_k: fn(i32): i32 = |x: i32| x + k;
+                apply(add_k, 1) + twice(|x: i32| x * 2, 3) + (|| k)()
+            };
+            ",
+        );
+
+        assert_eq!(module.closures.len(), 4);
+
+        let adder = &module.closures[0];
+        assert_eq!(adder.id.value, "{closure#0}");
+        assert_eq!(
+            adder.captures,
+            vec![(
+                ast::Identifier::from("n".to_string()),
+                Type::Int(IntType::I32)
+            )]
+        );
+        assert_eq!(adder.return_type, Type::Int(IntType::I32));
+
+        let twice = &module.closures[2];
+        assert!(twice.captures.is_empty());
+        assert_eq!(module.closures[3].return_type, Type::Int(IntType::I32));
+    }
+
+    #[test]
+    #[should_panic(expected = "can not assign to k, it is not a variable")]
+    fn closure_captures_are_copies() {
+        typecheck(
+            "
+            fn f(): () => {
+                let k: i32 = 0;
+                let g: fn(): () = || { k = 1; };
+            };
+            ",
+        );
+    }
+
+    #[test]
+    fn type_aliases() {
+        let module = typecheck(
+            "
+            mod units {
+                pub type Meters = u32;
+                pub enum Unit { Metric, Imperial }
+            }
+            type Length = units::Meters;
+            type System = units::Unit;
+            fn add(a: Length, b: u32): units::Meters => a + b;
+            fn metric(s: System): bool => match s { System::Metric => true, _ => false };
+            fn imperial(): units::Unit => System::Imperial;
+            ",
+        );
+
+        let add = &module.functions[0];
+        assert_eq!(add.parameters[0].1, Type::UInt(UIntType::U32));
+        assert_eq!(add.return_type, Type::UInt(UIntType::U32));
+        assert_eq!(
+            module.functions[1].parameters[0].1,
+            Type::Named(ast::Identifier::from("units::Unit".to_string()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "type alias A refers to itself: A -> B -> A")]
+    fn type_alias_cycle() {
+        typecheck("type A = (B, i32); type B = *const A;");
+    }
+
+    #[test]
+    #[should_panic(expected = "type alias B refers to itself: B -> C -> B")]
+    fn type_alias_cycle_of_plain_aliases() {
+        typecheck("type A = B; type B = C; type C = B;");
+    }
+
+    #[test]
+    #[should_panic(expected = "type alias m::A refers to itself: m::A -> m::A")]
+    fn type_alias_naming_itself() {
+        typecheck("mod m { pub type A = [A; 2]; }");
+    }
+
+    #[test]
+    fn newtypes() {
+        let module = typecheck(
+            "
+            newtype UserId = u64;
+            impl UserId {
+                fn next(self): UserId => UserId(self.0 + 1);
+            }
+            fn f(id: UserId): u64 => id.next().0;
+            ",
+        );
+
+        let user_id = Type::Named(ast::Identifier::from("UserId".to_string()));
+        assert_eq!(module.newtypes[0].type_, Type::UInt(UIntType::U64));
+        assert_eq!(module.functions[0].return_type, user_id);
+        assert_eq!(module.functions[1].parameters[0].1, user_id);
+        assert_eq!(module.functions[1].body.type_(), Type::UInt(UIntType::U64));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected return type UInt(U64) differs")]
+    fn newtypes_are_distinct_types() {
+        typecheck(
+            "
+            newtype UserId = u64;
+            fn f(id: UserId): u64 => id;
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "newtype List refers to itself")]
+    fn newtype_refers_to_itself() {
+        typecheck("newtype List = (i32, *const List);");
+    }
+
+    #[test]
+    #[should_panic(expected = "Empty has no size and can not be passed to C")]
+    fn extern_zero_sized_parameter() {
+        typecheck(
+            "
+            struct Empty {}
+            extern fn f(e: Empty): i32;
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "[i32; 0] has no size and can not be passed to C")]
+    fn extern_zero_sized_return_type() {
+        typecheck("extern fn f(): [i32; 0];");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "parameter p of f: (f32, f32) contains floats and can not be passed to C by value"
+    )]
+    fn extern_float_aggregate_parameter() {
+        typecheck("extern fn f(p: (f32, f32)): bool;");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "return type of f: Big is larger than 16 bytes and can not be passed to C by value"
+    )]
+    fn extern_large_struct_return_type() {
+        typecheck(
+            "
+            struct Big { a: u64, b: u8, c: u64 }
+            extern fn f(): Big;
+            ",
+        );
+    }
+
+    #[test]
+    fn extern_aggregates_up_to_16_bytes() {
+        typecheck(
+            "
+            struct Pair { a: u64, b: u32, c: u16 }
+            enum Shape { Dot(u8), Line(u32, u32, u8) }
+            newtype Meters = f64;
+            extern fn f(p: Pair, s: Shape, m: Meters, x: f32, text: &[u8]): (u8, i64);
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "parameter s of f: Shape is larger than 16 bytes")]
+    fn extern_large_enum_parameter() {
+        typecheck(
+            "
+            enum Shape { Dot(u8), Line(u64, u64) }
+            extern fn f(s: Shape): ();
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "fn(i32): i32 contains a closure and can not be passed to C")]
+    fn extern_closure_parameter() {
+        typecheck("extern fn qsort(compare: fn(i32): i32): ();");
+    }
+
+    #[test]
+    #[should_panic(expected = "Callbacks contains a closure and can not be passed to C")]
+    fn extern_struct_with_closure_return_type() {
+        typecheck(
+            "
+            struct Callbacks { on_exit: fn(): () }
+            extern fn callbacks(): Callbacks;
+            ",
+        );
+    }
+
+    #[test]
+    fn module_constants() {
+        let module = typecheck(
+            "
+            mod a {
+                pub let c: i32 = 4;
+                pub let limit: u8 = 200;
+                pub fn twice(): i32 => c * 2;
+            }
+            let negative: f32 = -1.5;
+            fn f(): i32 => a::c + a::twice();
+            fn g(x: u8): bool => x < a::limit;
+            ",
+        );
+
+        match &module.functions[0].body {
+            ir::Expression::Binary(binary) => assert!(matches!(
+                binary.lhs,
+                ir::Expression::Literal(ast::Literal::Int(4, LiteralIntType::Signed(IntType::I32)))
+            )),
+            other => panic!("expected binary expression, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "constant c must be initialized with a literal")]
+    fn module_constant_not_a_literal() {
+        typecheck("let c: i32 = 1 + 2;");
+    }
+
+    #[test]
+    #[should_panic(expected = "can not assign to c, it is not a variable")]
+    fn assign_to_module_constant() {
+        typecheck("let c: i32 = 1; fn f(): i32 => { c = 2; c };");
+    }
+
+    #[test]
+    fn function_declarations() {
+        let module = typecheck(
+            "
+            mod lib {
+                pub struct Pair { a: i32, b: i32 }
+                pub fn swap(p: Pair): Pair = \"root::pair::swap\";
+            }
+
+            fn f(): i32 => lib::swap(lib::Pair { a: 1, b: 2 }).a;
+            ",
+        );
+
+        let swap = &module.modules[0].function_declarations[0];
+        assert_eq!(swap.symbol, "root::pair::swap");
+        assert_eq!(
+            swap.return_type,
+            Type::Named(ast::Identifier::from("lib::Pair".to_string()))
+        );
+        assert_eq!(module.functions[0].body.type_(), Type::Int(IntType::I32));
+    }
+
+    #[test]
+    #[should_panic(expected = "Function declaration inside block is not allowed")]
+    fn function_declaration_in_block() {
+        typecheck("fn f(): i32 => { fn g(): i32 = \"g\"; 0 };");
+    }
+
+    #[test]
+    fn use_imports() {
+        let module = typecheck(
+            "
+            mod geometry {
+                pub mod shapes {
+                    pub struct Point { x: i32, y: i32 }
+                    pub fn origin(): Point => Point { x: 0, y: 0 };
+                    fn secret(): i32 => 7;
+                    pub mod nested { pub fn peek(): i32 => secret() + geometry::helper(); }
+                }
+
+                use shapes::origin;
+
+                fn helper(): i32 => 1;
+                pub fn hidden(): i32 => origin().x;
+            }
+
+            use geometry::shapes::Point;
+            use geometry::shapes::origin as zero;
+            use geometry::shapes::nested::*;
+
+            fn x(p: Point): i32 => p.x;
+            fn y(): i32 => zero().y + peek() + geometry::hidden();
+            ",
+        );
+
+        assert_eq!(
+            module.functions[0].parameters[0].1,
+            Type::Named(ast::Identifier::from("geometry::shapes::Point".to_string()))
+        );
+        assert_eq!(module.functions[1].body.type_(), Type::Int(IntType::I32));
+    }
+
+    #[test]
+    #[should_panic(expected = "item math::secret is private")]
+    fn private_function() {
+        typecheck(
+            "
+            mod math { fn secret(): i32 => 1; }
+            fn f(): i32 => math::secret();
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "item math::inner is private")]
+    fn private_module() {
+        typecheck(
+            "
+            mod math { mod inner { pub fn f(): i32 => 1; } }
+            use math::inner::f;
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unresolved import math::nope")]
+    fn unresolved_import() {
+        typecheck(
+            "
+            mod math { pub fn add(a: i32, b: i32): i32 => a + b; }
+            use math::nope;
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "max is ambiguous, it may refer to a::max or b::max")]
+    fn ambiguous_glob_imports() {
+        typecheck(
+            "
+            mod a { pub fn max(): i32 => 1; }
+            mod b { pub fn max(): i32 => 2; }
+            use a::*;
+            use b::*;
+            fn f(): i32 => max();
+            ",
+        );
+    }
+
+    #[test]
+    fn glob_imports_of_different_names() {
+        typecheck(
+            "
+            mod a { pub fn max(): i32 => 1; }
+            mod b { pub fn min(): i32 => 2; }
+            use a::*;
+            use b::*;
+            use a::*;
+            fn f(): i32 => max() + min();
+            ",
+        );
+    }
+
+    fn resolve_diagnostics(code: &str) -> Vec<resolve::Diagnostic> {
+        let mut module = ModuleParser::new().parse(0, code).unwrap();
+        resolve::resolve_names(&mut module).unwrap_err()
+    }
+
+    #[test]
+    fn ambiguous_names_point_at_their_use() {
+        let code = "
+            mod a { pub fn max(): i32 => 1; }
+            mod b { pub fn max(): i32 => 2; }
+            use a::*;
+            use b::*;
+            fn f(): i32 => max();
+            ";
+        let start = code.rfind("max()").unwrap();
+        let diagnostics = resolve_diagnostics(code);
+        assert_eq!(diagnostics[0].span, Some((start, start + 3)));
+    }
+
+    #[test]
+    fn unresolved_names_are_reported_with_their_span() {
+        let code = "
+            mod m { pub struct P { x: i32 } }
+            fn f(p: m::Q): i32 => nonsense(1) + m::g() + match 1 { m::E::A => 1, _ => 2 };
+            ";
+        let diagnostics = resolve_diagnostics(code);
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "unknown type m::Q",
+                "unresolved name nonsense",
+                "unresolved path m::g",
+                "unresolved path m::E::A",
+            ]
+        );
+
+        let span = |text: &str| {
+            let start = code.find(text).unwrap();
+            Some((start, start + text.len()))
+        };
+        assert_eq!(diagnostics[0].span, span("m::Q"));
+        assert_eq!(diagnostics[1].span, span("nonsense"));
+        assert_eq!(diagnostics[2].span, span("m::g"));
+        assert_eq!(diagnostics[3].span, span("m::E::A"));
+    }
+
+    #[test]
+    #[should_panic(expected = "y refers to x, which is hidden by a local variable")]
+    fn import_hidden_by_local() {
+        typecheck(
+            "
+            mod m { use x as y; pub fn f(x: i32): i32 => y; }
+            fn x(): i32 => 0;
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unresolved path math::sub")]
+    fn unresolved_module_item() {
+        typecheck(
+            "
+            mod math { fn add(a: i32, b: i32): i32 => a + b; }
+            fn f(): i32 => math::sub(1, 2);
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown type Point")]
+    fn module_types_are_qualified() {
+        typecheck(
+            "
+            mod shapes { struct Point { x: i32 } }
+            fn f(p: Point): i32 => 0;
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "struct A contains itself and would have infinite size")]
+    fn recursive_struct() {
+        typecheck(
+            "
+            struct A { b: [B; 2] }
+            struct B { a: A }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown type Pointt")]
+    fn unknown_type() {
+        typecheck("fn f(p: Pointt): i32 => 0;");
+    }
+
+    #[test]
+    fn coverage_merges_intervals() {
+        let mut coverage = Coverage::new();
+        coverage.insert((0, 4));
+        coverage.insert((10, 20));
+        coverage.insert((5, 9));
+
+        assert!(coverage.contains((3, 15)));
+        assert_eq!(coverage.first_gap((0, 20)), None);
+        assert_eq!(coverage.first_gap((0, 255)), Some(21));
+
+        coverage.insert((21, u128::MAX));
+        assert_eq!(coverage.first_gap((0, u128::MAX)), None);
+    }
+}
@@ -6,6 +6,12 @@ pub trait AstVisitor<Environment, Return> {
 
     fn visit_module_statement(&mut self, env: Environment, m: &ModuleStatement) -> Return;
     fn visit_statement(&mut self, env: Environment, s: &Statement) -> Return;
+    fn visit_module_declaration_statement(
+        &mut self,
+        env: Environment,
+        m: &ModuleDeclarationStatement,
+    ) -> Return;
+    fn visit_use_statement(&mut self, env: Environment, use_: &UseStatement) -> Return;
     fn visit_extern_function_declaration_statement(
         &mut self,
         env: Environment,
@@ -16,6 +22,11 @@ pub trait AstVisitor<Environment, Return> {
         env: Environment,
         function: &FunctionDefinitionStatement,
     ) -> Return;
+    fn visit_function_declaration_statement(
+        &mut self,
+        env: Environment,
+        function: &FunctionDeclarationStatement,
+    ) -> Return;
     fn visit_variable_definition_statement(
         &mut self,
         env: Environment,
@@ -26,14 +37,60 @@ pub trait AstVisitor<Environment, Return> {
         env: Environment,
         return_statement: &ReturnStatement,
     ) -> Return;
+    fn visit_assignment_statement(
+        &mut self,
+        env: Environment,
+        assignment: &AssignmentStatement,
+    ) -> Return;
+    fn visit_struct_definition_statement(
+        &mut self,
+        env: Environment,
+        struct_: &StructDefinitionStatement,
+    ) -> Return;
+    fn visit_enum_definition_statement(
+        &mut self,
+        env: Environment,
+        enum_: &EnumDefinitionStatement,
+    ) -> Return;
+    fn visit_type_alias_statement(
+        &mut self,
+        env: Environment,
+        alias: &TypeAliasStatement,
+    ) -> Return;
+    fn visit_newtype_statement(&mut self, env: Environment, newtype: &NewtypeStatement) -> Return;
+    fn visit_trait_definition_statement(
+        &mut self,
+        env: Environment,
+        trait_: &TraitDefinitionStatement,
+    ) -> Return;
+    fn visit_impl_statement(&mut self, env: Environment, impl_: &ImplStatement) -> Return;
+    fn visit_tuple_definition_statement(
+        &mut self,
+        env: Environment,
+        tuple: &TupleDefinitionStatement,
+    ) -> Return;
 
     fn visit_expression(&mut self, env: Environment, expression: &Expression) -> Return;
     fn visit_block_expression(&mut self, env: Environment, block: &BlockExpression) -> Return;
     fn visit_if_expression(&mut self, env: Environment, if_expression: &IfExpression) -> Return;
+    fn visit_match_expression(&mut self, env: Environment, match_: &MatchExpression) -> Return;
     fn visit_unary_expression(&mut self, env: Environment, unary: &UnaryExpression) -> Return;
     fn visit_binary_expression(&mut self, env: Environment, binary: &BinaryExpression) -> Return;
     fn visit_call_expression(&mut self, env: Environment, call: &CallExpression) -> Return;
     fn visit_access_expression(&mut self, env: Environment, access: &AccessExpression) -> Return;
+    fn visit_cast_expression(&mut self, env: Environment, cast: &CastExpression) -> Return;
+    fn visit_array_expression(&mut self, env: Environment, array: &ArrayExpression) -> Return;
+    fn visit_struct_expression(&mut self, env: Environment, struct_: &StructExpression) -> Return;
+    fn visit_field_expression(&mut self, env: Environment, field: &FieldExpression) -> Return;
+    fn visit_path_expression(&mut self, env: Environment, path: &PathExpression) -> Return;
+    fn visit_instantiation_expression(
+        &mut self,
+        env: Environment,
+        instantiation: &InstantiationExpression,
+    ) -> Return;
+    fn visit_tuple_expression(&mut self, env: Environment, tuple: &TupleExpression) -> Return;
+    fn visit_closure_expression(&mut self, env: Environment, closure: &ClosureExpression)
+        -> Return;
 
     fn visit_identifier(&mut self, env: Environment, id: &Identifier) -> Return;
 }
@@ -58,6 +115,22 @@ impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Envir
     }
 }
 
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for ModuleDeclarationStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_module_declaration_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for UseStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_use_statement(env, self)
+    }
+}
+
 impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
     for ExternFunctionDeclarationStatement
 {
@@ -74,6 +147,14 @@ impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Envir
     }
 }
 
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for FunctionDeclarationStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_function_declaration_statement(env, self)
+    }
+}
+
 impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
     for VariableDefinitionStatement
 {
@@ -90,6 +171,70 @@ impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Envir
     }
 }
 
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for AssignmentStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_assignment_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for StructDefinitionStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_struct_definition_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for EnumDefinitionStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_enum_definition_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for TypeAliasStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_type_alias_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for NewtypeStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_newtype_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for TraitDefinitionStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_trait_definition_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for ImplStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_impl_statement(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for TupleDefinitionStatement
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_tuple_definition_statement(env, self)
+    }
+}
+
 impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
     for Expression
 {
@@ -114,6 +259,14 @@ impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Envir
     }
 }
 
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for MatchExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_match_expression(env, self)
+    }
+}
+
 impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
     for UnaryExpression
 {
@@ -146,6 +299,70 @@ impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Envir
     }
 }
 
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for CastExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_cast_expression(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for ArrayExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_array_expression(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for StructExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_struct_expression(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for FieldExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_field_expression(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for PathExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_path_expression(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for InstantiationExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_instantiation_expression(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for TupleExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_tuple_expression(env, self)
+    }
+}
+
+impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
+    for ClosureExpression
+{
+    fn accept(&self, env: Environment, visitor: &mut V) -> Return {
+        visitor.visit_closure_expression(env, self)
+    }
+}
+
 impl<V: AstVisitor<Environment, Return>, Environment, Return> Visitable<V, Environment, Return>
     for Identifier
 {
@@ -1,8 +1,17 @@
+use crate::newtypes;
+use crate::runtime;
 use llvm_sys as llvm;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
-use toy_parser::ast::{BinaryOperator, IntType, Literal, LiteralIntType};
-use toy_parser::typecheck::ir::{BinaryExpression, BlockExpression, Expression, Module};
+use toy_parser::ast::{
+    BinaryOperator, FloatType, Identifier, Literal, LiteralIntType, Type, UIntType, UnaryOperator,
+};
+use toy_parser::typecheck::ir::{
+    ArrayElements, ArrayExpression, BinaryExpression, BlockExpression, CallExpression,
+    CastExpression, Closure, ClosureExpression, EnumDefinition, Expression, Function,
+    MatchExpression, Module, OffsetExpression, Pattern, PrintExpression, Statement,
+    UnaryExpression, VariantExpression,
+};
 
 pub struct ActivationRecords {
     table_stack: Vec<HashMap<String, *mut llvm::LLVMValue>>,
@@ -15,14 +24,6 @@ impl ActivationRecords {
         }
     }
 
-    fn contains_symbol(&self, id: &String) -> bool {
-        self.table_stack
-            .iter()
-            .rev()
-            .find_map(|table| Some(table.contains_key(id)))
-            .unwrap()
-    }
-
     fn insert(&mut self, id: String, value: *mut llvm::LLVMValue) {
         self.table_stack.last_mut().unwrap().insert(id, value);
     }
@@ -48,148 +49,3625 @@ impl std::ops::Index<&String> for ActivationRecords {
     }
 }
 
-pub fn populate_llvm_module(llvm_module: *mut llvm::LLVMModule, src_module: Module) -> () {
-    let builder = unsafe { llvm::core::LLVMCreateBuilder() };
+/// Code generation settings that do not change the meaning of a program.
+pub struct Options {
+    /// Trap on out of range array indices instead of reading past the array.
+    pub bounds_checks: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            bounds_checks: true,
+        }
+    }
+}
+
+pub fn populate_llvm_module(
+    llvm_module: *mut llvm::LLVMModule,
+    src_module: Module,
+    options: &Options,
+) {
+    let mut src_module = src_module;
+    newtypes::erase_newtypes(&mut src_module);
+
+    let llvm_context = unsafe { llvm::core::LLVMGetModuleContext(llvm_module) };
+    let builder = unsafe { llvm::core::LLVMCreateBuilderInContext(llvm_context) };
 
     let mut activation_records = ActivationRecords::new();
 
-    for function in &src_module.functions {
-        let mut parameter_types =
-            unsafe { vec![llvm::core::LLVMInt32Type(); function.parameters.len()] };
+    // Types are named after the module they are defined in, so the types of
+    // every module can share the LLVM module.
+    let modules = nested_modules(&src_module);
+    let structs: Vec<_> = modules.iter().flat_map(|m| &m.structs).collect();
+    let enums: Vec<_> = modules.iter().flat_map(|m| &m.enums).collect();
+
+    // Struct types are created before their bodies are set, so that fields
+    // can refer to structs defined later in the module.
+    let llvm_structs: Vec<_> = structs
+        .iter()
+        .map(|struct_| unsafe {
+            let c_name = CString::new(struct_.id.value.as_str()).unwrap();
+
+            llvm::core::LLVMStructCreateNamed(llvm_context, c_name.as_ptr())
+        })
+        .collect();
+
+    for enum_ in &enums {
+        let c_name = CString::new(enum_.id.value.as_str()).unwrap();
+
+        unsafe { llvm::core::LLVMStructCreateNamed(llvm_context, c_name.as_ptr()) };
+    }
+
+    for (struct_, llvm_struct) in structs.iter().zip(llvm_structs) {
+        let mut field_types: Vec<_> = struct_
+            .fields
+            .iter()
+            .map(|(_id, type_)| llvm_type(llvm_module, type_))
+            .collect();
+
+        unsafe {
+            llvm::core::LLVMStructSetBody(
+                llvm_struct,
+                field_types.as_mut_ptr(),
+                field_types.len() as std::os::raw::c_uint,
+                0,
+            )
+        };
+    }
+
+    // The layout of an enum depends on the size of its payloads, so its body
+    // is only set once every type it stores is sized.
+    let mut pending_enums = enums;
+
+    while !pending_enums.is_empty() {
+        let (ready, pending): (Vec<_>, Vec<_>) = pending_enums.into_iter().partition(|enum_| {
+            enum_
+                .variants
+                .iter()
+                .flat_map(|(_id, types)| types)
+                .all(|type_| unsafe {
+                    llvm::core::LLVMTypeIsSized(llvm_type(llvm_module, type_)) != 0
+                })
+        });
+
+        if ready.is_empty() {
+            panic!("enum {} has no finite layout", pending[0].id.value);
+        }
+
+        for enum_ in ready {
+            set_llvm_enum_body(llvm_module, enum_);
+        }
+
+        pending_enums = pending;
+    }
+
+    for (name, llvm_function) in runtime::declare_runtime_functions(llvm_module) {
+        activation_records.insert(name, llvm_function);
+    }
+
+    let functions = declare_llvm_functions(
+        llvm_module,
+        &src_module,
+        std::slice::from_ref(&src_module.id.value),
+    );
+
+    // Items are named by their path from the root module, so one scope holds
+    // the functions of every module, and takes precedence over the runtime.
+    activation_records.push();
+
+    for (id, llvm_function) in functions.by_path() {
+        activation_records.insert(id, llvm_function);
+    }
+
+    build_llvm_module(
+        llvm_module,
+        builder,
+        &mut activation_records,
+        options,
+        &src_module,
+        &functions,
+    );
+
+    activation_records.pop();
+
+    runtime::define_runtime_functions(llvm_module);
+
+    unsafe {
+        llvm::core::LLVMDisposeBuilder(builder);
+    };
+}
+
+/// `module` followed by the modules nested in it, outermost first.
+fn nested_modules(module: &Module) -> Vec<&Module> {
+    let mut modules = vec![module];
+
+    for nested in &module.modules {
+        modules.extend(nested_modules(nested));
+    }
+
+    modules
+}
+
+/// The LLVM functions declared for a module and for the modules nested in it.
+struct LlvmModuleFunctions {
+    extern_functions: Vec<(String, *mut llvm::LLVMValue)>,
+    functions: Vec<(String, *mut llvm::LLVMValue)>,
+    function_declarations: Vec<(String, *mut llvm::LLVMValue)>,
+    closures: Vec<(String, *mut llvm::LLVMValue)>,
+    modules: Vec<(String, LlvmModuleFunctions)>,
+}
+
+impl LlvmModuleFunctions {
+    /// The functions of the module and of the modules nested in it, named by
+    /// their path from the module as in the typechecked tree.
+    fn by_path(&self) -> Vec<(String, *mut llvm::LLVMValue)> {
+        let mut visible: Vec<_> = self
+            .extern_functions
+            .iter()
+            .chain(&self.functions)
+            .chain(&self.function_declarations)
+            .chain(&self.closures)
+            .cloned()
+            .collect();
+
+        for (module, functions) in &self.modules {
+            visible.extend(
+                functions
+                    .by_path()
+                    .into_iter()
+                    .map(|(id, function)| (format!("{}::{}", module, id), function)),
+            );
+        }
+
+        visible
+    }
+}
+
+/// Declares the functions of `module`, found at `path` from the root module.
+/// Functions are named by their full path, as in `root::math::add`, extern
+/// functions keep their C name and declared functions the symbol they are
+/// declared with. Only `pub` functions and `main` are visible outside of the
+/// object they are defined in.
+fn declare_llvm_functions(
+    llvm_module: *mut llvm::LLVMModule,
+    module: &Module,
+    path: &[String],
+) -> LlvmModuleFunctions {
+    let llvm_context = unsafe { llvm::core::LLVMGetModuleContext(llvm_module) };
+    let mut extern_functions = vec![];
+    let mut functions = vec![];
+    let mut function_declarations = vec![];
+    let mut closures = vec![];
+
+    for function in &module.extern_functions {
+        let mut parameter_types: Vec<_> = function
+            .parameters
+            .iter()
+            .map(|(_id, type_)| llvm_c_abi_type(llvm_module, type_))
+            .collect();
+
+        // Functions returning `()` return nothing in C.
+        let return_type = match &function.return_type {
+            Type::Tuple(types) if types.is_empty() => unsafe {
+                llvm::core::LLVMVoidTypeInContext(llvm_context)
+            },
+            type_ => llvm_c_abi_type(llvm_module, type_),
+        };
 
         let function_type = unsafe {
             llvm::core::LLVMFunctionType(
-                llvm::core::LLVMInt32Type(),
-                parameter_types.as_mut_ptr() as *mut *mut llvm::LLVMType,
+                return_type,
+                parameter_types.as_mut_ptr(),
                 parameter_types.len() as std::os::raw::c_uint,
                 0,
             )
         };
 
-        let llvm_function = unsafe {
-            let c_name = CString::new(function.id.value.as_str()).unwrap();
+        // Several modules may declare the same C function.
+        let c_name = CString::new(function.id.value.as_str()).unwrap();
+        let llvm_function = llvm_c_function(llvm_module, &c_name, function_type);
+
+        extern_functions.push((function.id.value.clone(), llvm_function));
+    }
+
+    for function in &module.functions {
+        let mut parameter_types: Vec<_> = function
+            .parameters
+            .iter()
+            .map(|(_id, type_)| llvm_type(llvm_module, type_))
+            .collect();
 
-            llvm::core::LLVMAddFunction(llvm_module, c_name.as_ptr(), function_type)
+        let function_type = unsafe {
+            llvm::core::LLVMFunctionType(
+                llvm_type(llvm_module, &function.return_type),
+                parameter_types.as_mut_ptr(),
+                parameter_types.len() as std::os::raw::c_uint,
+                0,
+            )
         };
 
-        activation_records.insert(function.id.value.clone(), llvm_function);
-    }
+        let llvm_function = unsafe {
+            let symbol = function_symbol(path, &function.id, function.public);
+            let c_name = CString::new(symbol.as_str()).unwrap();
+            let llvm_function =
+                llvm::core::LLVMAddFunction(llvm_module, c_name.as_ptr(), function_type);
+            if !function.public && symbol != "main" {
+                llvm::core::LLVMSetLinkage(llvm_function, llvm::LLVMLinkage::LLVMInternalLinkage);
+            }
 
-    for function in &src_module.functions {
-        let llvm_function = activation_records[&function.id.value];
+            llvm_function
+        };
 
-        let llvm_function_params = unsafe {
-            let llvm_function_params = Vec::with_capacity(function.parameters.len());
-            let mut llvm_function_params = std::mem::ManuallyDrop::new(llvm_function_params);
+        functions.push((function.id.value.clone(), llvm_function));
+    }
 
-            llvm::core::LLVMGetParams(llvm_function, llvm_function_params.as_mut_ptr());
+    for function in &module.function_declarations {
+        let mut parameter_types: Vec<_> = function
+            .parameters
+            .iter()
+            .map(|(_id, type_)| llvm_type(llvm_module, type_))
+            .collect();
 
-            Vec::from_raw_parts(
-                llvm_function_params.as_mut_ptr(),
-                llvm_function_params.capacity(),
-                llvm_function_params.capacity(),
+        let function_type = unsafe {
+            llvm::core::LLVMFunctionType(
+                llvm_type(llvm_module, &function.return_type),
+                parameter_types.as_mut_ptr(),
+                parameter_types.len() as std::os::raw::c_uint,
+                0,
             )
         };
 
-        activation_records.push();
+        // The same interface may be imported under several names.
+        let c_name = CString::new(function.symbol.as_str()).unwrap();
+        let llvm_function = llvm_c_function(llvm_module, &c_name, function_type);
 
-        for (value, name) in llvm_function_params
+        function_declarations.push((function.id.value.clone(), llvm_function));
+    }
+
+    for closure in &module.closures {
+        let parameter_types: Vec<_> = closure
+            .parameters
             .iter()
-            .zip(function.parameters.iter().map(|x| x.0.value.clone()))
-        {
-            println!("value name parameter: {}", name);
-            activation_records.insert(name.clone(), *value);
+            .map(|(_id, type_)| type_.clone())
+            .collect();
+        let function_type =
+            llvm_closure_function_type(llvm_module, &parameter_types, &closure.return_type);
+
+        let llvm_function = unsafe {
+            let c_name = CString::new(function_symbol(path, &closure.id, false)).unwrap();
+            let llvm_function =
+                llvm::core::LLVMAddFunction(llvm_module, c_name.as_ptr(), function_type);
+            llvm::core::LLVMSetLinkage(llvm_function, llvm::LLVMLinkage::LLVMInternalLinkage);
+
+            llvm_function
+        };
+
+        closures.push((closure.id.value.clone(), llvm_function));
+    }
+
+    let modules = module
+        .modules
+        .iter()
+        .map(|nested| {
+            let mut nested_path = path.to_vec();
+            nested_path.push(nested.id.value.clone());
+
+            let functions = declare_llvm_functions(llvm_module, nested, &nested_path);
+            (nested.id.value.clone(), functions)
+        })
+        .collect();
+
+    LlvmModuleFunctions {
+        extern_functions,
+        functions,
+        function_declarations,
+        closures,
+        modules,
+    }
+}
+
+/// The symbol of function `id` of the module at `path` from the root module,
+/// which is named after the program. `main` and the `pub` functions of the
+/// root module keep their name, so that C can call them, the other symbols
+/// can not be those of C functions. Methods, named like `<Point as Add>::add`,
+/// can not be called from C and keep their path, as other programs may have
+/// a `Point` of their own.
+pub(crate) fn function_symbol(path: &[String], id: &Identifier, public: bool) -> String {
+    match path {
+        [_root] if (public && !id.value.starts_with('<')) || id.value == "main" => id.value.clone(),
+        _ => format!("{}::{}", path.join("::"), id.value),
+    }
+}
+
+/// Builds the bodies of the functions of `module` and of the modules nested in
+/// it.
+fn build_llvm_module(
+    llvm_module: *mut llvm::LLVMModule,
+    builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    module: &Module,
+    functions: &LlvmModuleFunctions,
+) {
+    for (function, (_id, llvm_function)) in module.functions.iter().zip(&functions.functions) {
+        build_llvm_function(
+            llvm_module,
+            builder,
+            activation_records,
+            options,
+            function,
+            *llvm_function,
+        );
+    }
+
+    for (closure, (_id, llvm_function)) in module.closures.iter().zip(&functions.closures) {
+        build_llvm_closure_function(
+            llvm_module,
+            builder,
+            activation_records,
+            options,
+            closure,
+            *llvm_function,
+        );
+    }
+
+    for (nested, (_id, nested_functions)) in module.modules.iter().zip(&functions.modules) {
+        build_llvm_module(
+            llvm_module,
+            builder,
+            activation_records,
+            options,
+            nested,
+            nested_functions,
+        );
+    }
+}
+
+fn build_llvm_function(
+    llvm_module: *mut llvm::LLVMModule,
+    builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    function: &Function,
+    llvm_function: *mut llvm::LLVMValue,
+) {
+    build_llvm_function_body(
+        llvm_module,
+        builder,
+        activation_records,
+        options,
+        None,
+        &function.parameters,
+        &function.body,
+        llvm_function,
+    );
+}
+
+fn build_llvm_closure_function(
+    llvm_module: *mut llvm::LLVMModule,
+    builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    closure: &Closure,
+    llvm_function: *mut llvm::LLVMValue,
+) {
+    build_llvm_function_body(
+        llvm_module,
+        builder,
+        activation_records,
+        options,
+        Some(&closure.captures),
+        &closure.parameters,
+        &closure.body,
+        llvm_function,
+    );
+}
+
+/// The functions of closures take a pointer to the values they capture as
+/// their first parameter, which are loaded before their body runs.
+#[allow(clippy::too_many_arguments)]
+fn build_llvm_function_body(
+    llvm_module: *mut llvm::LLVMModule,
+    builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    captures: Option<&[(Identifier, Type)]>,
+    parameters: &[(Identifier, Type)],
+    body: &Expression,
+    llvm_function: *mut llvm::LLVMValue,
+) {
+    let llvm_context = unsafe { llvm::core::LLVMGetModuleContext(llvm_module) };
+
+    let mut names: Vec<_> = parameters
+        .iter()
+        .map(|(id, _type)| Some(&id.value))
+        .collect();
+    if captures.is_some() {
+        names.insert(0, None);
+    }
+
+    activation_records.push();
 
-            let c_name = CString::new(name.as_str()).unwrap();
-            let c_name_len = c_name.as_bytes().len();
+    for (index, name) in names.into_iter().enumerate() {
+        let value =
+            unsafe { llvm::core::LLVMGetParam(llvm_function, index as std::os::raw::c_uint) };
+        let c_name = CString::new(name.map_or("captures", String::as_str)).unwrap();
+        let c_name_len = c_name.as_bytes().len();
 
-            unsafe { llvm::core::LLVMSetValueName2(*value, c_name.as_ptr(), c_name_len) }
+        unsafe { llvm::core::LLVMSetValueName2(value, c_name.as_ptr(), c_name_len) }
+
+        if let Some(name) = name {
+            activation_records.insert(name.clone(), value);
         }
+    }
 
-        let function_block = unsafe {
-            llvm::core::LLVMAppendBasicBlock(
-                llvm_function,
-                b"body\0".as_ptr() as *const std::os::raw::c_char,
-            )
-        };
+    let function_block = unsafe {
+        llvm::core::LLVMAppendBasicBlockInContext(
+            llvm_context,
+            llvm_function,
+            b"body\0".as_ptr() as *const std::os::raw::c_char,
+        )
+    };
+
+    unsafe {
+        llvm::core::LLVMPositionBuilderAtEnd(builder, function_block);
+    }
+
+    if let Some(captures) = captures.filter(|captures| !captures.is_empty()) {
+        let captures_type = llvm_captures_type(llvm_module, captures);
 
         unsafe {
-            llvm::core::LLVMPositionBuilderAtEnd(builder, function_block);
-            llvm::core::LLVMBuildRet(
+            let pointer = llvm::core::LLVMBuildBitCast(
                 builder,
-                build_llvm_expression(llvm_module, builder, &function.body),
+                llvm::core::LLVMGetParam(llvm_function, 0),
+                llvm::core::LLVMPointerType(captures_type, 0),
+                CStr::from_bytes_with_nul_unchecked(b"captures_tmp\0").as_ptr(),
             );
-        };
 
-        activation_records.pop();
+            for (index, (id, type_)) in captures.iter().enumerate() {
+                let place = llvm::core::LLVMBuildStructGEP2(
+                    builder,
+                    captures_type,
+                    pointer,
+                    index as std::os::raw::c_uint,
+                    CStr::from_bytes_with_nul_unchecked(b"gep_tmp\0").as_ptr(),
+                );
+
+                let c_name = CString::new(id.value.as_str()).unwrap();
+                let value = llvm::core::LLVMBuildLoad2(
+                    builder,
+                    llvm_type(llvm_module, type_),
+                    place,
+                    c_name.as_ptr(),
+                );
+
+                activation_records.insert(id.value.clone(), value);
+            }
+        }
     }
 
     unsafe {
-        llvm::core::LLVMDisposeBuilder(builder);
+        llvm::core::LLVMBuildRet(
+            builder,
+            build_llvm_expression(llvm_module, builder, activation_records, options, body),
+        );
     };
+
+    activation_records.pop();
 }
 
-fn build_llvm_expression(
+pub(crate) fn llvm_type(llvm_module: *mut llvm::LLVMModule, type_: &Type) -> *mut llvm::LLVMType {
+    let llvm_context = unsafe { llvm::core::LLVMGetModuleContext(llvm_module) };
+
+    match type_ {
+        Type::Boolean => unsafe { llvm::core::LLVMInt1TypeInContext(llvm_context) },
+        Type::Char => unsafe { llvm::core::LLVMInt32TypeInContext(llvm_context) },
+        Type::Int(ty) => unsafe { llvm::core::LLVMIntTypeInContext(llvm_context, ty.bits()) },
+        Type::UInt(ty) => unsafe { llvm::core::LLVMIntTypeInContext(llvm_context, ty.bits()) },
+        Type::Float(FloatType::F32) => unsafe { llvm::core::LLVMFloatTypeInContext(llvm_context) },
+        Type::Float(FloatType::F64) => unsafe { llvm::core::LLVMDoubleTypeInContext(llvm_context) },
+        // Function values are closures, a pointer to their function and one to
+        // the values they capture.
+        Type::Function { .. } => {
+            let mut element_types = [llvm_byte_pointer_type(llvm_module); 2];
+
+            unsafe {
+                llvm::core::LLVMStructTypeInContext(
+                    llvm_context,
+                    element_types.as_mut_ptr(),
+                    element_types.len() as std::os::raw::c_uint,
+                    0,
+                )
+            }
+        }
+        Type::Array { element_type, size } => unsafe {
+            llvm::core::LLVMArrayType(
+                llvm_type(llvm_module, element_type),
+                *size as std::os::raw::c_uint,
+            )
+        },
+        Type::Pointer { pointee, .. } => unsafe {
+            llvm::core::LLVMPointerType(llvm_type(llvm_module, pointee), 0)
+        },
+        Type::Named(id) => unsafe {
+            let c_name = CString::new(id.value.as_str()).unwrap();
+            let llvm_type = llvm::core::LLVMGetTypeByName(llvm_module, c_name.as_ptr());
+
+            if llvm_type.is_null() {
+                panic!("unknown type {}", id.value);
+            }
+
+            llvm_type
+        },
+        Type::Tuple(types) => {
+            let mut element_types: Vec<_> = types
+                .iter()
+                .map(|type_| llvm_type(llvm_module, type_))
+                .collect();
+
+            unsafe {
+                llvm::core::LLVMStructTypeInContext(
+                    llvm_context,
+                    element_types.as_mut_ptr(),
+                    element_types.len() as std::os::raw::c_uint,
+                    0,
+                )
+            }
+        }
+        Type::Slice(element_type) => {
+            let mut element_types = [
+                unsafe { llvm::core::LLVMPointerType(llvm_type(llvm_module, element_type), 0) },
+                llvm_type(llvm_module, &Type::UInt(UIntType::USize)),
+            ];
+
+            unsafe {
+                llvm::core::LLVMStructTypeInContext(
+                    llvm_context,
+                    element_types.as_mut_ptr(),
+                    element_types.len() as std::os::raw::c_uint,
+                    0,
+                )
+            }
+        }
+        Type::None => todo!(),
+    }
+}
+
+fn llvm_byte_pointer_type(llvm_module: *mut llvm::LLVMModule) -> *mut llvm::LLVMType {
+    unsafe {
+        llvm::core::LLVMPointerType(
+            llvm::core::LLVMInt8TypeInContext(llvm::core::LLVMGetModuleContext(llvm_module)),
+            0,
+        )
+    }
+}
+
+/// The type of the function of a closure, which takes the values the closure
+/// captures before its parameters.
+fn llvm_closure_function_type(
     llvm_module: *mut llvm::LLVMModule,
-    llvm_builder: *mut llvm::LLVMBuilder,
-    expr: &Expression,
-) -> *mut llvm::LLVMValue {
-    match expr {
-        Expression::Literal(literal) => build_llvm_literal(llvm_module, llvm_builder, literal),
-        Expression::Binary(bin_expr) => {
-            build_llvm_binary_operation(llvm_module, llvm_builder, bin_expr)
+    parameters: &[Type],
+    return_type: &Type,
+) -> *mut llvm::LLVMType {
+    let mut parameter_types = vec![llvm_byte_pointer_type(llvm_module)];
+    parameter_types.extend(parameters.iter().map(|type_| llvm_type(llvm_module, type_)));
+
+    unsafe {
+        llvm::core::LLVMFunctionType(
+            llvm_type(llvm_module, return_type),
+            parameter_types.as_mut_ptr(),
+            parameter_types.len() as std::os::raw::c_uint,
+            0,
+        )
+    }
+}
+
+/// The struct the values captured by a closure are stored in.
+fn llvm_captures_type(
+    llvm_module: *mut llvm::LLVMModule,
+    captures: &[(Identifier, Type)],
+) -> *mut llvm::LLVMType {
+    let mut element_types: Vec<_> = captures
+        .iter()
+        .map(|(_id, type_)| llvm_type(llvm_module, type_))
+        .collect();
+
+    unsafe {
+        llvm::core::LLVMStructTypeInContext(
+            llvm::core::LLVMGetModuleContext(llvm_module),
+            element_types.as_mut_ptr(),
+            element_types.len() as std::os::raw::c_uint,
+            0,
+        )
+    }
+}
+
+/// Lays out an enum as `{ tag, [N x iA] }`, where the array is as large and
+/// as aligned as the largest payload. Each variant's payload gets a named
+/// struct `Enum::Variant` that the array is reinterpreted as.
+fn set_llvm_enum_body(llvm_module: *mut llvm::LLVMModule, enum_: &EnumDefinition) {
+    let llvm_context = unsafe { llvm::core::LLVMGetModuleContext(llvm_module) };
+    let data_layout = unsafe { llvm::target::LLVMGetModuleDataLayout(llvm_module) };
+
+    let mut payload_size = 0;
+    let mut payload_alignment = 1;
+
+    for (id, types) in &enum_.variants {
+        let mut field_types: Vec<_> = types
+            .iter()
+            .map(|type_| llvm_type(llvm_module, type_))
+            .collect();
+
+        unsafe {
+            let c_name = CString::new(format!("{}::{}", enum_.id.value, id.value)).unwrap();
+            let variant_type = llvm::core::LLVMStructCreateNamed(llvm_context, c_name.as_ptr());
+
+            llvm::core::LLVMStructSetBody(
+                variant_type,
+                field_types.as_mut_ptr(),
+                field_types.len() as std::os::raw::c_uint,
+                0,
+            );
+
+            payload_size =
+                payload_size.max(llvm::target::LLVMABISizeOfType(data_layout, variant_type));
+            payload_alignment = payload_alignment.max(llvm::target::LLVMABIAlignmentOfType(
+                data_layout,
+                variant_type,
+            ));
         }
-        Expression::Block(block) => build_llvm_block(llvm_module, llvm_builder, block),
-        _ => todo!(),
+    }
+
+    let tag_bits = match enum_.variants.len() {
+        0..=0x100 => 8,
+        0x101..=0x1_0000 => 16,
+        _ => 32,
+    };
+
+    unsafe {
+        let mut element_types = vec![llvm::core::LLVMIntTypeInContext(llvm_context, tag_bits)];
+
+        if payload_size > 0 {
+            let payload_alignment = payload_alignment as u64;
+
+            element_types.push(llvm::core::LLVMArrayType(
+                llvm::core::LLVMIntTypeInContext(
+                    llvm_context,
+                    (payload_alignment * 8) as std::os::raw::c_uint,
+                ),
+                payload_size.div_ceil(payload_alignment) as std::os::raw::c_uint,
+            ));
+        }
+
+        let c_name = CString::new(enum_.id.value.as_str()).unwrap();
+
+        llvm::core::LLVMStructSetBody(
+            llvm::core::LLVMGetTypeByName(llvm_module, c_name.as_ptr()),
+            element_types.as_mut_ptr(),
+            element_types.len() as std::os::raw::c_uint,
+            0,
+        );
     }
 }
 
-fn build_llvm_block(
+fn llvm_variant_type(
+    llvm_module: *mut llvm::LLVMModule,
+    enum_type: &Type,
+    variant: &str,
+) -> *mut llvm::LLVMType {
+    match enum_type {
+        Type::Named(id) => unsafe {
+            let c_name = CString::new(format!("{}::{}", id.value, variant)).unwrap();
+
+            llvm::core::LLVMGetTypeByName(llvm_module, c_name.as_ptr())
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Returns a pointer to the payload of the enum stored at `place`, viewed as
+/// the fields of `variant`.
+fn build_llvm_payload_place(
     llvm_module: *mut llvm::LLVMModule,
     llvm_builder: *mut llvm::LLVMBuilder,
-    block: &BlockExpression,
+    enum_type: &Type,
+    variant: &str,
+    place: *mut llvm::LLVMValue,
 ) -> *mut llvm::LLVMValue {
-    build_llvm_expression(llvm_module, llvm_builder, &block.return_expression)
+    unsafe {
+        let payload = llvm::core::LLVMBuildStructGEP2(
+            llvm_builder,
+            llvm_type(llvm_module, enum_type),
+            place,
+            1,
+            CStr::from_bytes_with_nul_unchecked(b"payload_tmp\0").as_ptr(),
+        );
+
+        llvm::core::LLVMBuildBitCast(
+            llvm_builder,
+            payload,
+            llvm::core::LLVMPointerType(llvm_variant_type(llvm_module, enum_type, variant), 0),
+            CStr::from_bytes_with_nul_unchecked(b"payload_cast_tmp\0").as_ptr(),
+        )
+    }
 }
 
-fn build_llvm_binary_operation(
+fn build_llvm_variant(
     llvm_module: *mut llvm::LLVMModule,
     llvm_builder: *mut llvm::LLVMBuilder,
-    bin_op: &BinaryExpression,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    variant: &VariantExpression,
 ) -> *mut llvm::LLVMValue {
-    match bin_op.operator {
-        BinaryOperator::Plus => unsafe {
-            llvm::core::LLVMBuildAdd(
+    let fields: Vec<_> = variant
+        .fields
+        .iter()
+        .map(|field| {
+            build_llvm_expression(
+                llvm_module,
                 llvm_builder,
-                build_llvm_expression(llvm_module, llvm_builder, &bin_op.lhs),
-                build_llvm_expression(llvm_module, llvm_builder, &bin_op.rhs),
-                CStr::from_bytes_with_nul_unchecked(b"add_tmp\0").as_ptr(),
+                activation_records,
+                options,
+                field,
             )
-        },
-        _ => todo!(),
+        })
+        .collect();
+
+    let enum_type = llvm_type(llvm_module, &variant.type_);
+
+    unsafe {
+        let slot = build_llvm_entry_alloca(
+            llvm_builder,
+            enum_type,
+            CStr::from_bytes_with_nul_unchecked(b"variant_tmp\0"),
+        );
+
+        let tag = llvm::core::LLVMBuildStructGEP2(
+            llvm_builder,
+            enum_type,
+            slot,
+            0,
+            CStr::from_bytes_with_nul_unchecked(b"tag_tmp\0").as_ptr(),
+        );
+        llvm::core::LLVMBuildStore(
+            llvm_builder,
+            llvm::core::LLVMConstInt(
+                llvm::core::LLVMStructGetTypeAtIndex(enum_type, 0),
+                variant.tag as std::os::raw::c_ulonglong,
+                0,
+            ),
+            tag,
+        );
+
+        if !fields.is_empty() {
+            let payload = build_llvm_payload_place(
+                llvm_module,
+                llvm_builder,
+                &variant.type_,
+                &variant.variant.value,
+                slot,
+            );
+
+            llvm::core::LLVMBuildStore(
+                llvm_builder,
+                build_llvm_aggregate(
+                    llvm_builder,
+                    llvm_variant_type(llvm_module, &variant.type_, &variant.variant.value),
+                    fields,
+                ),
+                payload,
+            );
+        }
+
+        llvm::core::LLVMBuildLoad2(
+            llvm_builder,
+            enum_type,
+            slot,
+            CStr::from_bytes_with_nul_unchecked(b"variant_load_tmp\0").as_ptr(),
+        )
     }
 }
 
-fn build_llvm_literal(
-    _llvm_module: *mut llvm::LLVMModule,
-    _llvm_builder: *mut llvm::LLVMBuilder,
-    literal: &Literal,
-) -> *mut llvm::LLVMValue {
-    match literal {
-        Literal::Int(value, ty) => match ty {
-            LiteralIntType::Signed(ty) => match ty {
-                IntType::I32 => unsafe {
-                    llvm::core::LLVMConstInt(
-                        llvm::core::LLVMInt32Type(),
-                        *value as std::os::raw::c_ulonglong,
-                        0 as std::os::raw::c_int,
+fn llvm_contains_float(llvm_type: *mut llvm::LLVMType) -> bool {
+    unsafe {
+        match llvm::core::LLVMGetTypeKind(llvm_type) {
+            llvm::LLVMTypeKind::LLVMFloatTypeKind | llvm::LLVMTypeKind::LLVMDoubleTypeKind => true,
+            llvm::LLVMTypeKind::LLVMArrayTypeKind => {
+                llvm_contains_float(llvm::core::LLVMGetElementType(llvm_type))
+            }
+            llvm::LLVMTypeKind::LLVMStructTypeKind => {
+                let count = llvm::core::LLVMCountStructElementTypes(llvm_type);
+                (0..count).any(|index| {
+                    llvm_contains_float(llvm::core::LLVMStructGetTypeAtIndex(llvm_type, index))
+                })
+            }
+            _ => false,
+        }
+    }
+}
+
+fn llvm_contains_pointer(llvm_type: *mut llvm::LLVMType) -> bool {
+    unsafe {
+        match llvm::core::LLVMGetTypeKind(llvm_type) {
+            llvm::LLVMTypeKind::LLVMPointerTypeKind => true,
+            llvm::LLVMTypeKind::LLVMArrayTypeKind => {
+                llvm_contains_pointer(llvm::core::LLVMGetElementType(llvm_type))
+            }
+            llvm::LLVMTypeKind::LLVMStructTypeKind => {
+                let count = llvm::core::LLVMCountStructElementTypes(llvm_type);
+                (0..count).any(|index| {
+                    llvm_contains_pointer(llvm::core::LLVMStructGetTypeAtIndex(llvm_type, index))
+                })
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The type a value is passed as to and from C functions.
+///
+/// Structs and tuples of up to 16 bytes travel in integer registers, so they
+/// are passed as integers covering their bytes, as C compilers do on x86-64
+/// and AArch64. Tuples are laid out like a C struct of their elements, and
+/// slices like a struct of a pointer and a `size_t`. Floats are passed
+/// as-is. The typechecker rejects aggregates containing floats, which would
+/// use floating-point registers, and aggregates larger than 16 bytes, which
+/// would be passed in memory.
+fn llvm_c_abi_type(llvm_module: *mut llvm::LLVMModule, type_: &Type) -> *mut llvm::LLVMType {
+    let llvm_context = unsafe { llvm::core::LLVMGetModuleContext(llvm_module) };
+    let llvm_type = llvm_type(llvm_module, type_);
+
+    if let Type::Named(_) | Type::Tuple(_) | Type::Slice(_) = type_ {
+        // Rejected by the typechecker.
+        if llvm_contains_float(llvm_type) {
+            panic!(
+                "{:?} contains floats and can not be passed to C by value",
+                type_
+            );
+        }
+
+        let size = unsafe {
+            llvm::target::LLVMABISizeOfType(
+                llvm::target::LLVMGetModuleDataLayout(llvm_module),
+                llvm_type,
+            )
+        };
+
+        return unsafe {
+            match size {
+                // Rejected by the typechecker, `i0` is not a valid type.
+                0 => panic!("{:?} has no size and can not be passed to C", type_),
+                1..=8 => llvm::core::LLVMIntTypeInContext(
+                    llvm_context,
+                    (size * 8) as std::os::raw::c_uint,
+                ),
+                9..=16 => {
+                    let mut element_types = [
+                        llvm::core::LLVMInt64TypeInContext(llvm_context),
+                        llvm::core::LLVMIntTypeInContext(
+                            llvm_context,
+                            ((size - 8) * 8) as std::os::raw::c_uint,
+                        ),
+                    ];
+
+                    llvm::core::LLVMStructTypeInContext(
+                        llvm_context,
+                        element_types.as_mut_ptr(),
+                        element_types.len() as std::os::raw::c_uint,
+                        0,
                     )
-                },
-                _ => todo!(),
-            },
-            LiteralIntType::Unsigned(_ty) => todo!(),
-            LiteralIntType::Unsufixed => todo!(),
+                }
+                // Rejected by the typechecker.
+                _ => panic!(
+                    "{:?} is larger than 16 bytes and can not be passed to C by value",
+                    type_
+                ),
+            }
+        };
+    }
+
+    llvm_type
+}
+
+/// Reinterprets the bytes of `value` as a value of `to_type` by storing it to
+/// a stack slot of `slot_type` and loading it back. Used to convert structs
+/// from and to their C ABI type, which is the slot as it is never smaller.
+fn build_llvm_abi_coercion(
+    llvm_builder: *mut llvm::LLVMBuilder,
+    value: *mut llvm::LLVMValue,
+    to_type: *mut llvm::LLVMType,
+    slot_type: *mut llvm::LLVMType,
+) -> *mut llvm::LLVMValue {
+    unsafe {
+        let slot = build_llvm_entry_alloca(
+            llvm_builder,
+            slot_type,
+            CStr::from_bytes_with_nul_unchecked(b"abi_tmp\0"),
+        );
+
+        let store_slot = llvm::core::LLVMBuildBitCast(
+            llvm_builder,
+            slot,
+            llvm::core::LLVMPointerType(llvm::core::LLVMTypeOf(value), 0),
+            CStr::from_bytes_with_nul_unchecked(b"abi_cast_tmp\0").as_ptr(),
+        );
+        llvm::core::LLVMBuildStore(llvm_builder, value, store_slot);
+
+        let load_slot = llvm::core::LLVMBuildBitCast(
+            llvm_builder,
+            slot,
+            llvm::core::LLVMPointerType(to_type, 0),
+            CStr::from_bytes_with_nul_unchecked(b"abi_cast_tmp\0").as_ptr(),
+        );
+        llvm::core::LLVMBuildLoad2(
+            llvm_builder,
+            to_type,
+            load_slot,
+            CStr::from_bytes_with_nul_unchecked(b"abi_load_tmp\0").as_ptr(),
+        )
+    }
+}
+
+fn build_llvm_const_int(
+    llvm_module: *mut llvm::LLVMModule,
+    type_: &Type,
+    value: u128,
+) -> *mut llvm::LLVMValue {
+    build_llvm_const_bits(llvm_type(llvm_module, type_), value)
+}
+
+/// An integer constant of `llvm_type` from the low bits of `value`, which
+/// unlike `LLVMConstInt` keeps the bits above 64 for 128-bit types.
+fn build_llvm_const_bits(llvm_type: *mut llvm::LLVMType, value: u128) -> *mut llvm::LLVMValue {
+    let words = [value as u64, (value >> 64) as u64];
+
+    unsafe {
+        llvm::core::LLVMConstIntOfArbitraryPrecision(
+            llvm_type,
+            words.len() as std::os::raw::c_uint,
+            words.as_ptr(),
+        )
+    }
+}
+
+fn build_llvm_expression(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    expr: &Expression,
+) -> *mut llvm::LLVMValue {
+    match expr {
+        Expression::Literal(literal) => build_llvm_literal(llvm_module, llvm_builder, literal),
+        Expression::Identifier(id) => {
+            build_llvm_identifier(llvm_module, llvm_builder, activation_records, &id.id)
+        }
+        Expression::Unary(unary) => build_llvm_unary_operation(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            unary,
+        ),
+        Expression::Binary(bin_expr) => build_llvm_binary_operation(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            bin_expr,
+        ),
+        Expression::Block(block) => build_llvm_block(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            block,
+        ),
+        Expression::Match(match_) => build_llvm_match(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            match_,
+        ),
+        Expression::Cast(cast) => {
+            build_llvm_cast(llvm_module, llvm_builder, activation_records, options, cast)
+        }
+        Expression::Array(array) => build_llvm_array(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            array,
+        ),
+        Expression::Access(_) => unsafe {
+            let place =
+                build_llvm_place(llvm_module, llvm_builder, activation_records, options, expr);
+
+            llvm::core::LLVMBuildLoad2(
+                llvm_builder,
+                llvm_type(llvm_module, &expr.type_()),
+                place,
+                CStr::from_bytes_with_nul_unchecked(b"load_tmp\0").as_ptr(),
+            )
+        },
+        Expression::Call(call) => {
+            build_llvm_call(llvm_module, llvm_builder, activation_records, options, call)
+        }
+        Expression::Tuple(tuple) => {
+            let elements = tuple
+                .elements
+                .iter()
+                .map(|element| {
+                    build_llvm_expression(
+                        llvm_module,
+                        llvm_builder,
+                        activation_records,
+                        options,
+                        element,
+                    )
+                })
+                .collect();
+
+            build_llvm_aggregate(llvm_builder, llvm_type(llvm_module, &tuple.type_), elements)
+        }
+        Expression::Struct(struct_) => {
+            let fields = struct_
+                .fields
+                .iter()
+                .map(|field| {
+                    build_llvm_expression(
+                        llvm_module,
+                        llvm_builder,
+                        activation_records,
+                        options,
+                        field,
+                    )
+                })
+                .collect();
+
+            build_llvm_aggregate(llvm_builder, llvm_type(llvm_module, &struct_.type_), fields)
+        }
+        Expression::Field(field) => unsafe {
+            let base = build_llvm_expression(
+                llvm_module,
+                llvm_builder,
+                activation_records,
+                options,
+                &field.base,
+            );
+
+            llvm::core::LLVMBuildExtractValue(
+                llvm_builder,
+                base,
+                field.index as std::os::raw::c_uint,
+                CStr::from_bytes_with_nul_unchecked(b"field_tmp\0").as_ptr(),
+            )
+        },
+        Expression::Print(print) => build_llvm_print(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            print,
+        ),
+        Expression::Length(length) => unsafe {
+            let slice = build_llvm_expression(
+                llvm_module,
+                llvm_builder,
+                activation_records,
+                options,
+                &length.slice,
+            );
+
+            llvm::core::LLVMBuildExtractValue(
+                llvm_builder,
+                slice,
+                1,
+                CStr::from_bytes_with_nul_unchecked(b"length_tmp\0").as_ptr(),
+            )
         },
+        Expression::Offset(offset) => build_llvm_offset(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            offset,
+        ),
+        Expression::Variant(variant) => build_llvm_variant(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            variant,
+        ),
+        Expression::Closure(closure) => build_llvm_closure(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            closure,
+            false,
+        ),
         _ => todo!(),
     }
 }
+
+/// Copies the captured values to the stack of the current function if
+/// `on_stack` is set, and to the heap otherwise.
+///
+/// Closures are copied by value and nothing tracks their copies, so an
+/// environment on the heap is never freed: it belongs to the program and stays
+/// for as long as the program runs. Only closures that can not outlive the
+/// function making them may keep their environment on the stack, see
+/// `build_llvm_call`.
+fn build_llvm_closure(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    closure: &ClosureExpression,
+    on_stack: bool,
+) -> *mut llvm::LLVMValue {
+    let byte_pointer_type = llvm_byte_pointer_type(llvm_module);
+
+    let captures = if closure.captures.is_empty() {
+        unsafe { llvm::core::LLVMConstNull(byte_pointer_type) }
+    } else {
+        let captures: Vec<_> = closure
+            .captures
+            .iter()
+            .map(|capture| (capture.id.clone(), capture.type_.clone()))
+            .collect();
+        let captures_type = llvm_captures_type(llvm_module, &captures);
+
+        unsafe {
+            let (memory, pointer) = if on_stack {
+                let pointer = build_llvm_entry_alloca(
+                    llvm_builder,
+                    captures_type,
+                    CStr::from_bytes_with_nul_unchecked(b"captures_alloca\0"),
+                );
+                let memory = llvm::core::LLVMBuildBitCast(
+                    llvm_builder,
+                    pointer,
+                    byte_pointer_type,
+                    CStr::from_bytes_with_nul_unchecked(b"captures_tmp\0").as_ptr(),
+                );
+
+                (memory, pointer)
+            } else {
+                let alloc = llvm::core::LLVMGetNamedFunction(
+                    llvm_module,
+                    CStr::from_bytes_with_nul_unchecked(b"toy_alloc\0").as_ptr(),
+                );
+                let usize_type = llvm_type(llvm_module, &Type::UInt(UIntType::USize));
+                let mut arguments = [
+                    llvm::core::LLVMConstIntCast(
+                        llvm::core::LLVMSizeOf(captures_type),
+                        usize_type,
+                        0,
+                    ),
+                    llvm::core::LLVMConstIntCast(
+                        llvm::core::LLVMAlignOf(captures_type),
+                        usize_type,
+                        0,
+                    ),
+                ];
+
+                let memory = llvm::core::LLVMBuildCall2(
+                    llvm_builder,
+                    llvm::core::LLVMGetElementType(llvm::core::LLVMTypeOf(alloc)),
+                    alloc,
+                    arguments.as_mut_ptr(),
+                    arguments.len() as std::os::raw::c_uint,
+                    CStr::from_bytes_with_nul_unchecked(b"captures_tmp\0").as_ptr(),
+                );
+                let pointer = llvm::core::LLVMBuildBitCast(
+                    llvm_builder,
+                    memory,
+                    llvm::core::LLVMPointerType(captures_type, 0),
+                    CStr::from_bytes_with_nul_unchecked(b"cast_tmp\0").as_ptr(),
+                );
+
+                (memory, pointer)
+            };
+
+            for (index, capture) in closure.captures.iter().enumerate() {
+                let value = build_llvm_identifier(
+                    llvm_module,
+                    llvm_builder,
+                    activation_records,
+                    &capture.id,
+                );
+                let place = llvm::core::LLVMBuildStructGEP2(
+                    llvm_builder,
+                    captures_type,
+                    pointer,
+                    index as std::os::raw::c_uint,
+                    CStr::from_bytes_with_nul_unchecked(b"gep_tmp\0").as_ptr(),
+                );
+
+                llvm::core::LLVMBuildStore(llvm_builder, value, place);
+            }
+
+            memory
+        }
+    };
+
+    unsafe {
+        let function = llvm::core::LLVMBuildBitCast(
+            llvm_builder,
+            activation_records[&closure.function.value],
+            byte_pointer_type,
+            CStr::from_bytes_with_nul_unchecked(b"cast_tmp\0").as_ptr(),
+        );
+
+        build_llvm_aggregate(
+            llvm_builder,
+            llvm_type(llvm_module, &closure.type_),
+            vec![function, captures],
+        )
+    }
+}
+
+/// A function used as a value is a closure capturing nothing, whose function
+/// takes the captures it ignores and calls it.
+fn build_llvm_function_closure(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_function: *mut llvm::LLVMValue,
+) -> *mut llvm::LLVMValue {
+    let byte_pointer_type = llvm_byte_pointer_type(llvm_module);
+
+    unsafe {
+        let llvm_context = llvm::core::LLVMGetModuleContext(llvm_module);
+        let function_type = llvm::core::LLVMGetElementType(llvm::core::LLVMTypeOf(llvm_function));
+
+        let mut name_len = 0;
+        let name = llvm::core::LLVMGetValueName2(llvm_function, &mut name_len);
+        let name = std::slice::from_raw_parts(name as *const u8, name_len);
+        let c_name =
+            CString::new(format!("{}::{{closure}}", String::from_utf8_lossy(name))).unwrap();
+
+        let mut closure_function = llvm::core::LLVMGetNamedFunction(llvm_module, c_name.as_ptr());
+
+        if closure_function.is_null() {
+            let mut parameter_types =
+                vec![std::ptr::null_mut(); llvm::core::LLVMCountParamTypes(function_type) as usize];
+            llvm::core::LLVMGetParamTypes(function_type, parameter_types.as_mut_ptr());
+            parameter_types.insert(0, byte_pointer_type);
+
+            let closure_function_type = llvm::core::LLVMFunctionType(
+                llvm::core::LLVMGetReturnType(function_type),
+                parameter_types.as_mut_ptr(),
+                parameter_types.len() as std::os::raw::c_uint,
+                0,
+            );
+            closure_function =
+                llvm::core::LLVMAddFunction(llvm_module, c_name.as_ptr(), closure_function_type);
+            llvm::core::LLVMSetLinkage(closure_function, llvm::LLVMLinkage::LLVMInternalLinkage);
+
+            let builder = llvm::core::LLVMCreateBuilderInContext(llvm_context);
+            let block = llvm::core::LLVMAppendBasicBlockInContext(
+                llvm_context,
+                closure_function,
+                CStr::from_bytes_with_nul_unchecked(b"body\0").as_ptr(),
+            );
+            llvm::core::LLVMPositionBuilderAtEnd(builder, block);
+
+            let mut arguments: Vec<_> = (1..parameter_types.len())
+                .map(|index| {
+                    llvm::core::LLVMGetParam(closure_function, index as std::os::raw::c_uint)
+                })
+                .collect();
+            let result = llvm::core::LLVMBuildCall2(
+                builder,
+                function_type,
+                llvm_function,
+                arguments.as_mut_ptr(),
+                arguments.len() as std::os::raw::c_uint,
+                CStr::from_bytes_with_nul_unchecked(b"call_tmp\0").as_ptr(),
+            );
+            llvm::core::LLVMBuildRet(builder, result);
+            llvm::core::LLVMDisposeBuilder(builder);
+        }
+
+        let mut elements = [
+            llvm::core::LLVMConstBitCast(closure_function, byte_pointer_type),
+            llvm::core::LLVMConstNull(byte_pointer_type),
+        ];
+
+        llvm::core::LLVMConstStructInContext(
+            llvm_context,
+            elements.as_mut_ptr(),
+            elements.len() as std::os::raw::c_uint,
+            0,
+        )
+    }
+}
+
+fn build_llvm_identifier(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &ActivationRecords,
+    id: &Identifier,
+) -> *mut llvm::LLVMValue {
+    let value = activation_records[&id.value];
+
+    unsafe {
+        if !llvm::core::LLVMIsAFunction(value).is_null() {
+            build_llvm_function_closure(llvm_module, value)
+        } else if llvm::core::LLVMIsAAllocaInst(value).is_null() {
+            value
+        } else {
+            llvm::core::LLVMBuildLoad2(
+                llvm_builder,
+                llvm::core::LLVMGetAllocatedType(value),
+                value,
+                CStr::from_bytes_with_nul_unchecked(b"load_tmp\0").as_ptr(),
+            )
+        }
+    }
+}
+
+fn build_llvm_block(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    block: &BlockExpression,
+) -> *mut llvm::LLVMValue {
+    activation_records.push();
+
+    for statement in &block.statements {
+        match statement {
+            Statement::VariableDefinition(variable) => {
+                let value = build_llvm_expression(
+                    llvm_module,
+                    llvm_builder,
+                    activation_records,
+                    options,
+                    &variable.initialize_expression,
+                );
+
+                let c_name = CString::new(variable.id.value.as_str()).unwrap();
+                let alloca = build_llvm_entry_alloca(
+                    llvm_builder,
+                    llvm_type(llvm_module, &variable.type_),
+                    &c_name,
+                );
+
+                unsafe { llvm::core::LLVMBuildStore(llvm_builder, value, alloca) };
+
+                activation_records.insert(variable.id.value.clone(), alloca);
+            }
+            Statement::TupleDefinition(tuple) => {
+                let value = build_llvm_expression(
+                    llvm_module,
+                    llvm_builder,
+                    activation_records,
+                    options,
+                    &tuple.initialize_expression,
+                );
+
+                let element_types = match &tuple.type_ {
+                    Type::Tuple(types) => types,
+                    _ => unreachable!(),
+                };
+
+                for (index, (id, type_)) in tuple.ids.iter().zip(element_types).enumerate() {
+                    let c_name = CString::new(id.value.as_str()).unwrap();
+                    let alloca = build_llvm_entry_alloca(
+                        llvm_builder,
+                        llvm_type(llvm_module, type_),
+                        &c_name,
+                    );
+
+                    unsafe {
+                        let element = llvm::core::LLVMBuildExtractValue(
+                            llvm_builder,
+                            value,
+                            index as std::os::raw::c_uint,
+                            CStr::from_bytes_with_nul_unchecked(b"element_tmp\0").as_ptr(),
+                        );
+                        llvm::core::LLVMBuildStore(llvm_builder, element, alloca);
+                    }
+
+                    activation_records.insert(id.value.clone(), alloca);
+                }
+            }
+            Statement::Assignment(assignment) => {
+                let place = build_llvm_place(
+                    llvm_module,
+                    llvm_builder,
+                    activation_records,
+                    options,
+                    &assignment.target,
+                );
+                let value = build_llvm_expression(
+                    llvm_module,
+                    llvm_builder,
+                    activation_records,
+                    options,
+                    &assignment.expression,
+                );
+
+                unsafe { llvm::core::LLVMBuildStore(llvm_builder, value, place) };
+            }
+            _ => todo!(),
+        }
+    }
+
+    let value = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &block.return_expression,
+    );
+
+    activation_records.pop();
+
+    value
+}
+
+/// Stack slots live at the start of the entry block so that they are
+/// allocated once per call, whichever branch defines them.
+fn build_llvm_entry_alloca(
+    llvm_builder: *mut llvm::LLVMBuilder,
+    llvm_type: *mut llvm::LLVMType,
+    name: &CStr,
+) -> *mut llvm::LLVMValue {
+    unsafe {
+        let llvm_function =
+            llvm::core::LLVMGetBasicBlockParent(llvm::core::LLVMGetInsertBlock(llvm_builder));
+        let entry_block = llvm::core::LLVMGetEntryBasicBlock(llvm_function);
+        let first_instruction = llvm::core::LLVMGetFirstInstruction(entry_block);
+
+        let entry_builder =
+            llvm::core::LLVMCreateBuilderInContext(llvm::core::LLVMGetTypeContext(llvm_type));
+        if first_instruction.is_null() {
+            llvm::core::LLVMPositionBuilderAtEnd(entry_builder, entry_block);
+        } else {
+            llvm::core::LLVMPositionBuilderBefore(entry_builder, first_instruction);
+        }
+
+        let alloca = llvm::core::LLVMBuildAlloca(entry_builder, llvm_type, name.as_ptr());
+        llvm::core::LLVMDisposeBuilder(entry_builder);
+
+        alloca
+    }
+}
+
+/// Returns a pointer to the memory an expression denotes. Expressions that
+/// are not stored anywhere, like parameters and temporaries, are spilled to
+/// the stack first.
+fn build_llvm_place(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    expr: &Expression,
+) -> *mut llvm::LLVMValue {
+    match expr {
+        Expression::Identifier(id)
+            if unsafe {
+                !llvm::core::LLVMIsAAllocaInst(activation_records[&id.id.value]).is_null()
+            } =>
+        {
+            activation_records[&id.id.value]
+        }
+        Expression::Unary(unary) if matches!(unary.operator, UnaryOperator::Deref) => {
+            build_llvm_expression(
+                llvm_module,
+                llvm_builder,
+                activation_records,
+                options,
+                &unary.expression,
+            )
+        }
+        Expression::Field(field) => {
+            let base = build_llvm_place(
+                llvm_module,
+                llvm_builder,
+                activation_records,
+                options,
+                &field.base,
+            );
+
+            unsafe {
+                llvm::core::LLVMBuildStructGEP2(
+                    llvm_builder,
+                    llvm_type(llvm_module, &field.base.type_()),
+                    base,
+                    field.index as std::os::raw::c_uint,
+                    CStr::from_bytes_with_nul_unchecked(b"gep_tmp\0").as_ptr(),
+                )
+            }
+        }
+        Expression::Access(access) => {
+            let index_type = Type::UInt(UIntType::USize);
+
+            // Arrays are indexed in place, slices through their pointer.
+            let (base, length) = match access.base.type_() {
+                Type::Array { size, .. } => {
+                    let base = build_llvm_place(
+                        llvm_module,
+                        llvm_builder,
+                        activation_records,
+                        options,
+                        &access.base,
+                    );
+                    (
+                        base,
+                        build_llvm_const_int(llvm_module, &index_type, size as u128),
+                    )
+                }
+                Type::Slice(_) => unsafe {
+                    let slice = build_llvm_expression(
+                        llvm_module,
+                        llvm_builder,
+                        activation_records,
+                        options,
+                        &access.base,
+                    );
+                    let elements = llvm::core::LLVMBuildExtractValue(
+                        llvm_builder,
+                        slice,
+                        0,
+                        CStr::from_bytes_with_nul_unchecked(b"elements_tmp\0").as_ptr(),
+                    );
+                    let length = llvm::core::LLVMBuildExtractValue(
+                        llvm_builder,
+                        slice,
+                        1,
+                        CStr::from_bytes_with_nul_unchecked(b"length_tmp\0").as_ptr(),
+                    );
+                    (elements, length)
+                },
+                other => panic!("can not index into {:?}", other),
+            };
+
+            let offset = build_llvm_expression(
+                llvm_module,
+                llvm_builder,
+                activation_records,
+                options,
+                &access.offset,
+            );
+
+            let index = unsafe {
+                llvm::core::LLVMBuildIntCast2(
+                    llvm_builder,
+                    offset,
+                    llvm_type(llvm_module, &index_type),
+                    matches!(access.offset.type_(), Type::Int(_)) as llvm::prelude::LLVMBool,
+                    CStr::from_bytes_with_nul_unchecked(b"index_tmp\0").as_ptr(),
+                )
+            };
+
+            if options.bounds_checks {
+                // Indices wider than `usize` are checked before they are
+                // truncated, so they can not wrap around into range.
+                let checked_index =
+                    if unsafe { llvm::core::LLVMGetIntTypeWidth(llvm::core::LLVMTypeOf(offset)) }
+                        > UIntType::USize.bits()
+                    {
+                        offset
+                    } else {
+                        index
+                    };
+                let length = unsafe {
+                    llvm::core::LLVMBuildZExtOrBitCast(
+                        llvm_builder,
+                        length,
+                        llvm::core::LLVMTypeOf(checked_index),
+                        CStr::from_bytes_with_nul_unchecked(b"length_tmp\0").as_ptr(),
+                    )
+                };
+                build_llvm_bounds_check(llvm_module, llvm_builder, checked_index, length);
+            }
+
+            let (base_type, mut indices) = match access.base.type_() {
+                Type::Slice(element_type) => (llvm_type(llvm_module, &element_type), vec![index]),
+                base_type => (
+                    llvm_type(llvm_module, &base_type),
+                    vec![build_llvm_const_int(llvm_module, &index_type, 0), index],
+                ),
+            };
+
+            unsafe {
+                llvm::core::LLVMBuildInBoundsGEP2(
+                    llvm_builder,
+                    base_type,
+                    base,
+                    indices.as_mut_ptr(),
+                    indices.len() as std::os::raw::c_uint,
+                    CStr::from_bytes_with_nul_unchecked(b"gep_tmp\0").as_ptr(),
+                )
+            }
+        }
+        _ => {
+            let value =
+                build_llvm_expression(llvm_module, llvm_builder, activation_records, options, expr);
+            let alloca = build_llvm_entry_alloca(
+                llvm_builder,
+                llvm_type(llvm_module, &expr.type_()),
+                unsafe { CStr::from_bytes_with_nul_unchecked(b"spill_tmp\0") },
+            );
+
+            unsafe { llvm::core::LLVMBuildStore(llvm_builder, value, alloca) };
+
+            alloca
+        }
+    }
+}
+
+/// Indices are compared unsigned in their own width, so negative ones are
+/// out of range too. Constant indices that are known to be in range need no check.
+fn build_llvm_bounds_check(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    index: *mut llvm::LLVMValue,
+    length: *mut llvm::LLVMValue,
+) {
+    unsafe {
+        if !llvm::core::LLVMIsAConstantInt(index).is_null()
+            && !llvm::core::LLVMIsAConstantInt(length).is_null()
+            && llvm::core::LLVMGetIntTypeWidth(llvm::core::LLVMTypeOf(index)) <= 64
+            && llvm::core::LLVMConstIntGetZExtValue(index)
+                < llvm::core::LLVMConstIntGetZExtValue(length)
+        {
+            return;
+        }
+
+        let llvm_function =
+            llvm::core::LLVMGetBasicBlockParent(llvm::core::LLVMGetInsertBlock(llvm_builder));
+
+        let out_of_bounds = llvm::core::LLVMBuildICmp(
+            llvm_builder,
+            llvm::LLVMIntPredicate::LLVMIntUGE,
+            index,
+            length,
+            CStr::from_bytes_with_nul_unchecked(b"out_of_bounds_tmp\0").as_ptr(),
+        );
+
+        let fail_block = llvm::core::LLVMAppendBasicBlockInContext(
+            llvm::core::LLVMGetModuleContext(llvm_module),
+            llvm_function,
+            CStr::from_bytes_with_nul_unchecked(b"bounds_fail\0").as_ptr(),
+        );
+        let ok_block = llvm::core::LLVMAppendBasicBlockInContext(
+            llvm::core::LLVMGetModuleContext(llvm_module),
+            llvm_function,
+            CStr::from_bytes_with_nul_unchecked(b"bounds_ok\0").as_ptr(),
+        );
+
+        llvm::core::LLVMBuildCondBr(llvm_builder, out_of_bounds, fail_block, ok_block);
+
+        llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, fail_block);
+
+        let trap_type = llvm::core::LLVMFunctionType(
+            llvm::core::LLVMVoidTypeInContext(llvm::core::LLVMGetModuleContext(llvm_module)),
+            std::ptr::null_mut(),
+            0,
+            0,
+        );
+        let trap_name = CStr::from_bytes_with_nul_unchecked(b"llvm.trap\0");
+        let mut trap = llvm::core::LLVMGetNamedFunction(llvm_module, trap_name.as_ptr());
+        if trap.is_null() {
+            trap = llvm::core::LLVMAddFunction(llvm_module, trap_name.as_ptr(), trap_type);
+        }
+
+        llvm::core::LLVMBuildCall2(
+            llvm_builder,
+            trap_type,
+            trap,
+            std::ptr::null_mut(),
+            0,
+            CStr::from_bytes_with_nul_unchecked(b"\0").as_ptr(),
+        );
+        llvm::core::LLVMBuildUnreachable(llvm_builder);
+
+        llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, ok_block);
+    }
+}
+
+fn build_llvm_array(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    array: &ArrayExpression,
+) -> *mut llvm::LLVMValue {
+    let elements = match &array.elements {
+        ArrayElements::List(elements) => elements
+            .iter()
+            .map(|element| {
+                build_llvm_expression(
+                    llvm_module,
+                    llvm_builder,
+                    activation_records,
+                    options,
+                    element,
+                )
+            })
+            .collect(),
+        ArrayElements::Repeat(element, size) => {
+            let element = build_llvm_expression(
+                llvm_module,
+                llvm_builder,
+                activation_records,
+                options,
+                element,
+            );
+
+            vec![element; *size as usize]
+        }
+    };
+
+    build_llvm_aggregate(llvm_builder, llvm_type(llvm_module, &array.type_), elements)
+}
+
+/// Builds an array or struct value out of its elements, as a constant when
+/// they all are constants.
+fn build_llvm_aggregate(
+    llvm_builder: *mut llvm::LLVMBuilder,
+    llvm_type: *mut llvm::LLVMType,
+    elements: Vec<*mut llvm::LLVMValue>,
+) -> *mut llvm::LLVMValue {
+    let mut elements = elements;
+
+    unsafe {
+        if elements
+            .iter()
+            .all(|element| llvm::core::LLVMIsConstant(*element) != 0)
+        {
+            return match llvm::core::LLVMGetTypeKind(llvm_type) {
+                llvm::LLVMTypeKind::LLVMArrayTypeKind => llvm::core::LLVMConstArray(
+                    llvm::core::LLVMGetElementType(llvm_type),
+                    elements.as_mut_ptr(),
+                    elements.len() as std::os::raw::c_uint,
+                ),
+                _ => llvm::core::LLVMConstNamedStruct(
+                    llvm_type,
+                    elements.as_mut_ptr(),
+                    elements.len() as std::os::raw::c_uint,
+                ),
+            };
+        }
+
+        elements.iter().enumerate().fold(
+            llvm::core::LLVMGetUndef(llvm_type),
+            |aggregate, (index, element)| {
+                llvm::core::LLVMBuildInsertValue(
+                    llvm_builder,
+                    aggregate,
+                    *element,
+                    index as std::os::raw::c_uint,
+                    CStr::from_bytes_with_nul_unchecked(b"insert_tmp\0").as_ptr(),
+                )
+            },
+        )
+    }
+}
+
+/// Functions named by the callee are called directly, other callees are
+/// closures whose function is called with their captures.
+///
+/// Arguments and results whose C ABI type differs from their own type are
+/// converted on the way, which only happens for calls to extern functions.
+fn build_llvm_call(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    call: &CallExpression,
+) -> *mut llvm::LLVMValue {
+    let function = match &call.callee {
+        Expression::Identifier(id)
+            if unsafe {
+                !llvm::core::LLVMIsAFunction(activation_records[&id.id.value]).is_null()
+            } =>
+        {
+            Some(activation_records[&id.id.value])
+        }
+        _ => None,
+    };
+
+    let (callee, function_type, mut arguments) = match function {
+        Some(function) => unsafe {
+            let function_type = llvm::core::LLVMGetElementType(llvm::core::LLVMTypeOf(function));
+
+            (function, function_type, vec![])
+        },
+        None => unsafe {
+            let (parameters, return_type) = match call.callee.type_() {
+                Type::Function {
+                    parameters,
+                    return_type,
+                } => (parameters, return_type),
+                other => panic!("can not call {:?}", other),
+            };
+            let function_type = llvm_closure_function_type(llvm_module, &parameters, &return_type);
+
+            let closure = build_llvm_expression(
+                llvm_module,
+                llvm_builder,
+                activation_records,
+                options,
+                &call.callee,
+            );
+            let function = llvm::core::LLVMBuildExtractValue(
+                llvm_builder,
+                closure,
+                0,
+                CStr::from_bytes_with_nul_unchecked(b"function_tmp\0").as_ptr(),
+            );
+            let captures = llvm::core::LLVMBuildExtractValue(
+                llvm_builder,
+                closure,
+                1,
+                CStr::from_bytes_with_nul_unchecked(b"captures_tmp\0").as_ptr(),
+            );
+            let function = llvm::core::LLVMBuildBitCast(
+                llvm_builder,
+                function,
+                llvm::core::LLVMPointerType(function_type, 0),
+                CStr::from_bytes_with_nul_unchecked(b"cast_tmp\0").as_ptr(),
+            );
+
+            (function, function_type, vec![captures])
+        },
+    };
+
+    unsafe {
+        let mut parameter_types =
+            vec![std::ptr::null_mut(); llvm::core::LLVMCountParamTypes(function_type) as usize];
+        llvm::core::LLVMGetParamTypes(function_type, parameter_types.as_mut_ptr());
+
+        // A closure passed straight to a function can only outlive the call
+        // if the function returns it or stores it through a pointer. Closures
+        // are never passed to C, and a closure called through another closure
+        // may store it through the pointers that closure captured.
+        let keeps_closures = |index: usize| {
+            function.is_none()
+                || llvm_contains_pointer(llvm::core::LLVMGetReturnType(function_type))
+                || parameter_types
+                    .iter()
+                    .enumerate()
+                    .any(|(other, type_)| other != index && llvm_contains_pointer(*type_))
+        };
+        let on_stack: Vec<_> = (0..call.arguments.len())
+            .map(|index| !keeps_closures(index))
+            .collect();
+
+        arguments.extend(
+            call.arguments
+                .iter()
+                .zip(on_stack)
+                .zip(parameter_types.iter().copied().skip(arguments.len()))
+                .map(|((argument, on_stack), parameter_type)| {
+                    let argument = match argument {
+                        Expression::Closure(closure) => build_llvm_closure(
+                            llvm_module,
+                            llvm_builder,
+                            activation_records,
+                            closure,
+                            on_stack,
+                        ),
+                        argument => build_llvm_expression(
+                            llvm_module,
+                            llvm_builder,
+                            activation_records,
+                            options,
+                            argument,
+                        ),
+                    };
+
+                    if llvm::core::LLVMTypeOf(argument) == parameter_type {
+                        argument
+                    } else {
+                        build_llvm_abi_coercion(
+                            llvm_builder,
+                            argument,
+                            parameter_type,
+                            parameter_type,
+                        )
+                    }
+                }),
+        );
+
+        let result_type = llvm_type(llvm_module, &call.type_);
+        let return_type = llvm::core::LLVMGetReturnType(function_type);
+        let returns_void =
+            llvm::core::LLVMGetTypeKind(return_type) == llvm::LLVMTypeKind::LLVMVoidTypeKind;
+
+        // Calls that return nothing can not be named.
+        let name: &[u8] = if returns_void { b"\0" } else { b"call_tmp\0" };
+        let result = llvm::core::LLVMBuildCall2(
+            llvm_builder,
+            function_type,
+            callee,
+            arguments.as_mut_ptr(),
+            arguments.len() as std::os::raw::c_uint,
+            CStr::from_bytes_with_nul_unchecked(name).as_ptr(),
+        );
+
+        if returns_void {
+            llvm::core::LLVMConstNull(result_type)
+        } else if return_type == result_type {
+            result
+        } else {
+            build_llvm_abi_coercion(llvm_builder, result, result_type, return_type)
+        }
+    }
+}
+
+fn build_llvm_binary_operation(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    bin_op: &BinaryExpression,
+) -> *mut llvm::LLVMValue {
+    if let BinaryOperator::And | BinaryOperator::Or = bin_op.operator {
+        return build_llvm_short_circuit(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            bin_op,
+        );
+    }
+
+    let lhs = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &bin_op.lhs,
+    );
+    let rhs = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &bin_op.rhs,
+    );
+    if let Type::Float(_) = bin_op.lhs.type_() {
+        return build_llvm_float_operation(llvm_builder, bin_op.operator, lhs, rhs);
+    }
+
+    let signed = matches!(bin_op.lhs.type_(), Type::Int(_));
+
+    let compare = |predicate| unsafe {
+        llvm::core::LLVMBuildICmp(
+            llvm_builder,
+            predicate,
+            lhs,
+            rhs,
+            CStr::from_bytes_with_nul_unchecked(b"cmp_tmp\0").as_ptr(),
+        )
+    };
+
+    let shift_amount = || unsafe {
+        llvm::core::LLVMBuildIntCast2(
+            llvm_builder,
+            rhs,
+            llvm::core::LLVMTypeOf(lhs),
+            0,
+            CStr::from_bytes_with_nul_unchecked(b"shift_amount_tmp\0").as_ptr(),
+        )
+    };
+
+    unsafe {
+        match bin_op.operator {
+            BinaryOperator::Plus => llvm::core::LLVMBuildAdd(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"add_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Minus => llvm::core::LLVMBuildSub(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"sub_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Multiplication => llvm::core::LLVMBuildMul(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"mul_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Division if signed => llvm::core::LLVMBuildSDiv(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"div_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Division => llvm::core::LLVMBuildUDiv(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"div_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Remainder if signed => llvm::core::LLVMBuildSRem(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"rem_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Remainder => llvm::core::LLVMBuildURem(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"rem_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::BitAnd => llvm::core::LLVMBuildAnd(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"and_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::BitOr => llvm::core::LLVMBuildOr(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"or_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::BitXor => llvm::core::LLVMBuildXor(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"xor_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::ShiftLeft => llvm::core::LLVMBuildShl(
+                llvm_builder,
+                lhs,
+                shift_amount(),
+                CStr::from_bytes_with_nul_unchecked(b"shl_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::ShiftRight if signed => llvm::core::LLVMBuildAShr(
+                llvm_builder,
+                lhs,
+                shift_amount(),
+                CStr::from_bytes_with_nul_unchecked(b"shr_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::ShiftRight => llvm::core::LLVMBuildLShr(
+                llvm_builder,
+                lhs,
+                shift_amount(),
+                CStr::from_bytes_with_nul_unchecked(b"shr_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Equal => compare(llvm::LLVMIntPredicate::LLVMIntEQ),
+            BinaryOperator::NotEqual => compare(llvm::LLVMIntPredicate::LLVMIntNE),
+            BinaryOperator::GreaterThan if signed => compare(llvm::LLVMIntPredicate::LLVMIntSGT),
+            BinaryOperator::GreaterThan => compare(llvm::LLVMIntPredicate::LLVMIntUGT),
+            BinaryOperator::GreaterEqualThan if signed => {
+                compare(llvm::LLVMIntPredicate::LLVMIntSGE)
+            }
+            BinaryOperator::GreaterEqualThan => compare(llvm::LLVMIntPredicate::LLVMIntUGE),
+            BinaryOperator::LessThan if signed => compare(llvm::LLVMIntPredicate::LLVMIntSLT),
+            BinaryOperator::LessThan => compare(llvm::LLVMIntPredicate::LLVMIntULT),
+            BinaryOperator::LessEqualThan if signed => compare(llvm::LLVMIntPredicate::LLVMIntSLE),
+            BinaryOperator::LessEqualThan => compare(llvm::LLVMIntPredicate::LLVMIntULE),
+            BinaryOperator::And | BinaryOperator::Or => unreachable!(),
+        }
+    }
+}
+
+/// Comparisons are ordered, so they are false when either operand is NaN,
+/// except `!=` which is true.
+fn build_llvm_float_operation(
+    llvm_builder: *mut llvm::LLVMBuilder,
+    operator: BinaryOperator,
+    lhs: *mut llvm::LLVMValue,
+    rhs: *mut llvm::LLVMValue,
+) -> *mut llvm::LLVMValue {
+    let compare = |predicate| unsafe {
+        llvm::core::LLVMBuildFCmp(
+            llvm_builder,
+            predicate,
+            lhs,
+            rhs,
+            CStr::from_bytes_with_nul_unchecked(b"cmp_tmp\0").as_ptr(),
+        )
+    };
+
+    unsafe {
+        match operator {
+            BinaryOperator::Plus => llvm::core::LLVMBuildFAdd(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"add_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Minus => llvm::core::LLVMBuildFSub(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"sub_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Multiplication => llvm::core::LLVMBuildFMul(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"mul_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Division => llvm::core::LLVMBuildFDiv(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"div_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Remainder => llvm::core::LLVMBuildFRem(
+                llvm_builder,
+                lhs,
+                rhs,
+                CStr::from_bytes_with_nul_unchecked(b"rem_tmp\0").as_ptr(),
+            ),
+            BinaryOperator::Equal => compare(llvm::LLVMRealPredicate::LLVMRealOEQ),
+            BinaryOperator::NotEqual => compare(llvm::LLVMRealPredicate::LLVMRealUNE),
+            BinaryOperator::GreaterThan => compare(llvm::LLVMRealPredicate::LLVMRealOGT),
+            BinaryOperator::GreaterEqualThan => compare(llvm::LLVMRealPredicate::LLVMRealOGE),
+            BinaryOperator::LessThan => compare(llvm::LLVMRealPredicate::LLVMRealOLT),
+            BinaryOperator::LessEqualThan => compare(llvm::LLVMRealPredicate::LLVMRealOLE),
+            other => unreachable!("{:?} on floats", other),
+        }
+    }
+}
+
+/// `&&` and `||` only evaluate their right hand side when the left hand side
+/// does not already decide the result.
+fn build_llvm_short_circuit(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    bin_op: &BinaryExpression,
+) -> *mut llvm::LLVMValue {
+    let lhs = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &bin_op.lhs,
+    );
+
+    unsafe {
+        let lhs_block = llvm::core::LLVMGetInsertBlock(llvm_builder);
+        let llvm_function = llvm::core::LLVMGetBasicBlockParent(lhs_block);
+
+        let rhs_block = llvm::core::LLVMAppendBasicBlockInContext(
+            llvm::core::LLVMGetModuleContext(llvm_module),
+            llvm_function,
+            CStr::from_bytes_with_nul_unchecked(b"logic_rhs\0").as_ptr(),
+        );
+        let end_block = llvm::core::LLVMAppendBasicBlockInContext(
+            llvm::core::LLVMGetModuleContext(llvm_module),
+            llvm_function,
+            CStr::from_bytes_with_nul_unchecked(b"logic_end\0").as_ptr(),
+        );
+
+        match bin_op.operator {
+            BinaryOperator::And => {
+                llvm::core::LLVMBuildCondBr(llvm_builder, lhs, rhs_block, end_block)
+            }
+            _ => llvm::core::LLVMBuildCondBr(llvm_builder, lhs, end_block, rhs_block),
+        };
+
+        llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, rhs_block);
+        let rhs = build_llvm_expression(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            &bin_op.rhs,
+        );
+        let rhs_block = llvm::core::LLVMGetInsertBlock(llvm_builder);
+        llvm::core::LLVMBuildBr(llvm_builder, end_block);
+
+        llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, end_block);
+        let phi = llvm::core::LLVMBuildPhi(
+            llvm_builder,
+            llvm_type(llvm_module, &Type::Boolean),
+            CStr::from_bytes_with_nul_unchecked(b"logic_tmp\0").as_ptr(),
+        );
+
+        let mut incoming_values = [lhs, rhs];
+        let mut incoming_blocks = [lhs_block, rhs_block];
+        llvm::core::LLVMAddIncoming(
+            phi,
+            incoming_values.as_mut_ptr(),
+            incoming_blocks.as_mut_ptr(),
+            incoming_values.len() as std::os::raw::c_uint,
+        );
+
+        phi
+    }
+}
+
+fn build_llvm_unary_operation(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    unary: &UnaryExpression,
+) -> *mut llvm::LLVMValue {
+    if let UnaryOperator::AddressOf | UnaryOperator::MutAddressOf = unary.operator {
+        return build_llvm_place(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            &unary.expression,
+        );
+    }
+
+    let value = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &unary.expression,
+    );
+
+    unsafe {
+        match unary.operator {
+            UnaryOperator::Minus if matches!(unary.type_, Type::Float(_)) => {
+                llvm::core::LLVMBuildFNeg(
+                    llvm_builder,
+                    value,
+                    CStr::from_bytes_with_nul_unchecked(b"neg_tmp\0").as_ptr(),
+                )
+            }
+            UnaryOperator::Minus => llvm::core::LLVMBuildNeg(
+                llvm_builder,
+                value,
+                CStr::from_bytes_with_nul_unchecked(b"neg_tmp\0").as_ptr(),
+            ),
+            UnaryOperator::BitNot | UnaryOperator::Not => llvm::core::LLVMBuildNot(
+                llvm_builder,
+                value,
+                CStr::from_bytes_with_nul_unchecked(b"not_tmp\0").as_ptr(),
+            ),
+            UnaryOperator::Deref => llvm::core::LLVMBuildLoad2(
+                llvm_builder,
+                llvm_type(llvm_module, &unary.type_),
+                value,
+                CStr::from_bytes_with_nul_unchecked(b"deref_tmp\0").as_ptr(),
+            ),
+            _ => todo!(),
+        }
+    }
+}
+
+/// Integers are truncated or extended according to the signedness of the
+/// source type; casts between types of the same width are no-ops.
+///
+/// Floats become integers with `llvm.fptosi.sat`/`llvm.fptoui.sat`, which
+/// saturate: a float that does not fit the integer type gives its smallest or
+/// largest value, and NaN gives 0. Integers become floats with
+/// `sitofp`/`uitofp`, which round to the nearest float.
+fn build_llvm_cast(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    cast: &CastExpression,
+) -> *mut llvm::LLVMValue {
+    let value = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &cast.expression,
+    );
+
+    if let Some(value) = build_llvm_float_cast(llvm_module, llvm_builder, value, cast) {
+        return value;
+    }
+    if let Some(value) = build_llvm_pointer_cast(llvm_module, llvm_builder, value, cast) {
+        return value;
+    }
+
+    let (from_bits, from_signed) = match cast.expression.type_() {
+        Type::Boolean => (1, false),
+        Type::Char => (32, false),
+        Type::Int(ty) => (ty.bits(), true),
+        Type::UInt(ty) => (ty.bits(), false),
+        other => panic!("can not cast from {:?}", other),
+    };
+
+    let to_bits = match &cast.type_ {
+        Type::Boolean => 1,
+        Type::Char => 32,
+        Type::Int(ty) => ty.bits(),
+        Type::UInt(ty) => ty.bits(),
+        other => panic!("can not cast to {:?}", other),
+    };
+
+    let name = unsafe { CStr::from_bytes_with_nul_unchecked(b"cast_tmp\0").as_ptr() };
+    let to_type = llvm_type(llvm_module, &cast.type_);
+
+    if cast.type_ == Type::Char && from_bits == 32 {
+        return build_llvm_char_from_u32(llvm_module, llvm_builder, value);
+    }
+
+    unsafe {
+        if to_bits < from_bits {
+            llvm::core::LLVMBuildTrunc(llvm_builder, value, to_type, name)
+        } else if to_bits == from_bits {
+            value
+        } else if from_signed {
+            llvm::core::LLVMBuildSExt(llvm_builder, value, to_type, name)
+        } else {
+            llvm::core::LLVMBuildZExt(llvm_builder, value, to_type, name)
+        }
+    }
+}
+
+fn build_llvm_float_cast(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    value: *mut llvm::LLVMValue,
+    cast: &CastExpression,
+) -> Option<*mut llvm::LLVMValue> {
+    let name = unsafe { CStr::from_bytes_with_nul_unchecked(b"cast_tmp\0").as_ptr() };
+    let to_type = llvm_type(llvm_module, &cast.type_);
+
+    let value = unsafe {
+        match (cast.expression.type_(), &cast.type_) {
+            (Type::Float(from), Type::Float(to)) if from.bits() < to.bits() => {
+                llvm::core::LLVMBuildFPExt(llvm_builder, value, to_type, name)
+            }
+            (Type::Float(from), Type::Float(to)) if from.bits() > to.bits() => {
+                llvm::core::LLVMBuildFPTrunc(llvm_builder, value, to_type, name)
+            }
+            (Type::Float(_), Type::Float(_)) => value,
+            (Type::Float(from), to) => {
+                let signedness = if let Type::Int(_) = to { "s" } else { "u" };
+                let intrinsic = format!(
+                    "llvm.fpto{}i.sat.i{}.f{}",
+                    signedness,
+                    llvm::core::LLVMGetIntTypeWidth(to_type),
+                    from.bits()
+                );
+                let mut parameter_types = [llvm::core::LLVMTypeOf(value)];
+                let function_type = llvm::core::LLVMFunctionType(
+                    to_type,
+                    parameter_types.as_mut_ptr(),
+                    parameter_types.len() as std::os::raw::c_uint,
+                    0,
+                );
+                let function = llvm_c_function(
+                    llvm_module,
+                    &CString::new(intrinsic).unwrap(),
+                    function_type,
+                );
+                let mut arguments = [value];
+
+                llvm::core::LLVMBuildCall2(
+                    llvm_builder,
+                    function_type,
+                    function,
+                    arguments.as_mut_ptr(),
+                    arguments.len() as std::os::raw::c_uint,
+                    name,
+                )
+            }
+            (Type::Int(_), Type::Float(_)) => {
+                llvm::core::LLVMBuildSIToFP(llvm_builder, value, to_type, name)
+            }
+            (_, Type::Float(_)) => llvm::core::LLVMBuildUIToFP(llvm_builder, value, to_type, name),
+            _ => return None,
+        }
+    };
+
+    Some(value)
+}
+
+/// Pointers convert to and from integers of any width, zero extending or
+/// truncating the address.
+fn build_llvm_pointer_cast(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    value: *mut llvm::LLVMValue,
+    cast: &CastExpression,
+) -> Option<*mut llvm::LLVMValue> {
+    let name = unsafe { CStr::from_bytes_with_nul_unchecked(b"cast_tmp\0").as_ptr() };
+    let to_type = llvm_type(llvm_module, &cast.type_);
+
+    let value = unsafe {
+        match (cast.expression.type_(), &cast.type_) {
+            (Type::Pointer { .. }, Type::Pointer { .. }) => {
+                llvm::core::LLVMBuildBitCast(llvm_builder, value, to_type, name)
+            }
+            (Type::Pointer { .. }, _) => {
+                llvm::core::LLVMBuildPtrToInt(llvm_builder, value, to_type, name)
+            }
+            (_, Type::Pointer { .. }) => {
+                llvm::core::LLVMBuildIntToPtr(llvm_builder, value, to_type, name)
+            }
+            _ => return None,
+        }
+    };
+
+    Some(value)
+}
+
+/// A `u32` that is not a Unicode scalar value, because it is a surrogate or
+/// above `char::MAX`, becomes the replacement character U+FFFD.
+fn build_llvm_char_from_u32(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    value: *mut llvm::LLVMValue,
+) -> *mut llvm::LLVMValue {
+    let constant = |value: u32| build_llvm_const_int(llvm_module, &Type::Char, value as u128);
+
+    unsafe {
+        // Subtracting the start of the surrogates moves them to 0..0x800.
+        let offset = llvm::core::LLVMBuildSub(
+            llvm_builder,
+            value,
+            constant(0xd800),
+            CStr::from_bytes_with_nul_unchecked(b"surrogate_offset_tmp\0").as_ptr(),
+        );
+        let is_surrogate = llvm::core::LLVMBuildICmp(
+            llvm_builder,
+            llvm::LLVMIntPredicate::LLVMIntULT,
+            offset,
+            constant(0x800),
+            CStr::from_bytes_with_nul_unchecked(b"is_surrogate_tmp\0").as_ptr(),
+        );
+        let is_too_large = llvm::core::LLVMBuildICmp(
+            llvm_builder,
+            llvm::LLVMIntPredicate::LLVMIntUGT,
+            value,
+            constant(char::MAX as u32),
+            CStr::from_bytes_with_nul_unchecked(b"is_too_large_tmp\0").as_ptr(),
+        );
+        let is_invalid = llvm::core::LLVMBuildOr(
+            llvm_builder,
+            is_surrogate,
+            is_too_large,
+            CStr::from_bytes_with_nul_unchecked(b"is_invalid_tmp\0").as_ptr(),
+        );
+
+        llvm::core::LLVMBuildSelect(
+            llvm_builder,
+            is_invalid,
+            constant(std::char::REPLACEMENT_CHARACTER as u32),
+            value,
+            CStr::from_bytes_with_nul_unchecked(b"cast_tmp\0").as_ptr(),
+        )
+    }
+}
+
+/// Constant patterns become cases of a single `switch`; values it does not
+/// catch fall through to a chain of range checks, in arm order, that ends
+/// at the wildcard arm if there is one. Enums switch on their tag, and arms
+/// that bind payload fields read them from a spill of the scrutinee.
+fn build_llvm_match(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    match_: &MatchExpression,
+) -> *mut llvm::LLVMValue {
+    let scrutinee_type = match_.scrutinee.type_();
+    let scrutinee = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &match_.scrutinee,
+    );
+
+    let llvm_function = unsafe {
+        llvm::core::LLVMGetBasicBlockParent(llvm::core::LLVMGetInsertBlock(llvm_builder))
+    };
+
+    let (switch_value, switch_type) = match scrutinee_type {
+        Type::Named(_) => unsafe {
+            let tag = llvm::core::LLVMBuildExtractValue(
+                llvm_builder,
+                scrutinee,
+                0,
+                CStr::from_bytes_with_nul_unchecked(b"tag_tmp\0").as_ptr(),
+            );
+
+            (tag, llvm::core::LLVMTypeOf(tag))
+        },
+        _ => (scrutinee, llvm_type(llvm_module, &scrutinee_type)),
+    };
+
+    let scrutinee_place = if match_.arms.iter().any(|arm| !arm.bindings.is_empty()) {
+        unsafe {
+            let slot = build_llvm_entry_alloca(
+                llvm_builder,
+                llvm::core::LLVMTypeOf(scrutinee),
+                CStr::from_bytes_with_nul_unchecked(b"scrutinee_tmp\0"),
+            );
+            llvm::core::LLVMBuildStore(llvm_builder, scrutinee, slot);
+
+            Some(slot)
+        }
+    } else {
+        None
+    };
+
+    let append_block = |name: &[u8]| unsafe {
+        llvm::core::LLVMAppendBasicBlockInContext(
+            llvm::core::LLVMGetModuleContext(llvm_module),
+            llvm_function,
+            CStr::from_bytes_with_nul_unchecked(name).as_ptr(),
+        )
+    };
+
+    let arm_blocks: Vec<_> = match_
+        .arms
+        .iter()
+        .map(|_| append_block(b"match_arm\0"))
+        .collect();
+    let fallback_block = append_block(b"match_fallback\0");
+    let end_block = append_block(b"match_end\0");
+
+    let case_count = match_
+        .arms
+        .iter()
+        .flat_map(|arm| &arm.patterns)
+        .filter(|pattern| matches!(pattern, Pattern::Constant(_)))
+        .count();
+
+    let switch = unsafe {
+        llvm::core::LLVMBuildSwitch(
+            llvm_builder,
+            switch_value,
+            fallback_block,
+            case_count as std::os::raw::c_uint,
+        )
+    };
+
+    for (arm, arm_block) in match_.arms.iter().zip(&arm_blocks) {
+        for pattern in &arm.patterns {
+            if let Pattern::Constant(value) = pattern {
+                unsafe {
+                    llvm::core::LLVMAddCase(
+                        switch,
+                        build_llvm_const_bits(switch_type, *value),
+                        *arm_block,
+                    )
+                };
+            }
+        }
+    }
+
+    let (greater_equal, less_equal) = match scrutinee_type {
+        Type::Int(_) => (
+            llvm::LLVMIntPredicate::LLVMIntSGE,
+            llvm::LLVMIntPredicate::LLVMIntSLE,
+        ),
+        _ => (
+            llvm::LLVMIntPredicate::LLVMIntUGE,
+            llvm::LLVMIntPredicate::LLVMIntULE,
+        ),
+    };
+
+    unsafe { llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, fallback_block) };
+
+    let mut wildcard_block = None;
+
+    for (arm, arm_block) in match_.arms.iter().zip(&arm_blocks) {
+        for pattern in &arm.patterns {
+            match pattern {
+                Pattern::Range(start, end) => unsafe {
+                    let above_start = llvm::core::LLVMBuildICmp(
+                        llvm_builder,
+                        greater_equal,
+                        scrutinee,
+                        build_llvm_const_int(llvm_module, &scrutinee_type, *start),
+                        CStr::from_bytes_with_nul_unchecked(b"range_start_tmp\0").as_ptr(),
+                    );
+                    let below_end = llvm::core::LLVMBuildICmp(
+                        llvm_builder,
+                        less_equal,
+                        scrutinee,
+                        build_llvm_const_int(llvm_module, &scrutinee_type, *end),
+                        CStr::from_bytes_with_nul_unchecked(b"range_end_tmp\0").as_ptr(),
+                    );
+                    let in_range = llvm::core::LLVMBuildAnd(
+                        llvm_builder,
+                        above_start,
+                        below_end,
+                        CStr::from_bytes_with_nul_unchecked(b"in_range_tmp\0").as_ptr(),
+                    );
+
+                    let next_block = append_block(b"match_range\0");
+                    llvm::core::LLVMBuildCondBr(llvm_builder, in_range, *arm_block, next_block);
+                    llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, next_block);
+                },
+                Pattern::Wildcard => wildcard_block = Some(*arm_block),
+                Pattern::Constant(_) => (),
+            }
+        }
+    }
+
+    unsafe {
+        match wildcard_block {
+            Some(wildcard_block) => llvm::core::LLVMBuildBr(llvm_builder, wildcard_block),
+            None => llvm::core::LLVMBuildUnreachable(llvm_builder),
+        }
+    };
+
+    let mut incoming_values = vec![];
+    let mut incoming_blocks = vec![];
+
+    for (arm, arm_block) in match_.arms.iter().zip(&arm_blocks) {
+        unsafe { llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, *arm_block) };
+
+        activation_records.push();
+
+        for binding in &arm.bindings {
+            let payload = build_llvm_payload_place(
+                llvm_module,
+                llvm_builder,
+                &scrutinee_type,
+                &binding.variant.value,
+                scrutinee_place.unwrap(),
+            );
+
+            let value = unsafe {
+                let field = llvm::core::LLVMBuildStructGEP2(
+                    llvm_builder,
+                    llvm_variant_type(llvm_module, &scrutinee_type, &binding.variant.value),
+                    payload,
+                    binding.field as std::os::raw::c_uint,
+                    CStr::from_bytes_with_nul_unchecked(b"binding_tmp\0").as_ptr(),
+                );
+
+                llvm::core::LLVMBuildLoad2(
+                    llvm_builder,
+                    llvm_type(llvm_module, &binding.type_),
+                    field,
+                    CStr::from_bytes_with_nul_unchecked(b"load_tmp\0").as_ptr(),
+                )
+            };
+
+            activation_records.insert(binding.id.value.clone(), value);
+        }
+
+        incoming_values.push(build_llvm_expression(
+            llvm_module,
+            llvm_builder,
+            activation_records,
+            options,
+            &arm.expression,
+        ));
+
+        activation_records.pop();
+        incoming_blocks.push(unsafe { llvm::core::LLVMGetInsertBlock(llvm_builder) });
+
+        unsafe { llvm::core::LLVMBuildBr(llvm_builder, end_block) };
+    }
+
+    unsafe {
+        llvm::core::LLVMPositionBuilderAtEnd(llvm_builder, end_block);
+
+        let phi = llvm::core::LLVMBuildPhi(
+            llvm_builder,
+            llvm_type(llvm_module, &match_.type_),
+            CStr::from_bytes_with_nul_unchecked(b"match_tmp\0").as_ptr(),
+        );
+        llvm::core::LLVMAddIncoming(
+            phi,
+            incoming_values.as_mut_ptr(),
+            incoming_blocks.as_mut_ptr(),
+            incoming_values.len() as std::os::raw::c_uint,
+        );
+
+        phi
+    }
+}
+
+fn build_llvm_literal(
+    llvm_module: *mut llvm::LLVMModule,
+    _llvm_builder: *mut llvm::LLVMBuilder,
+    literal: &Literal,
+) -> *mut llvm::LLVMValue {
+    match literal {
+        Literal::Int(_, LiteralIntType::Unsufixed) => todo!(),
+        Literal::Int(value, _) => build_llvm_const_int(llvm_module, &literal.type_(), *value),
+        Literal::Float(value, _) => unsafe {
+            llvm::core::LLVMConstReal(llvm_type(llvm_module, &literal.type_()), *value)
+        },
+        Literal::Boolean(value) => {
+            build_llvm_const_int(llvm_module, &Type::Boolean, *value as u128)
+        }
+        Literal::Char(value) => build_llvm_const_int(llvm_module, &Type::Char, *value as u128),
+        Literal::Str(value) => build_llvm_str(llvm_module, value),
+        Literal::Null(None) => todo!(),
+        Literal::Null(Some(type_)) => unsafe {
+            llvm::core::LLVMConstPointerNull(llvm_type(llvm_module, type_))
+        },
+    }
+}
+
+/// The bytes of a string literal live in a private constant global. They
+/// are followed by a NUL that the length leaves out, so that literals can
+/// also be handed to C functions expecting a `const char *`.
+pub(crate) fn build_llvm_str(
+    llvm_module: *mut llvm::LLVMModule,
+    value: &str,
+) -> *mut llvm::LLVMValue {
+    unsafe {
+        let llvm_context = llvm::core::LLVMGetModuleContext(llvm_module);
+
+        let bytes = llvm::core::LLVMConstStringInContext(
+            llvm_context,
+            value.as_ptr() as *const std::os::raw::c_char,
+            value.len() as std::os::raw::c_uint,
+            0,
+        );
+
+        let global = llvm::core::LLVMAddGlobal(
+            llvm_module,
+            llvm::core::LLVMTypeOf(bytes),
+            CStr::from_bytes_with_nul_unchecked(b".str\0").as_ptr(),
+        );
+        llvm::core::LLVMSetInitializer(global, bytes);
+        llvm::core::LLVMSetGlobalConstant(global, 1);
+        llvm::core::LLVMSetLinkage(global, llvm::LLVMLinkage::LLVMPrivateLinkage);
+        llvm::core::LLVMSetUnnamedAddress(global, llvm::LLVMUnnamedAddr::LLVMGlobalUnnamedAddr);
+
+        let mut fields = [
+            llvm::core::LLVMConstBitCast(
+                global,
+                llvm::core::LLVMPointerType(llvm::core::LLVMInt8TypeInContext(llvm_context), 0),
+            ),
+            build_llvm_const_int(
+                llvm_module,
+                &Type::UInt(UIntType::USize),
+                value.len() as u128,
+            ),
+        ];
+
+        llvm::core::LLVMConstStructInContext(
+            llvm_context,
+            fields.as_mut_ptr(),
+            fields.len() as std::os::raw::c_uint,
+            0,
+        )
+    }
+}
+
+/// Offsets are not `inbounds`, a pointer may be moved anywhere as long as
+/// it is only dereferenced where there is memory.
+fn build_llvm_offset(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    offset: &OffsetExpression,
+) -> *mut llvm::LLVMValue {
+    let pointer = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &offset.pointer,
+    );
+    let count = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &offset.count,
+    );
+
+    let pointee = match &offset.type_ {
+        Type::Pointer { pointee, .. } => pointee,
+        other => panic!("can not offset {:?}", other),
+    };
+    let mut indices = [count];
+
+    unsafe {
+        llvm::core::LLVMBuildGEP2(
+            llvm_builder,
+            llvm_type(llvm_module, pointee),
+            pointer,
+            indices.as_mut_ptr(),
+            indices.len() as std::os::raw::c_uint,
+            CStr::from_bytes_with_nul_unchecked(b"offset_tmp\0").as_ptr(),
+        )
+    }
+}
+
+/// A C function of type `function_type`, declared unless the module already
+/// declares it, possibly with a different type.
+pub(crate) fn llvm_c_function(
+    llvm_module: *mut llvm::LLVMModule,
+    name: &CStr,
+    function_type: *mut llvm::LLVMType,
+) -> *mut llvm::LLVMValue {
+    unsafe {
+        let function = llvm::core::LLVMGetNamedFunction(llvm_module, name.as_ptr());
+        if function.is_null() {
+            return llvm::core::LLVMAddFunction(llvm_module, name.as_ptr(), function_type);
+        }
+
+        let pointer_type = llvm::core::LLVMPointerType(function_type, 0);
+        if llvm::core::LLVMTypeOf(function) == pointer_type {
+            function
+        } else {
+            llvm::core::LLVMConstBitCast(function, pointer_type)
+        }
+    }
+}
+
+/// Lowers `print` and `println` to `write(1, bytes, length)` from libc.
+fn build_llvm_print(
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_builder: *mut llvm::LLVMBuilder,
+    activation_records: &mut ActivationRecords,
+    options: &Options,
+    print: &PrintExpression,
+) -> *mut llvm::LLVMValue {
+    let argument = build_llvm_expression(
+        llvm_module,
+        llvm_builder,
+        activation_records,
+        options,
+        &print.argument,
+    );
+
+    unsafe {
+        let llvm_context = llvm::core::LLVMGetModuleContext(llvm_module);
+        let usize_type = llvm_type(llvm_module, &Type::UInt(UIntType::USize));
+
+        let mut parameter_types = [
+            llvm::core::LLVMInt32TypeInContext(llvm_context),
+            llvm::core::LLVMPointerType(llvm::core::LLVMInt8TypeInContext(llvm_context), 0),
+            usize_type,
+        ];
+        let write_type = llvm::core::LLVMFunctionType(
+            usize_type,
+            parameter_types.as_mut_ptr(),
+            parameter_types.len() as std::os::raw::c_uint,
+            0,
+        );
+        let write = llvm_c_function(
+            llvm_module,
+            CStr::from_bytes_with_nul_unchecked(b"write\0"),
+            write_type,
+        );
+
+        let build_write = |slice: *mut llvm::LLVMValue| {
+            let mut arguments = [
+                llvm::core::LLVMConstInt(parameter_types[0], 1, 0),
+                llvm::core::LLVMBuildExtractValue(
+                    llvm_builder,
+                    slice,
+                    0,
+                    CStr::from_bytes_with_nul_unchecked(b"bytes_tmp\0").as_ptr(),
+                ),
+                llvm::core::LLVMBuildExtractValue(
+                    llvm_builder,
+                    slice,
+                    1,
+                    CStr::from_bytes_with_nul_unchecked(b"length_tmp\0").as_ptr(),
+                ),
+            ];
+
+            llvm::core::LLVMBuildCall2(
+                llvm_builder,
+                write_type,
+                write,
+                arguments.as_mut_ptr(),
+                arguments.len() as std::os::raw::c_uint,
+                CStr::from_bytes_with_nul_unchecked(b"write_tmp\0").as_ptr(),
+            )
+        };
+
+        let written = build_write(argument);
+
+        if print.newline {
+            build_write(build_llvm_str(llvm_module, "\n"));
+        }
+
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toy_parser::parser::ModuleParser;
+    use toy_parser::resolve::resolve_names;
+    use toy_parser::typecheck::typecheck_root_module;
+
+    const X86_64_DATA_LAYOUT: &[u8] =
+        b"e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128\0";
+
+    fn emit_llvm_ir(code: &str) -> String {
+        emit_llvm_ir_with_options(code, &Options::default())
+    }
+
+    fn emit_llvm_ir_with_options(code: &str, options: &Options) -> String {
+        let mut module = ModuleParser::new().parse(0, code).unwrap();
+
+        if let Err(diagnostics) = resolve_names(&mut module) {
+            panic!("{}", diagnostics[0].message);
+        }
+
+        let module = typecheck_root_module(module);
+
+        unsafe {
+            // Like `toyc`, every module gets its own context, which keeps the
+            // struct types of tests running in parallel apart.
+            let llvm_context = llvm::core::LLVMContextCreate();
+            let llvm_module = llvm::core::LLVMModuleCreateWithNameInContext(
+                CStr::from_bytes_with_nul_unchecked(b"test\0").as_ptr(),
+                llvm_context,
+            );
+            llvm::core::LLVMSetDataLayout(
+                llvm_module,
+                CStr::from_bytes_with_nul_unchecked(X86_64_DATA_LAYOUT).as_ptr(),
+            );
+
+            populate_llvm_module(llvm_module, module, options);
+
+            let mut error = std::ptr::null_mut();
+            if llvm::analysis::LLVMVerifyModule(
+                llvm_module,
+                llvm::analysis::LLVMVerifierFailureAction::LLVMReturnStatusAction,
+                &mut error,
+            ) == 1
+            {
+                panic!(
+                    "invalid module: {}",
+                    CStr::from_ptr(error).to_str().unwrap()
+                );
+            }
+
+            let ir = CStr::from_ptr(llvm::core::LLVMPrintModuleToString(llvm_module))
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            llvm::core::LLVMDisposeModule(llvm_module);
+            llvm::core::LLVMContextDispose(llvm_context);
+
+            ir
+        }
+    }
+
+    #[test]
+    fn match_lowers_to_switch() {
+        let ir = emit_llvm_ir(
+            "
+            fn classify(x: u8): u8 => match x {
+                0 => 10,
+                1 | 2 => 20,
+                3..=9 => 30,
+                _ => 40,
+            };
+            ",
+        );
+
+        assert!(ir.contains("switch i8 %x, label %match_fallback"));
+        assert!(ir.contains("i8 1, label %match_arm"));
+        assert!(ir.contains("icmp uge i8 %x, 3"));
+        assert!(ir.contains("icmp ule i8 %x, 9"));
+        assert!(ir.contains("phi i8"));
+    }
+
+    #[test]
+    fn match_signed_range_and_bool() {
+        let ir = emit_llvm_ir(
+            "
+            fn sign(x: i8): bool => match x {
+                -128..=-1 => true,
+                _ => false,
+            };
+            fn negate(b: bool): bool => match b {
+                true => false,
+                false => true,
+            };
+            ",
+        );
+
+        assert!(ir.contains("icmp sge i8 %x, -128"));
+        assert!(ir.contains("icmp sle i8 %x, -1"));
+        assert!(ir.contains("switch i1 %b"));
+        assert!(ir.contains("unreachable"));
+    }
+
+    #[test]
+    fn cast_matrix() {
+        let types = [
+            ("i8", 8, true),
+            ("i16", 16, true),
+            ("i32", 32, true),
+            ("i64", 64, true),
+            ("i128", 128, true),
+            ("isize", 64, true),
+            ("u8", 8, false),
+            ("u16", 16, false),
+            ("u32", 32, false),
+            ("u64", 64, false),
+            ("u128", 128, false),
+            ("usize", 64, false),
+        ];
+
+        for (from, from_bits, from_signed) in &types {
+            for (to, to_bits, _) in &types {
+                let ir = emit_llvm_ir(&format!("fn f(x: {}): {} => x as {};", from, to, to));
+
+                let expected = if to_bits < from_bits {
+                    format!("trunc i{} %x to i{}", from_bits, to_bits)
+                } else if to_bits == from_bits {
+                    format!("ret i{} %x", to_bits)
+                } else if *from_signed {
+                    format!("sext i{} %x to i{}", from_bits, to_bits)
+                } else {
+                    format!("zext i{} %x to i{}", from_bits, to_bits)
+                };
+
+                assert!(ir.contains(&expected), "{} as {}:\n{}", from, to, ir);
+            }
+        }
+    }
+
+    #[test]
+    fn chars_are_i32() {
+        let ir = emit_llvm_ir(
+            "
+            fn byte(c: char): u8 => c as u8;
+            fn widen(b: u8): char => b as char;
+            fn checked(x: u32): char => x as char;
+            fn newline(): char => '\\n';
+            ",
+        );
+
+        assert!(ir.contains("trunc i32 %c to i8"));
+        assert!(ir.contains("zext i8 %b to i32"));
+        assert!(ir.contains("select i1 %is_invalid_tmp, i32 65533, i32 %x"));
+        assert!(ir.contains("ret i32 10"));
+    }
+
+    #[test]
+    fn wide_integer_constants_keep_their_high_bits() {
+        let ir = emit_llvm_ir(
+            "
+            fn big(): u128 => 0x1_0000_0000_0000_0002;
+            fn min(): i128 => -0x8000_0000_0000_0000_0000_0000_0000_0000i128;
+            fn classify(x: u128): u8 => match x {
+                0x2_0000_0000_0000_0000 => 1,
+                _ => 0,
+            };
+            ",
+        );
+
+        assert!(ir.contains("ret i128 18446744073709551618"));
+        assert!(ir.contains("ret i128 -170141183460469231731687303715884105728"));
+        assert!(ir.contains("i128 36893488147419103232, label %match_arm"));
+    }
+
+    #[test]
+    fn floats_use_float_instructions() {
+        let ir = emit_llvm_ir(
+            "
+            extern fn sqrt(x: f64): f64;
+            fn length(x: f64, y: f64): f64 => sqrt(x * x + y * y);
+            fn less(a: f32, b: f32): bool => a < b;
+            fn convert(x: i32): f32 => x as f64 as f32;
+            fn truncate(x: f32): u8 => x as u8;
+            fn half(): f32 => 0.5;
+            ",
+        );
+
+        assert!(ir.contains("declare double @sqrt(double)"));
+        assert!(ir.contains("fadd double %mul_tmp, %mul_tmp1"));
+        assert!(ir.contains("fcmp olt float %a, %b"));
+        assert!(ir.contains("sitofp i32 %x to double"));
+        assert!(ir.contains("fptrunc double %cast_tmp to float"));
+        assert!(ir.contains("call i8 @llvm.fptoui.sat.i8.f32(float %x)"));
+        assert!(ir.contains("ret float 5.000000e-01"));
+    }
+
+    #[test]
+    fn float_to_integer_casts_saturate() {
+        let ir = emit_llvm_ir(
+            "
+            fn byte(x: f64): u8 => x as u8;
+            fn wide(x: f32): i128 => x as i128;
+            fn signed(x: f64): i32 => x as i32;
+            fn again(x: f64): i32 => x as i32;
+            ",
+        );
+
+        assert!(ir.contains("call i8 @llvm.fptoui.sat.i8.f64(double %x)"));
+        assert!(ir.contains("call i128 @llvm.fptosi.sat.i128.f32(float %x)"));
+        assert_eq!(
+            ir.matches("declare i32 @llvm.fptosi.sat.i32.f64(double)")
+                .count(),
+            1
+        );
+        assert!(!ir.contains("fptosi "));
+        assert!(!ir.contains("fptoui "));
+    }
+
+    #[test]
+    fn pointers_are_typed_llvm_pointers() {
+        let ir = emit_llvm_ir(
+            "
+            extern fn malloc(n: usize): *mut u8;
+            fn store(p: *mut u16, x: u16): u16 => {
+                *offset(p, 3) = x;
+                *p
+            };
+            fn words(p: *mut u8): *mut u64 => p as *mut u64;
+            fn address(p: *mut u8): usize => p as usize;
+            fn none(): *const u8 => null;
+            ",
+        );
+
+        assert!(ir.contains("declare i8* @malloc(i64)"));
+        assert!(ir.contains("%offset_tmp = getelementptr i16, i16* %p, i64 3"));
+        assert!(ir.contains("store i16 %x, i16* %offset_tmp, align 2"));
+        assert!(ir.contains("load i16, i16* %p, align 2"));
+        assert!(ir.contains("bitcast i8* %p to i64*"));
+        assert!(ir.contains("ptrtoint i8* %p to i64"));
+        assert!(ir.contains("ret i8* null"));
+    }
+
+    #[test]
+    fn runtime_functions_are_defined_when_used() {
+        let ir = emit_llvm_ir(
+            "
+            fn buffer(): *mut u8 => alloc(64, 8);
+            fn stop(): () => exit(3);
+            ",
+        );
+
+        assert!(ir.contains("define linkonce_odr i8* @toy_alloc(i64 %size, i64 %align)"));
+        assert!(ir.contains("call i8* @aligned_alloc(i64 %align, i64 %rounded_tmp)"));
+        assert!(ir.contains("call i8* @toy_alloc(i64 64, i64 8)"));
+        assert!(ir.contains("define linkonce_odr {} @toy_exit(i32 %code)"));
+        assert!(ir.contains("call void @exit(i32 %code)"));
+        // `alloc` panics when out of memory, nothing calls `free` or `realloc`.
+        assert!(ir.contains("define linkonce_odr {} @toy_panic"));
+        assert!(!ir.contains("@toy_free"));
+        assert!(!ir.contains("@toy_realloc"));
+
+        let ir = emit_llvm_ir("fn f(): i32 => 0;");
+        assert!(!ir.contains("toy_"));
+    }
+
+    #[test]
+    fn extern_functions_returning_unit_return_void() {
+        let ir = emit_llvm_ir(
+            "
+            extern fn srand(seed: u32): ();
+            fn seed(): () => srand(7);
+            ",
+        );
+
+        assert!(ir.contains("declare void @srand(i32)"));
+        assert!(ir.contains("call void @srand(i32 7)"));
+        assert!(ir.contains("ret {} zeroinitializer"));
+    }
+
+    #[test]
+    fn module_functions_are_mangled() {
+        let ir = emit_llvm_ir(
+            "
+            mod math {
+                extern fn abs(x: i32): i32;
+                pub fn add(a: i32, b: i32): i32 => abs(a) + b;
+                pub mod inner { pub fn add(a: i32): i32 => a; }
+            }
+            extern fn abs(x: i32): i32;
+            fn add(a: i32): i32 => math::add(a, math::inner::add(abs(a)));
+            ",
+        );
+
+        assert!(ir.contains("define internal i32 @\"root::add\"(i32 %a)"));
+        assert!(ir.contains("define i32 @\"root::math::add\"(i32 %a, i32 %b)"));
+        assert!(ir.contains("define i32 @\"root::math::inner::add\"(i32 %a)"));
+        assert!(ir.contains("call i32 @\"root::math::inner::add\""));
+        assert_eq!(ir.matches("declare i32 @abs(i32)").count(), 1);
+    }
+
+    #[test]
+    fn only_public_functions_and_main_are_exported() {
+        let ir = emit_llvm_ir(
+            "
+            fn write(x: i32): i32 => x;
+            pub fn area(x: i32): i32 => write(x);
+            mod geo { pub fn double(x: i32): i32 => x * 2; }
+            fn greet(): isize => println(\"hi\");
+            fn main(): i32 => area(geo::double(1));
+            ",
+        );
+
+        assert!(ir.contains("define internal i32 @\"root::write\"(i32 %x)"));
+        assert!(ir.contains("define i32 @area(i32 %x)"));
+        assert!(ir.contains("define i32 @\"root::geo::double\"(i32 %x)"));
+        assert!(ir.contains("define i32 @main()"));
+        assert!(ir.contains("declare i64 @write(i32, i8*, i64)"));
+    }
+
+    #[test]
+    fn generic_instances_are_emitted_once() {
+        let ir = emit_llvm_ir(
+            "
+            trait Ord { fn gt(self, other: Self): bool; }
+            impl Ord for i64 { fn gt(self, other: i64): bool => self > other; }
+            fn max<T: Ord>(a: T, b: T): T => match a.gt(b) { true => a, false => b };
+            mod m { pub fn first<T>(t: (T, T)): T => t.0; }
+            fn f(a: i64, b: f32): i64 => max(a, max(a, 2)) + max(m::first((a, a)), b as i64);
+            ",
+        );
+
+        assert_eq!(
+            ir.matches("define internal i64 @\"root::max::<i64>\"(i64 %a, i64 %b)")
+                .count(),
+            1
+        );
+        assert_eq!(ir.matches("call i64 @\"root::max::<i64>\"").count(), 3);
+        assert!(ir.contains("define internal i64 @\"root::m::first::<i64>\"({ i64, i64 } %t)"));
+    }
+
+    #[test]
+    fn methods_are_called_directly() {
+        let ir = emit_llvm_ir(
+            "
+            trait Neg { fn neg(self): Self; }
+            impl Neg for i64 { fn neg(self): i64 => 0 - self; }
+            mod m { impl Neg for bool { fn neg(self): bool => !self; } }
+            fn f(a: i64, b: bool): bool => match a.neg() > 0 { true => b.neg(), false => b };
+            ",
+        );
+
+        assert!(ir.contains("define i64 @\"root::<i64 as Neg>::neg\"(i64 %self)"));
+        assert!(ir.contains("call i64 @\"root::<i64 as Neg>::neg\"(i64 %a)"));
+        assert!(ir.contains("call i1 @\"root::m::<bool as Neg>::neg\"(i1 %b)"));
+    }
+
+    #[test]
+    fn associated_functions_are_mangled() {
+        let ir = emit_llvm_ir(
+            "
+            mod geo {
+                pub struct Point { x: i32, y: i32 }
+                impl Point {
+                    pub fn new(x: i32): Point => Point { x: x, y: x };
+                    fn y(self): i32 => self.y;
+                }
+            }
+            fn f(): i32 => geo::Point::new(1).x;
+            ",
+        );
+
+        assert!(ir.contains("define %\"geo::Point\" @\"root::geo::<geo::Point>::new\"(i32 %x)"));
+        assert!(ir.contains("define internal i32 @\"root::geo::<geo::Point>::y\""));
+        assert!(ir.contains("call %\"geo::Point\" @\"root::geo::<geo::Point>::new\"(i32 1)"));
+    }
+
+    #[test]
+    fn function_values_are_called_indirectly() {
+        let ir = emit_llvm_ir(
+            "
+            fn inc(x: i8): i8 => x + 1;
+            fn apply(f: fn(i8): i8, x: i8): i8 => f(x);
+            fn g(): i8 => apply(inc, 1);
+            ",
+        );
+
+        assert!(ir.contains("define internal i8 @\"root::apply\"({ i8*, i8* } %f, i8 %x)"));
+        assert!(ir.contains("bitcast i8* %function_tmp to i8 (i8*, i8)*"));
+        assert!(ir.contains("call i8 %cast_tmp(i8* %captures_tmp, i8 %x)"));
+        // Functions are closures capturing nothing.
+        assert!(ir.contains("define internal i8 @\"root::inc::{closure}\"(i8* %0, i8 %1)"));
+        assert!(ir.contains("call i8 @\"root::inc\"(i8 %1)"));
+        assert!(ir.contains(
+            "call i8 @\"root::apply\"({ i8*, i8* } { i8* bitcast (i8 (i8*, i8)* @\"root::inc::{closure}\" to i8*), i8* null }, i8 1)"
+        ));
+    }
+
+    #[test]
+    fn closures_copy_their_captures_to_the_heap() {
+        let ir = emit_llvm_ir(
+            "
+            fn adder(k: i8): fn(i8): i8 => |x: i8| x + k;
+            ",
+        );
+
+        assert!(ir.contains("define internal i8 @\"root::{closure#0}\"(i8* %captures, i8 %x)"));
+        assert!(ir.contains("bitcast i8* %captures to { i8 }*"));
+        assert!(ir.contains("%k = load i8, i8* %gep_tmp, align 1"));
+        assert!(ir.contains("%captures_tmp = call i8* @toy_alloc("));
+        assert!(ir.contains("store i8 %k, i8* %gep_tmp, align 1"));
+        assert!(ir.contains("i8* bitcast (i8 (i8*, i8)* @\"root::{closure#0}\" to i8*), i8* undef }, i8* %captures_tmp, 1"));
+    }
+
+    #[test]
+    fn closures_passed_to_functions_keep_their_captures_on_the_stack() {
+        let ir = emit_llvm_ir(
+            "
+            fn apply(f: fn(i8): i8, x: i8): i8 => f(x);
+            fn h(k: i8): i8 => apply(|x: i8| x + k, 2);
+            fn keep(f: fn(i8): i8, p: *mut fn(i8): i8): i8 => 0;
+            fn g(k: i8, p: *mut fn(i8): i8): i8 => keep(|x: i8| x - k, p);
+            ",
+        );
+
+        assert!(ir.contains("%captures_alloca = alloca { i8 }"));
+        assert!(ir.contains("%captures_tmp = bitcast { i8 }* %captures_alloca to i8*"));
+        assert_eq!(ir.matches("call i8* @toy_alloc(").count(), 1);
+    }
+
+    #[test]
+    fn newtypes_are_erased() {
+        let ir = emit_llvm_ir(
+            "
+            newtype UserId = u64;
+            newtype Users = [UserId; 2];
+            fn first(users: Users): UserId => users.0[0];
+            fn make(): UserId => UserId(7);
+            ",
+        );
+
+        assert!(ir.contains("define internal i64 @\"root::first\"([2 x i64] %users)"));
+        assert!(ir.contains("define internal i64 @\"root::make\"()"));
+        assert!(ir.contains("ret i64 7"));
+    }
+
+    #[test]
+    fn declared_functions_are_called_by_symbol() {
+        let ir = emit_llvm_ir(
+            "
+            mod geo {
+                pub struct Point { x: i32, y: i32 }
+                pub fn origin(): Point = \"root::geo::origin\";
+            }
+            fn f(): i32 => geo::origin().x;
+            ",
+        );
+
+        assert!(ir.contains("declare %\"geo::Point\" @\"root::geo::origin\"()"));
+        assert!(ir.contains("call %\"geo::Point\" @\"root::geo::origin\"()"));
+    }
+
+    #[test]
+    fn cast_bool_to_int() {
+        let ir = emit_llvm_ir("fn f(b: bool): u32 => b as u32;");
+
+        assert!(ir.contains("zext i1 %b to i32"));
+    }
+
+    #[test]
+    fn bitwise_and_shift_operators() {
+        let ir = emit_llvm_ir(
+            "
+            fn unsigned_ops(x: u32, y: u32, n: u8): u32 => (x % y & y | x ^ ~y) >> n << 1;
+            fn signed_ops(x: i16, y: i16, n: u64): i16 => x % y + (x >> n) - -x / y;
+            ",
+        );
+
+        assert!(ir.contains("urem i32 %x, %y"));
+        assert!(ir.contains("and i32"));
+        assert!(ir.contains("xor i32 %y, -1"));
+        assert!(ir.contains("zext i8 %n to i32"));
+        assert!(ir.contains("lshr i32"));
+        assert!(ir.contains("shl i32"));
+
+        assert!(ir.contains("srem i16 %x, %y"));
+        assert!(ir.contains("trunc i64 %n to i16"));
+        assert!(ir.contains("ashr i16 %x"));
+        assert!(ir.contains("sub i16 0, %x"));
+        assert!(ir.contains("sdiv i16"));
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let ir = emit_llvm_ir(
+            "
+            fn signed(a: i32, b: i32): bool => a < b || a >= b && a != b;
+            fn unsigned(a: u8, b: u8): bool => a > b == (a <= b);
+            fn boolean(a: bool, b: bool): bool => !a != b;
+            ",
+        );
+
+        assert!(ir.contains("icmp slt i32 %a, %b"));
+        assert!(ir.contains("icmp sge i32 %a, %b"));
+        assert!(ir.contains("icmp ne i32 %a, %b"));
+        assert!(ir.contains("br i1 %cmp_tmp, label %logic_end, label %logic_rhs"));
+        assert!(ir.contains("phi i1"));
+
+        assert!(ir.contains("icmp ugt i8 %a, %b"));
+        assert!(ir.contains("icmp ule i8 %a, %b"));
+        assert!(ir.contains("icmp eq i1"));
+
+        assert!(ir.contains("xor i1 %a, true"));
+        assert!(ir.contains("icmp ne i1"));
+    }
+
+    #[test]
+    fn array_indexing_is_bounds_checked() {
+        let ir = emit_llvm_ir(
+            "
+            fn get(values: [u8; 4], i: u32): u8 => values[i];
+            fn set(i: i64): i32 => {
+                let values: [i32; 3] = [1, 2, i as i32];
+                values[i] = 7;
+                values[0]
+            };
+            ",
+        );
+
+        assert!(ir.contains("alloca [3 x i32]"));
+        assert!(ir.contains("zext i32 %i to i64"));
+        assert!(ir.contains("icmp uge i64 %index_tmp, 4"));
+        assert!(ir.contains("icmp uge i64 %i, 3"));
+        assert!(ir.contains("getelementptr inbounds [4 x i8]"));
+        assert!(ir.contains("call void @llvm.trap()"));
+        assert!(ir.contains("store i32 7"));
+        assert!(!ir.contains("icmp uge i64 0"));
+    }
+
+    #[test]
+    fn wide_indices_are_checked_before_truncation() {
+        let ir = emit_llvm_ir("fn get(values: [u8; 4], i: u128): u8 => values[i];");
+
+        assert!(ir.contains("icmp uge i128 %i, 4"));
+        assert!(ir.contains("trunc i128 %i to i64"));
+    }
+
+    #[test]
+    fn bounds_checks_can_be_disabled() {
+        let ir = emit_llvm_ir_with_options(
+            "fn get(values: [[u8; 2]; 4], i: usize): u8 => values[i][1];",
+            &Options {
+                bounds_checks: false,
+            },
+        );
+
+        assert!(ir.contains("getelementptr inbounds [4 x [2 x i8]]"));
+        assert!(!ir.contains("icmp"));
+        assert!(!ir.contains("llvm.trap"));
+    }
+
+    #[test]
+    fn constant_array_literals() {
+        let ir = emit_llvm_ir("fn f(): [u16; 3] => [7; 3];");
+
+        assert!(ir.contains("ret [3 x i16] [i16 7, i16 7, i16 7]"));
+    }
+
+    #[test]
+    fn structs() {
+        let ir = emit_llvm_ir(
+            "
+            struct Point { x: i32, y: i32 }
+            struct Line { from: Point, to: Point }
+            fn origin(): Point => Point { y: 0, x: 0 };
+            fn stretch(line: Line, dx: i32): Line => {
+                let moved: Line = Line { from: origin(), to: line.to };
+                moved.to.x = moved.to.x + dx;
+                moved
+            };
+            ",
+        );
+
+        assert!(ir.contains("%Point = type { i32, i32 }"));
+        assert!(ir.contains("%Line = type { %Point, %Point }"));
+        assert!(ir.contains("ret %Point zeroinitializer"));
+        assert!(ir.contains("getelementptr inbounds %Line, %Line* %moved, i32 0, i32 1"));
+        assert!(ir.contains("extractvalue %Line %line, 1"));
+    }
+
+    #[test]
+    fn tuples_return_as_aggregates() {
+        let ir = emit_llvm_ir(
+            "
+            extern fn split(value: u64): (u32, u16);
+            fn divmod(a: u32, b: u32): (u32, u32) => (a / b, a % b);
+            fn sum(value: u64): u32 => {
+                let (high, low) = split(value);
+                let (q, r) = divmod(high, 3);
+                q + r + low as u32
+            };
+            ",
+        );
+
+        assert!(ir.contains("declare i64 @split(i64)"));
+        assert!(ir.contains("bitcast i64* %abi_tmp to { i32, i16 }*"));
+        assert!(ir.contains("define internal { i32, i32 } @\"root::divmod\"(i32 %a, i32 %b)"));
+        assert!(ir.contains("insertvalue { i32, i32 } undef, i32 %div_tmp, 0"));
+        assert!(ir.contains("extractvalue { i32, i32 } %call_tmp2, 1"));
+    }
+
+    #[test]
+    fn strings_are_private_globals_printed_with_write() {
+        let ir = emit_llvm_ir(
+            r#"
+            fn greet(name: str): isize => {
+                let written: isize = print("hi\t");
+                written + println(name)
+            };
+            "#,
+        );
+
+        assert!(ir.contains(r#"@.str = private unnamed_addr constant [4 x i8] c"hi\09\00""#));
+        assert!(ir.contains("declare i64 @write(i32, i8*, i64)"));
+        assert!(ir.contains(
+            "call i64 @write(i32 1, i8* getelementptr inbounds ([4 x i8], [4 x i8]* @.str, i32 0, i32 0), i64 3)"
+        ));
+        assert!(ir.contains("%bytes_tmp = extractvalue { i8*, i64 } %name, 0"));
+        assert!(ir.contains(r#"c"\0A\00""#));
+    }
+
+    #[test]
+    fn slice_indexing_is_bounds_checked_against_the_length() {
+        let ir = emit_llvm_ir(
+            "
+            fn get(s: str, i: u32): u8 => s[i];
+            fn length(s: str): usize => len(s);
+            ",
+        );
+
+        assert!(ir.contains("%elements_tmp = extractvalue { i8*, i64 } %s, 0"));
+        assert!(ir.contains("%length_tmp = extractvalue { i8*, i64 } %s, 1"));
+        assert!(ir.contains("icmp uge i64 %index_tmp, %length_tmp"));
+        assert!(ir.contains("getelementptr inbounds i8, i8* %elements_tmp, i64 %index_tmp"));
+        assert!(ir.contains("call void @llvm.trap()"));
+        assert!(ir.contains("ret i64 %length_tmp"));
+    }
+
+    #[test]
+    fn enums() {
+        let ir = emit_llvm_ir(
+            "
+            enum Shape { Empty, Circle(u8), Rect(u32, u16) }
+            fn circle(r: u8): Shape => Shape::Circle(r);
+            fn height(s: Shape): u16 => match s {
+                Shape::Rect(_, h) => h,
+                _ => 0,
+            };
+            ",
+        );
+
+        assert!(ir.contains("%Shape = type { i8, [2 x i32] }"));
+        assert!(ir.contains("%\"Shape::Rect\" = type { i32, i16 }"));
+        assert!(ir.contains("store i8 1, i8* %tag_tmp"));
+        assert!(ir.contains("bitcast [2 x i32]* %payload_tmp to %\"Shape::Circle\"*"));
+        assert!(
+            ir.contains("switch i8 %tag_tmp, label %match_fallback [\n    i8 2, label %match_arm")
+        );
+        assert!(ir.contains(
+            "getelementptr inbounds %\"Shape::Rect\", %\"Shape::Rect\"* %payload_cast_tmp, i32 0, i32 1"
+        ));
+    }
+
+    #[test]
+    fn structs_cross_extern_functions_in_registers() {
+        let ir = emit_llvm_ir(
+            "
+            struct Pair { a: u8, b: u32 }
+            struct Wide { a: u64, b: u16 }
+            extern fn swap(pair: Pair, wide: Wide): Wide;
+            fn call(pair: Pair, wide: Wide): Wide => swap(pair, wide);
+            ",
+        );
+
+        assert!(ir.contains("declare { i64, i64 } @swap(i64, { i64, i64 })"));
+        assert!(
+            ir.contains("call { i64, i64 } @swap(i64 %abi_load_tmp, { i64, i64 } %abi_load_tmp")
+        );
+        assert!(ir.contains("load %Wide, %Wide*"));
+    }
+}
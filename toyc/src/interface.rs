@@ -0,0 +1,452 @@
+//! Writes the interface of a program, the `.toyi` file other programs are
+//! compiled against with `--extern`. It keeps every type and trait
+//! definition, as the functions of the program may take or return them, and
+//! declares each `pub` function and method and each method of a trait with
+//! the symbol it is defined under in the object file, and each `pub` constant
+//! with its value. It is written in the syntax of the language, so loading it
+//! is parsing it.
+//!
+//! Generic functions are compiled with the programs calling them, from their
+//! body, so a program with `pub` generic functions has no interface.
+
+use crate::backend::function_symbol;
+use std::fmt::Write;
+use toy_parser::ast::{
+    Expression, Identifier, ImplStatement, Literal, LiteralFloatType, LiteralIntType,
+    ModuleStatement, Statement, Type,
+};
+use toy_parser::generics::method_name;
+
+/// The interface of `root`, whose names must be resolved, or why it has none.
+pub fn write_interface(root: &ModuleStatement) -> Result<String, String> {
+    let mut interface = String::new();
+
+    write_module(
+        &mut interface,
+        root,
+        std::slice::from_ref(&root.id.value),
+        "",
+    )?;
+
+    Ok(interface)
+}
+
+/// Writes the items of the module at `path` from the root module.
+fn write_module(
+    out: &mut String,
+    module: &ModuleStatement,
+    path: &[String],
+    indent: &str,
+) -> Result<(), String> {
+    let visibility = |public| if public { "pub " } else { "" };
+
+    for statement in &module.statements {
+        match statement {
+            Statement::Module(m) => {
+                let mut nested_path = path.to_vec();
+                nested_path.push(m.id.value.clone());
+
+                writeln!(
+                    out,
+                    "{}{}mod {} {{",
+                    indent,
+                    visibility(m.public),
+                    m.id.value
+                )
+                .unwrap();
+                write_module(out, m, &nested_path, &format!("{}    ", indent))?;
+                writeln!(out, "{}}}", indent).unwrap();
+            }
+            Statement::StructDefinition(struct_) => {
+                let fields = match struct_.fields.as_slice() {
+                    [] => String::new(),
+                    fields => format!(" {} ", write_parameters(fields)),
+                };
+
+                writeln!(
+                    out,
+                    "{}{}struct {} {{{}}}",
+                    indent,
+                    visibility(struct_.public),
+                    item_name(&struct_.id.value),
+                    fields
+                )
+                .unwrap();
+            }
+            Statement::EnumDefinition(enum_) => {
+                let variants: Vec<_> = enum_
+                    .variants
+                    .iter()
+                    .map(|(id, types)| match types.as_slice() {
+                        [] => id.value.clone(),
+                        _ => format!("{}({})", id.value, write_types(types)),
+                    })
+                    .collect();
+
+                writeln!(
+                    out,
+                    "{}{}enum {} {{ {} }}",
+                    indent,
+                    visibility(enum_.public),
+                    item_name(&enum_.id.value),
+                    variants.join(", ")
+                )
+                .unwrap();
+            }
+            Statement::TypeAlias(alias) => {
+                writeln!(
+                    out,
+                    "{}{}type {} = {};",
+                    indent,
+                    visibility(alias.public),
+                    item_name(&alias.id.value),
+                    alias.type_
+                )
+                .unwrap();
+            }
+            Statement::Newtype(newtype) => {
+                writeln!(
+                    out,
+                    "{}{}newtype {} = {};",
+                    indent,
+                    visibility(newtype.public),
+                    item_name(&newtype.id.value),
+                    newtype.type_
+                )
+                .unwrap();
+            }
+            Statement::ExternFunctionDeclaration(function) if function.public => {
+                writeln!(
+                    out,
+                    "{}pub extern fn {}({}): {};",
+                    indent,
+                    function.id.value,
+                    write_parameters(&function.parameters),
+                    function.return_type
+                )
+                .unwrap();
+            }
+            Statement::FunctionDefinition(function)
+                if function.public && !function.type_parameters.is_empty() =>
+            {
+                let mut name = path[1..].to_vec();
+                name.push(function.id.value.clone());
+
+                return Err(format!(
+                    "pub generic function {} can not be declared in an interface",
+                    name.join("::")
+                ));
+            }
+            Statement::FunctionDefinition(function) if function.public => {
+                writeln!(
+                    out,
+                    "{}pub fn {}({}): {} = \"{}\";",
+                    indent,
+                    function.id.value,
+                    write_parameters(&function.parameters),
+                    function.return_type,
+                    function_symbol(path, &function.id, true)
+                )
+                .unwrap();
+            }
+            Statement::FunctionDeclaration(function) if function.public => {
+                writeln!(
+                    out,
+                    "{}pub fn {}({}): {} = \"{}\";",
+                    indent,
+                    function.id.value,
+                    write_parameters(&function.parameters),
+                    function.return_type,
+                    function.symbol
+                )
+                .unwrap();
+            }
+            Statement::VariableDefinition(constant) if constant.public => {
+                writeln!(
+                    out,
+                    "{}pub let {}: {} = {};",
+                    indent,
+                    constant.id.value,
+                    constant.type_,
+                    write_constant(&constant.id, &constant.initialize_expression)
+                )
+                .unwrap();
+            }
+            Statement::TraitDefinition(trait_) => {
+                let methods: Vec<_> = trait_
+                    .methods
+                    .iter()
+                    .map(|method| {
+                        format!(
+                            "fn {}({}): {};",
+                            method.id.value,
+                            write_parameters(&method.parameters),
+                            method.return_type
+                        )
+                    })
+                    .collect();
+                let methods = match methods.as_slice() {
+                    [] => String::new(),
+                    methods => format!(" {} ", methods.join(" ")),
+                };
+
+                writeln!(
+                    out,
+                    "{}{}trait {} {{{}}}",
+                    indent,
+                    visibility(trait_.public),
+                    item_name(&trait_.id.value),
+                    methods
+                )
+                .unwrap();
+            }
+            Statement::Impl(impl_) => write_impl(out, impl_, path, indent),
+            // Private functions can not be called from other programs.
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Declares the methods of a trait impl, or the `pub` functions of an impl
+/// without a trait.
+fn write_impl(out: &mut String, impl_: &ImplStatement, path: &[String], indent: &str) {
+    let declarations: Vec<_> = impl_
+        .functions
+        .iter()
+        .filter(|function| function.public || impl_.trait_.is_some())
+        .map(|function| {
+            let id = Identifier::from(method_name(
+                &impl_.type_,
+                impl_.trait_.as_ref(),
+                &function.id,
+            ));
+
+            format!(
+                "{}fn {}({}): {} = \"{}\";",
+                if function.public { "pub " } else { "" },
+                function.id.value,
+                write_parameters(&function.parameters),
+                function.return_type,
+                function_symbol(path, &id, true)
+            )
+        })
+        .chain(impl_.declarations.iter().map(|function| {
+            format!(
+                "{}fn {}({}): {} = \"{}\";",
+                if function.public { "pub " } else { "" },
+                function.id.value,
+                write_parameters(&function.parameters),
+                function.return_type,
+                function.symbol
+            )
+        }))
+        .collect();
+
+    if declarations.is_empty() {
+        return;
+    }
+
+    let trait_ = match &impl_.trait_ {
+        Some(trait_) => format!("{} for ", trait_.value),
+        None => String::new(),
+    };
+
+    writeln!(
+        out,
+        "{}impl {}{} {{ {} }}",
+        indent,
+        trait_,
+        impl_.type_,
+        declarations.join(" ")
+    )
+    .unwrap();
+}
+
+/// Type definitions are named by their path once resolved.
+fn item_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap()
+}
+
+/// Constants are initialized with a possibly negated literal, typecheck
+/// rejects the others.
+fn write_constant(id: &Identifier, value: &Expression) -> String {
+    match value {
+        Expression::Literal(Literal::Int(value, type_)) => match type_ {
+            LiteralIntType::Signed(t) => format!("{}{}", value, Type::Int(t.clone())),
+            LiteralIntType::Unsigned(t) => format!("{}{}", value, Type::UInt(t.clone())),
+            LiteralIntType::Unsufixed => value.to_string(),
+        },
+        // The debug format always keeps a fraction or an exponent.
+        Expression::Literal(Literal::Float(value, type_)) => match type_ {
+            LiteralFloatType::Suffixed(t) => format!("{:?}{}", value, Type::Float(t.clone())),
+            LiteralFloatType::Unsufixed => format!("{:?}", value),
+        },
+        Expression::Literal(Literal::Boolean(value)) => value.to_string(),
+        Expression::Literal(Literal::Char(c)) => format!("'{}'", c.escape_default()),
+        Expression::Literal(Literal::Str(s)) => format!("\"{}\"", s.escape_default()),
+        Expression::Unary(unary) => format!("-{}", write_constant(id, &unary.expression)),
+        _ => unreachable!("constant {} is not a literal", id.value),
+    }
+}
+
+fn write_parameters(parameters: &[(Identifier, Type)]) -> String {
+    let parameters: Vec<_> = parameters
+        .iter()
+        .map(|(id, type_)| match id.value.as_str() {
+            "self" => id.value.clone(),
+            _ => format!("{}: {}", id.value, type_),
+        })
+        .collect();
+
+    parameters.join(", ")
+}
+
+fn write_types(types: &[Type]) -> String {
+    let types: Vec<_> = types.iter().map(Type::to_string).collect();
+
+    types.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toy_parser::parser::ModuleParser;
+    use toy_parser::resolve::resolve_names;
+
+    fn interface(code: &str) -> String {
+        let mut module = ModuleParser::new().parse(0, code).unwrap();
+        resolve_names(&mut module).unwrap();
+
+        write_interface(&module).unwrap()
+    }
+
+    #[test]
+    fn declares_public_functions_with_their_symbol() {
+        let interface = interface(
+            "
+            pub struct Point { x: f32, y: f32 }
+            fn helper(): i32 => 1;
+            pub fn origin(): Point => Point { x: 0.0f32, y: 0.0f32 };
+            pub mod geo {
+                enum Shape { Dot(Point), Line([Point; 2]), Empty }
+                pub fn shape(p: *const Point, s: &[u8]): (Shape,) => (Shape::Dot(*p),);
+            }
+            ",
+        );
+
+        assert_eq!(
+            interface,
+            "pub struct Point { x: f32, y: f32 }\n\
+             pub fn origin(): Point = \"origin\";\n\
+             pub mod geo {\n    \
+                 enum Shape { Dot(Point), Line([Point; 2]), Empty }\n    \
+                 pub fn shape(p: *const Point, s: &[u8]): (geo::Shape,) = \"root::geo::shape\";\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn keeps_type_aliases_and_newtypes() {
+        let interface = interface(
+            "
+            pub mod units {
+                pub type Meters = u32;
+                pub newtype UserId = u64;
+            }
+            pub fn user(id: units::UserId, m: units::Meters): units::UserId => id;
+            ",
+        );
+
+        assert_eq!(
+            interface,
+            "pub mod units {\n    \
+                 pub type Meters = u32;\n    \
+                 pub newtype UserId = u64;\n\
+             }\n\
+             pub fn user(id: units::UserId, m: units::Meters): units::UserId = \"user\";\n"
+        );
+    }
+
+    #[test]
+    fn declares_public_constants_with_their_value() {
+        let interface = interface(
+            r#"
+            pub mod limits {
+                pub let max: u8 = 200;
+                let hidden: i32 = 1;
+                pub let scale: f32 = -2.5f32;
+                pub let tab: char = '\t';
+                pub let name: str = "a\"b";
+            }
+            "#,
+        );
+
+        assert_eq!(
+            interface,
+            "pub mod limits {\n    \
+                 pub let max: u8 = 200;\n    \
+                 pub let scale: f32 = -2.5f32;\n    \
+                 pub let tab: char = '\\t';\n    \
+                 pub let name: &[u8] = \"a\\\"b\";\n\
+             }\n"
+        );
+        ModuleParser::new().parse(0, &interface).unwrap();
+    }
+
+    #[test]
+    fn declares_traits_and_methods() {
+        let interface = interface(
+            "
+            pub trait Area { fn area(self): i32; fn scale(self, by: i32): Self; }
+            pub struct Rect { w: i32, h: i32 }
+            impl Area for Rect {
+                fn area(self): i32 => self.w * self.h;
+                fn scale(self, by: i32): Self => Rect { w: self.w * by, h: self.h * by };
+            }
+            impl Rect {
+                pub fn new(w: i32, h: i32): Self => Rect { w: w, h: h };
+                fn hidden(self): i32 => 0;
+            }
+            ",
+        );
+
+        assert_eq!(
+            interface,
+            "pub trait Area { fn area(self): i32; fn scale(self, by: i32): Self; }\n\
+             pub struct Rect { w: i32, h: i32 }\n\
+             impl Area for Rect { \
+                 fn area(self): i32 = \"root::<Rect as Area>::area\"; \
+                 fn scale(self, by: i32): Self = \"root::<Rect as Area>::scale\"; }\n\
+             impl Rect { pub fn new(w: i32, h: i32): Self = \"root::<Rect>::new\"; }\n"
+        );
+        ModuleParser::new().parse(0, &interface).unwrap();
+    }
+
+    #[test]
+    fn public_generic_functions_have_no_interface() {
+        let mut module = ModuleParser::new()
+            .parse(0, "pub mod util { pub fn id<T>(x: T): T => x; }")
+            .unwrap();
+        resolve_names(&mut module).unwrap();
+
+        assert_eq!(
+            write_interface(&module),
+            Err("pub generic function util::id can not be declared in an interface".to_string())
+        );
+    }
+
+    #[test]
+    fn interfaces_parse() {
+        let interface = interface(
+            "
+            pub struct Pair { a: (i32, bool), b: () }
+            pub extern fn abs(x: i32): i32;
+            pub fn first(p: Pair): (i32, bool) => p.a;
+            ",
+        );
+
+        ModuleParser::new().parse(0, &interface).unwrap();
+    }
+}
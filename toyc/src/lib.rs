@@ -1,4 +1,8 @@
 mod backend;
+mod interface;
+mod newtypes;
+mod runtime;
+mod source;
 
 #[derive(Debug, structopt::StructOpt)]
 pub struct Config {
@@ -10,49 +14,104 @@ pub struct Config {
 
     #[structopt(short = "a", long)]
     pub emit_ast: bool,
+
+    /// Do not check array indices against the array length at runtime
+    #[structopt(long)]
+    pub no_bounds_checks: bool,
+
+    /// The name of the program, which prefixes the symbols of its functions.
+    /// The file name without its extension by default
+    #[structopt(long)]
+    pub name: Option<String>,
+
+    /// Use the program with interface file `path` as module `name`
+    #[structopt(
+        long = "extern",
+        value_name = "name=path",
+        number_of_values = 1,
+        parse(try_from_str = parse_extern)
+    )]
+    pub externs: Vec<(String, std::path::PathBuf)>,
+}
+
+fn parse_extern(argument: &str) -> Result<(String, std::path::PathBuf), String> {
+    match argument.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), path.into()))
+        }
+        _ => Err(format!("expected name=path, found {}", argument)),
+    }
 }
 
 pub fn drive(config: Config) {
     use llvm_sys as llvm;
-    use std::ffi::{CStr, CString};
-    use std::fs::File;
-    use std::io::prelude::Read;
-    use toy_parser::parser::ModuleParser;
+    use std::ffi::CString;
 
     if !config.file.is_file() {
         panic!("Please provide a valid file");
     }
 
-    let file_buffer = match File::open(&config.file) {
-        Ok(mut file) => {
-            let mut file_buffer = String::new();
-
-            match file.read_to_string(&mut file_buffer) {
-                Ok(_) => (),
-                Err(e) => {
-                    println!(
-                        "Failed to read file {}: {}",
-                        (&config.file).to_str().unwrap(),
-                        e
-                    );
-                    return;
-                }
-            };
-
-            file_buffer
-        }
-        Err(e) => {
-            println!(
-                "Error trying to open file {}: {}",
-                config.file.to_str().unwrap(),
-                e
-            );
-            return;
+    let mut sources = source::SourceMap::default();
+
+    let mut module = match source::load_root_module(&config.file, &mut sources) {
+        Ok(module) => module,
+        Err(error) => {
+            eprintln!("error: {}", sources.render(&error));
+            std::process::exit(1);
         }
     };
 
-    let module = ModuleParser::new().parse(&file_buffer).unwrap();
+    // Programs linked together must have different names.
+    module.id.value = match &config.name {
+        Some(name) => name.clone(),
+        None => config.file.file_stem().unwrap().to_string_lossy().into_owned(),
+    };
+
+    for (name, path) in config.externs.iter().rev() {
+        let defined = module.statements.iter().any(|statement| {
+            matches!(statement, toy_parser::ast::Statement::Module(m) if m.id.value == *name)
+        });
+
+        if defined {
+            eprintln!("error: extern {} has the same name as a module", name);
+            std::process::exit(1);
+        }
+
+        match source::load_interface(name, path, &mut sources) {
+            Ok(interface) => module.statements.insert(
+                0,
+                toy_parser::ast::Statement::Module(Box::new(interface)),
+            ),
+            Err(error) => {
+                eprintln!("error: {}", sources.render(&error));
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Err(diagnostics) = toy_parser::resolve::resolve_names(&mut module) {
+        for diagnostic in &diagnostics {
+            eprintln!("error: {}", sources.render_diagnostic(diagnostic));
+        }
+        std::process::exit(1);
+    }
+
+    // The interface is written once the program typechecks, which makes sure
+    // its constants are literals.
+    let names = module.clone();
     let module = toy_parser::typecheck::typecheck_root_module(module);
+    let interface = interface::write_interface(&names);
+
+    for warning in &module.warnings {
+        eprintln!("warning: {}", sources.render_diagnostic(warning));
+    }
+
+    if !module.errors.is_empty() {
+        for error in &module.errors {
+            eprintln!("error: {}", sources.render_diagnostic(error));
+        }
+        std::process::exit(1);
+    }
 
     if config.emit_ast {
         println!("{:#?}", module);
@@ -104,7 +163,11 @@ pub fn drive(config: Config) {
         );
     }
 
-    backend::populate_llvm_module(llvm_module, module);
+    let options = backend::Options {
+        bounds_checks: !config.no_bounds_checks,
+    };
+
+    backend::populate_llvm_module(llvm_module, module, &options);
 
     if config.emit_llvm_ir {
         let asm =
@@ -135,6 +198,30 @@ pub fn drive(config: Config) {
         panic!("Failed to initialize llvm native target");
     };
 
+    let interface_name = config.file.with_extension("toyi");
+    let interface_name = interface_name.file_name().unwrap();
+
+    // Without an interface, the one of an earlier build is removed so that
+    // no program is compiled against it.
+    let written = match interface {
+        Ok(interface) => std::fs::write(interface_name, interface),
+        Err(reason) => {
+            eprintln!(
+                "warning: {} not written: {}",
+                interface_name.to_string_lossy(),
+                reason
+            );
+            match std::fs::remove_file(interface_name) {
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                removed => removed,
+            }
+        }
+    };
+    if let Err(error) = written {
+        eprintln!("error: {}: {}", interface_name.to_string_lossy(), error);
+        std::process::exit(1);
+    }
+
     unsafe { llvm::target_machine::LLVMDisposeTargetMachine(target_machine) };
 
     unsafe { llvm::core::LLVMDisposeModule(llvm_module) };
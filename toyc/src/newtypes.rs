@@ -0,0 +1,237 @@
+//! Newtypes are distinct types for typechecking only. Before code generation
+//! every newtype is replaced by the type it wraps, and making or unwrapping a
+//! value of a newtype by the value itself.
+
+use std::collections::HashMap;
+use toy_parser::ast::{Identifier, Type};
+use toy_parser::typecheck::ir::{ArrayElements, Expression, Module, Statement};
+
+/// Erases the newtypes defined in `root` and in the modules nested in it.
+pub fn erase_newtypes(root: &mut Module) {
+    let mut newtypes = HashMap::new();
+    collect_newtypes(root, &mut newtypes);
+
+    Eraser { newtypes }.module(root);
+}
+
+fn collect_newtypes(module: &Module, newtypes: &mut HashMap<Identifier, Type>) {
+    for newtype in &module.newtypes {
+        newtypes.insert(newtype.id.clone(), newtype.type_.clone());
+    }
+
+    for nested in &module.modules {
+        collect_newtypes(nested, newtypes);
+    }
+}
+
+struct Eraser {
+    newtypes: HashMap<Identifier, Type>,
+}
+
+impl Eraser {
+    fn module(&self, module: &mut Module) {
+        for struct_ in &mut module.structs {
+            for (_id, type_) in &mut struct_.fields {
+                self.type_(type_);
+            }
+        }
+
+        for enum_ in &mut module.enums {
+            for type_ in enum_.variants.iter_mut().flat_map(|(_id, types)| types) {
+                self.type_(type_);
+            }
+        }
+
+        for function in &mut module.extern_functions {
+            for (_id, type_) in &mut function.parameters {
+                self.type_(type_);
+            }
+            self.type_(&mut function.return_type);
+        }
+
+        for function in &mut module.functions {
+            for (_id, type_) in &mut function.parameters {
+                self.type_(type_);
+            }
+            self.type_(&mut function.return_type);
+            self.expression(&mut function.body);
+        }
+
+        for function in &mut module.function_declarations {
+            for (_id, type_) in &mut function.parameters {
+                self.type_(type_);
+            }
+            self.type_(&mut function.return_type);
+        }
+
+        for closure in &mut module.closures {
+            for (_id, type_) in closure.captures.iter_mut().chain(&mut closure.parameters) {
+                self.type_(type_);
+            }
+            self.type_(&mut closure.return_type);
+            self.expression(&mut closure.body);
+        }
+
+        for nested in &mut module.modules {
+            self.module(nested);
+        }
+    }
+
+    fn type_(&self, type_: &mut Type) {
+        match type_ {
+            Type::Named(id) => {
+                if let Some(wrapped) = self.newtypes.get(id) {
+                    *type_ = wrapped.clone();
+                    self.type_(type_);
+                }
+            }
+            Type::Function {
+                parameters,
+                return_type,
+            } => {
+                for parameter in parameters {
+                    self.type_(parameter);
+                }
+                self.type_(return_type);
+            }
+            Type::Array { element_type, .. } | Type::Slice(element_type) => {
+                self.type_(element_type)
+            }
+            Type::Pointer { pointee, .. } => self.type_(pointee),
+            Type::Tuple(types) => {
+                for type_ in types {
+                    self.type_(type_);
+                }
+            }
+            Type::Boolean
+            | Type::Char
+            | Type::Int(_)
+            | Type::UInt(_)
+            | Type::Float(_)
+            | Type::None => {}
+        }
+    }
+
+    fn statement(&self, statement: &mut Statement) {
+        match statement {
+            Statement::Module(module) => self.module(module),
+            Statement::VariableDefinition(variable) => {
+                self.type_(&mut variable.type_);
+                self.expression(&mut variable.initialize_expression);
+            }
+            Statement::TupleDefinition(tuple) => {
+                self.type_(&mut tuple.type_);
+                self.expression(&mut tuple.initialize_expression);
+            }
+            Statement::Assignment(assignment) => {
+                self.expression(&mut assignment.target);
+                self.expression(&mut assignment.expression);
+            }
+            Statement::None => {}
+        }
+    }
+
+    fn expression(&self, expression: &mut Expression) {
+        match expression {
+            Expression::Block(block) => {
+                self.type_(&mut block.type_);
+                for statement in &mut block.statements {
+                    self.statement(statement);
+                }
+                self.expression(&mut block.return_expression);
+            }
+            Expression::Unary(unary) => {
+                self.type_(&mut unary.type_);
+                self.expression(&mut unary.expression);
+            }
+            Expression::Binary(binary) => {
+                self.type_(&mut binary.type_);
+                self.expression(&mut binary.lhs);
+                self.expression(&mut binary.rhs);
+            }
+            Expression::Match(match_) => {
+                self.type_(&mut match_.type_);
+                self.expression(&mut match_.scrutinee);
+                for arm in &mut match_.arms {
+                    for binding in &mut arm.bindings {
+                        self.type_(&mut binding.type_);
+                    }
+                    self.expression(&mut arm.expression);
+                }
+            }
+            Expression::Cast(cast) => {
+                self.type_(&mut cast.type_);
+                self.expression(&mut cast.expression);
+            }
+            Expression::Array(array) => {
+                self.type_(&mut array.type_);
+                match &mut array.elements {
+                    ArrayElements::List(elements) => {
+                        for element in elements {
+                            self.expression(element);
+                        }
+                    }
+                    ArrayElements::Repeat(element, _size) => self.expression(element),
+                }
+            }
+            Expression::Access(access) => {
+                self.type_(&mut access.type_);
+                self.expression(&mut access.base);
+                self.expression(&mut access.offset);
+            }
+            Expression::Call(call) => {
+                self.type_(&mut call.type_);
+                self.expression(&mut call.callee);
+                for argument in &mut call.arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Print(print) => self.expression(&mut print.argument),
+            Expression::Length(length) => self.expression(&mut length.slice),
+            Expression::Offset(offset) => {
+                self.type_(&mut offset.type_);
+                self.expression(&mut offset.pointer);
+                self.expression(&mut offset.count);
+            }
+            Expression::Tuple(tuple) => {
+                self.type_(&mut tuple.type_);
+                for element in &mut tuple.elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Struct(struct_) => {
+                self.type_(&mut struct_.type_);
+                for field in &mut struct_.fields {
+                    self.expression(field);
+                }
+            }
+            Expression::Field(field) => {
+                self.type_(&mut field.type_);
+                self.expression(&mut field.base);
+            }
+            Expression::Variant(variant) => {
+                self.type_(&mut variant.type_);
+                for field in &mut variant.fields {
+                    self.expression(field);
+                }
+            }
+            Expression::Closure(closure) => {
+                self.type_(&mut closure.type_);
+                for capture in &mut closure.captures {
+                    self.type_(&mut capture.type_);
+                }
+            }
+            Expression::Newtype(_) => {
+                let newtype = match std::mem::replace(expression, Expression::None) {
+                    Expression::Newtype(newtype) => newtype,
+                    _ => unreachable!(),
+                };
+
+                *expression = newtype.expression;
+                self.expression(expression);
+            }
+            Expression::Identifier(id) => self.type_(&mut id.type_),
+            Expression::Literal(_) | Expression::None => {}
+        }
+    }
+}
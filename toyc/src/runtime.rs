@@ -0,0 +1,455 @@
+//! Definitions of the functions in `toy_parser::runtime`.
+//!
+//! Every object file gets its own copy of the runtime functions it calls,
+//! with `linkonce_odr` linkage so the linker keeps only one of them. They
+//! are named `toy_<name>` to stay clear of the libc functions they call.
+
+use crate::backend::{build_llvm_str, llvm_c_function, llvm_type};
+use llvm_sys as llvm;
+use std::ffi::{CStr, CString};
+use toy_parser::ast::{Type, UIntType};
+
+/// `malloc` only guarantees alignment for the largest scalar types.
+const MALLOC_ALIGNMENT: u64 = 16;
+
+/// Declares the runtime functions, returning them by the name programs
+/// call them with.
+pub fn declare_runtime_functions(
+    llvm_module: *mut llvm::LLVMModule,
+) -> Vec<(String, *mut llvm::LLVMValue)> {
+    toy_parser::runtime::functions()
+        .into_iter()
+        .map(|function| {
+            let mut parameter_types: Vec<_> = function
+                .parameters
+                .iter()
+                .map(|(_id, type_)| llvm_type(llvm_module, type_))
+                .collect();
+
+            unsafe {
+                let function_type = llvm::core::LLVMFunctionType(
+                    llvm_type(llvm_module, &function.return_type),
+                    parameter_types.as_mut_ptr(),
+                    parameter_types.len() as std::os::raw::c_uint,
+                    0,
+                );
+
+                let c_name = CString::new(format!("toy_{}", function.id.value)).unwrap();
+                let llvm_function =
+                    llvm::core::LLVMAddFunction(llvm_module, c_name.as_ptr(), function_type);
+
+                (function.id.value, llvm_function)
+            }
+        })
+        .collect()
+}
+
+/// Gives the runtime functions the module calls a body and removes the
+/// others, so that objects only depend on the parts of libc they use.
+pub fn define_runtime_functions(llvm_module: *mut llvm::LLVMModule) {
+    // Callers come before the runtime functions they call.
+    let definitions: [(&str, BuildBody); 5] = [
+        ("toy_realloc\0", Runtime::build_realloc),
+        ("toy_alloc\0", Runtime::build_alloc),
+        ("toy_free\0", Runtime::build_free),
+        ("toy_exit\0", Runtime::build_exit),
+        ("toy_panic\0", Runtime::build_panic),
+    ];
+
+    unsafe {
+        let runtime = Runtime {
+            llvm_module,
+            llvm_context: llvm::core::LLVMGetModuleContext(llvm_module),
+            llvm_builder: llvm::core::LLVMCreateBuilderInContext(llvm::core::LLVMGetModuleContext(
+                llvm_module,
+            )),
+        };
+
+        for (name, build_body) in definitions.iter() {
+            let c_name = CStr::from_bytes_with_nul_unchecked(name.as_bytes());
+            let llvm_function = llvm::core::LLVMGetNamedFunction(llvm_module, c_name.as_ptr());
+
+            if llvm::core::LLVMGetFirstUse(llvm_function).is_null() {
+                llvm::core::LLVMDeleteFunction(llvm_function);
+                continue;
+            }
+
+            llvm::core::LLVMSetLinkage(llvm_function, llvm::LLVMLinkage::LLVMLinkOnceODRLinkage);
+            build_body(&runtime, llvm_function);
+        }
+
+        llvm::core::LLVMDisposeBuilder(runtime.llvm_builder);
+    }
+}
+
+type BuildBody = fn(&Runtime, *mut llvm::LLVMValue);
+
+struct Runtime {
+    llvm_module: *mut llvm::LLVMModule,
+    llvm_context: *mut llvm::LLVMContext,
+    llvm_builder: *mut llvm::LLVMBuilder,
+}
+
+impl Runtime {
+    /// `alloc(size, align)` uses `malloc`, or `aligned_alloc` for
+    /// alignments `malloc` does not guarantee.
+    fn build_alloc(&self, llvm_function: *mut llvm::LLVMValue) {
+        let size = self.param(llvm_function, 0, "size");
+        let align = self.param(llvm_function, 1, "align");
+
+        let entry = self.append_block(llvm_function, "entry");
+        let unaligned = self.append_block(llvm_function, "unaligned");
+        let aligned = self.append_block(llvm_function, "aligned");
+        let allocated = self.append_block(llvm_function, "allocated");
+
+        unsafe {
+            self.position_at_end(entry);
+            self.build_branch_if_over_aligned(align, aligned, unaligned);
+
+            self.position_at_end(unaligned);
+            let unaligned_pointer = self.call_libc("malloc", self.pointer_type(), &mut [size]);
+            llvm::core::LLVMBuildBr(self.llvm_builder, allocated);
+
+            // `aligned_alloc` wants the size to be a multiple of the alignment.
+            self.position_at_end(aligned);
+            let mask = llvm::core::LLVMBuildSub(
+                self.llvm_builder,
+                align,
+                self.usize_const(1),
+                CStr::from_bytes_with_nul_unchecked(b"mask_tmp\0").as_ptr(),
+            );
+            let padded = llvm::core::LLVMBuildAdd(
+                self.llvm_builder,
+                size,
+                mask,
+                CStr::from_bytes_with_nul_unchecked(b"padded_tmp\0").as_ptr(),
+            );
+            let rounded = llvm::core::LLVMBuildAnd(
+                self.llvm_builder,
+                padded,
+                llvm::core::LLVMBuildNot(
+                    self.llvm_builder,
+                    mask,
+                    CStr::from_bytes_with_nul_unchecked(b"not_mask_tmp\0").as_ptr(),
+                ),
+                CStr::from_bytes_with_nul_unchecked(b"rounded_tmp\0").as_ptr(),
+            );
+            let aligned_pointer =
+                self.call_libc("aligned_alloc", self.pointer_type(), &mut [align, rounded]);
+            llvm::core::LLVMBuildBr(self.llvm_builder, allocated);
+
+            self.position_at_end(allocated);
+            let pointer = llvm::core::LLVMBuildPhi(
+                self.llvm_builder,
+                self.pointer_type(),
+                CStr::from_bytes_with_nul_unchecked(b"pointer\0").as_ptr(),
+            );
+            let mut incoming_values = [unaligned_pointer, aligned_pointer];
+            let mut incoming_blocks = [unaligned, aligned];
+            llvm::core::LLVMAddIncoming(
+                pointer,
+                incoming_values.as_mut_ptr(),
+                incoming_blocks.as_mut_ptr(),
+                incoming_values.len() as std::os::raw::c_uint,
+            );
+
+            self.build_out_of_memory_check(llvm_function, pointer, size);
+            llvm::core::LLVMBuildRet(self.llvm_builder, pointer);
+        }
+    }
+
+    /// `realloc(pointer, size, align, new_size)` uses `realloc`, which
+    /// does not keep larger alignments, so over-aligned memory is moved to a
+    /// new allocation instead.
+    fn build_realloc(&self, llvm_function: *mut llvm::LLVMValue) {
+        let pointer = self.param(llvm_function, 0, "pointer");
+        let size = self.param(llvm_function, 1, "size");
+        let align = self.param(llvm_function, 2, "align");
+        let new_size = self.param(llvm_function, 3, "new_size");
+
+        let entry = self.append_block(llvm_function, "entry");
+        let unaligned = self.append_block(llvm_function, "unaligned");
+        let aligned = self.append_block(llvm_function, "aligned");
+
+        unsafe {
+            self.position_at_end(entry);
+            self.build_branch_if_over_aligned(align, aligned, unaligned);
+
+            self.position_at_end(unaligned);
+            let new_pointer =
+                self.call_libc("realloc", self.pointer_type(), &mut [pointer, new_size]);
+            self.build_out_of_memory_check(llvm_function, new_pointer, new_size);
+            llvm::core::LLVMBuildRet(self.llvm_builder, new_pointer);
+
+            self.position_at_end(aligned);
+            let new_pointer = self.call_runtime("toy_alloc\0", &mut [new_size, align]);
+            let shrinks = llvm::core::LLVMBuildICmp(
+                self.llvm_builder,
+                llvm::LLVMIntPredicate::LLVMIntULT,
+                new_size,
+                size,
+                CStr::from_bytes_with_nul_unchecked(b"shrinks_tmp\0").as_ptr(),
+            );
+            let kept = llvm::core::LLVMBuildSelect(
+                self.llvm_builder,
+                shrinks,
+                new_size,
+                size,
+                CStr::from_bytes_with_nul_unchecked(b"kept_tmp\0").as_ptr(),
+            );
+            llvm::core::LLVMBuildMemCpy(self.llvm_builder, new_pointer, 1, pointer, 1, kept);
+            self.call_libc("free", self.void_type(), &mut [pointer]);
+            llvm::core::LLVMBuildRet(self.llvm_builder, new_pointer);
+        }
+    }
+
+    fn build_free(&self, llvm_function: *mut llvm::LLVMValue) {
+        let pointer = self.param(llvm_function, 0, "pointer");
+
+        unsafe {
+            self.position_at_end(self.append_block(llvm_function, "entry"));
+            self.call_libc("free", self.void_type(), &mut [pointer]);
+            llvm::core::LLVMBuildRet(self.llvm_builder, self.unit());
+        }
+    }
+
+    fn build_exit(&self, llvm_function: *mut llvm::LLVMValue) {
+        let code = self.param(llvm_function, 0, "code");
+
+        unsafe {
+            self.position_at_end(self.append_block(llvm_function, "entry"));
+            self.call_libc("exit", self.void_type(), &mut [code]);
+            llvm::core::LLVMBuildUnreachable(self.llvm_builder);
+        }
+    }
+
+    /// `panic(message)` writes `panic: <message>` and a newline to standard
+    /// error and exits with status 101.
+    fn build_panic(&self, llvm_function: *mut llvm::LLVMValue) {
+        let message = self.param(llvm_function, 0, "message");
+
+        unsafe {
+            self.position_at_end(self.append_block(llvm_function, "entry"));
+
+            for slice in &[
+                build_llvm_str(self.llvm_module, "panic: "),
+                message,
+                build_llvm_str(self.llvm_module, "\n"),
+            ] {
+                let mut arguments = [
+                    llvm::core::LLVMConstInt(
+                        llvm::core::LLVMInt32TypeInContext(self.llvm_context),
+                        2,
+                        0,
+                    ),
+                    llvm::core::LLVMBuildExtractValue(
+                        self.llvm_builder,
+                        *slice,
+                        0,
+                        CStr::from_bytes_with_nul_unchecked(b"bytes_tmp\0").as_ptr(),
+                    ),
+                    llvm::core::LLVMBuildExtractValue(
+                        self.llvm_builder,
+                        *slice,
+                        1,
+                        CStr::from_bytes_with_nul_unchecked(b"length_tmp\0").as_ptr(),
+                    ),
+                ];
+                self.call_libc("write", self.usize_type(), &mut arguments);
+            }
+
+            let mut status = [llvm::core::LLVMConstInt(
+                llvm::core::LLVMInt32TypeInContext(self.llvm_context),
+                101,
+                0,
+            )];
+            self.call_libc("exit", self.void_type(), &mut status);
+            llvm::core::LLVMBuildUnreachable(self.llvm_builder);
+        }
+    }
+
+    /// Panics with "out of memory" when `pointer` is null even though
+    /// `size` bytes were asked for.
+    fn build_out_of_memory_check(
+        &self,
+        llvm_function: *mut llvm::LLVMValue,
+        pointer: *mut llvm::LLVMValue,
+        size: *mut llvm::LLVMValue,
+    ) {
+        let out_of_memory = self.append_block(llvm_function, "out_of_memory");
+        let done = self.append_block(llvm_function, "done");
+
+        unsafe {
+            let is_null = llvm::core::LLVMBuildIsNull(
+                self.llvm_builder,
+                pointer,
+                CStr::from_bytes_with_nul_unchecked(b"is_null_tmp\0").as_ptr(),
+            );
+            let is_empty = llvm::core::LLVMBuildICmp(
+                self.llvm_builder,
+                llvm::LLVMIntPredicate::LLVMIntEQ,
+                size,
+                self.usize_const(0),
+                CStr::from_bytes_with_nul_unchecked(b"is_empty_tmp\0").as_ptr(),
+            );
+            let failed = llvm::core::LLVMBuildAnd(
+                self.llvm_builder,
+                is_null,
+                llvm::core::LLVMBuildNot(
+                    self.llvm_builder,
+                    is_empty,
+                    CStr::from_bytes_with_nul_unchecked(b"not_empty_tmp\0").as_ptr(),
+                ),
+                CStr::from_bytes_with_nul_unchecked(b"failed_tmp\0").as_ptr(),
+            );
+            llvm::core::LLVMBuildCondBr(self.llvm_builder, failed, out_of_memory, done);
+
+            self.position_at_end(out_of_memory);
+            self.call_runtime(
+                "toy_panic\0",
+                &mut [build_llvm_str(self.llvm_module, "out of memory")],
+            );
+            llvm::core::LLVMBuildUnreachable(self.llvm_builder);
+
+            self.position_at_end(done);
+        }
+    }
+
+    fn build_branch_if_over_aligned(
+        &self,
+        align: *mut llvm::LLVMValue,
+        then_block: *mut llvm::LLVMBasicBlock,
+        else_block: *mut llvm::LLVMBasicBlock,
+    ) {
+        unsafe {
+            let over_aligned = llvm::core::LLVMBuildICmp(
+                self.llvm_builder,
+                llvm::LLVMIntPredicate::LLVMIntUGT,
+                align,
+                self.usize_const(MALLOC_ALIGNMENT),
+                CStr::from_bytes_with_nul_unchecked(b"over_aligned_tmp\0").as_ptr(),
+            );
+            llvm::core::LLVMBuildCondBr(self.llvm_builder, over_aligned, then_block, else_block);
+        }
+    }
+
+    fn call_libc(
+        &self,
+        name: &str,
+        return_type: *mut llvm::LLVMType,
+        arguments: &mut [*mut llvm::LLVMValue],
+    ) -> *mut llvm::LLVMValue {
+        unsafe {
+            let mut parameter_types: Vec<_> = arguments
+                .iter()
+                .map(|argument| llvm::core::LLVMTypeOf(*argument))
+                .collect();
+            let function_type = llvm::core::LLVMFunctionType(
+                return_type,
+                parameter_types.as_mut_ptr(),
+                parameter_types.len() as std::os::raw::c_uint,
+                0,
+            );
+            let c_name = CString::new(name).unwrap();
+            let function = llvm_c_function(self.llvm_module, &c_name, function_type);
+
+            // Calls that return nothing can not be named.
+            let call_name: &[u8] = if return_type == self.void_type() {
+                b"\0"
+            } else {
+                b"call_tmp\0"
+            };
+
+            llvm::core::LLVMBuildCall2(
+                self.llvm_builder,
+                function_type,
+                function,
+                arguments.as_mut_ptr(),
+                arguments.len() as std::os::raw::c_uint,
+                CStr::from_bytes_with_nul_unchecked(call_name).as_ptr(),
+            )
+        }
+    }
+
+    fn call_runtime(
+        &self,
+        name: &str,
+        arguments: &mut [*mut llvm::LLVMValue],
+    ) -> *mut llvm::LLVMValue {
+        unsafe {
+            let c_name = CStr::from_bytes_with_nul_unchecked(name.as_bytes());
+            let function = llvm::core::LLVMGetNamedFunction(self.llvm_module, c_name.as_ptr());
+
+            llvm::core::LLVMBuildCall2(
+                self.llvm_builder,
+                llvm::core::LLVMGetElementType(llvm::core::LLVMTypeOf(function)),
+                function,
+                arguments.as_mut_ptr(),
+                arguments.len() as std::os::raw::c_uint,
+                CStr::from_bytes_with_nul_unchecked(b"call_tmp\0").as_ptr(),
+            )
+        }
+    }
+
+    fn param(
+        &self,
+        llvm_function: *mut llvm::LLVMValue,
+        index: u32,
+        name: &str,
+    ) -> *mut llvm::LLVMValue {
+        unsafe {
+            let param = llvm::core::LLVMGetParam(llvm_function, index);
+            llvm::core::LLVMSetValueName2(param, name.as_ptr() as *const _, name.len());
+            param
+        }
+    }
+
+    fn append_block(
+        &self,
+        llvm_function: *mut llvm::LLVMValue,
+        name: &str,
+    ) -> *mut llvm::LLVMBasicBlock {
+        let c_name = CString::new(name).unwrap();
+
+        unsafe {
+            llvm::core::LLVMAppendBasicBlockInContext(
+                self.llvm_context,
+                llvm_function,
+                c_name.as_ptr(),
+            )
+        }
+    }
+
+    fn position_at_end(&self, block: *mut llvm::LLVMBasicBlock) {
+        unsafe { llvm::core::LLVMPositionBuilderAtEnd(self.llvm_builder, block) }
+    }
+
+    fn usize_type(&self) -> *mut llvm::LLVMType {
+        llvm_type(self.llvm_module, &Type::UInt(UIntType::USize))
+    }
+
+    fn usize_const(&self, value: u64) -> *mut llvm::LLVMValue {
+        unsafe { llvm::core::LLVMConstInt(self.usize_type(), value, 0) }
+    }
+
+    fn pointer_type(&self) -> *mut llvm::LLVMType {
+        unsafe {
+            llvm::core::LLVMPointerType(llvm::core::LLVMInt8TypeInContext(self.llvm_context), 0)
+        }
+    }
+
+    fn void_type(&self) -> *mut llvm::LLVMType {
+        unsafe { llvm::core::LLVMVoidTypeInContext(self.llvm_context) }
+    }
+
+    fn unit(&self) -> *mut llvm::LLVMValue {
+        unsafe {
+            llvm::core::LLVMConstNull(llvm::core::LLVMStructTypeInContext(
+                self.llvm_context,
+                std::ptr::null_mut(),
+                0,
+                0,
+            ))
+        }
+    }
+}
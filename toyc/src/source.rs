@@ -0,0 +1,613 @@
+//! Loads a program from several files. `mod foo;` is replaced by the module
+//! in `foo.toy` or `foo/mod.toy`, looked up next to the file that declares it,
+//! so one module tree is built from every file of the program. The interfaces
+//! of other programs are loaded as modules too.
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use toy_parser::ast::{Identifier, ModuleDeclarationStatement, ModuleStatement, Statement, Type};
+use toy_parser::escape::EscapeError;
+use toy_parser::parser::ModuleParser;
+use toy_parser::resolve::Diagnostic;
+
+/// Refers to a file of a `SourceMap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileId(usize);
+
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub text: String,
+    /// Where the file starts among the files of the program.
+    pub start: usize,
+}
+
+/// The files a program was loaded from, used to report where errors are.
+/// Byte offsets count through every file in the order they were added, so an
+/// offset alone tells which file it is in.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn add(&mut self, path: PathBuf, text: String) -> FileId {
+        // A byte between files keeps the end of a file apart from the start
+        // of the next one.
+        let start = self
+            .files
+            .last()
+            .map_or(0, |file| file.start + file.text.len() + 1);
+
+        self.files.push(SourceFile { path, text, start });
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    /// The file byte `offset` is in, with the line and column, counting from
+    /// 1, of the byte in it.
+    pub fn location(&self, offset: usize) -> (&SourceFile, usize, usize) {
+        let file = self
+            .files
+            .iter()
+            .rev()
+            .find(|file| file.start <= offset)
+            .expect("offset before the first file");
+
+        let before = &file.text[..offset - file.start];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+        (
+            file,
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    fn located(&self, offset: usize, message: &str) -> String {
+        let (file, line, column) = self.location(offset);
+
+        format!("{}:{}:{}: {}", file.path.display(), line, column, message)
+    }
+
+    pub fn render(&self, error: &LoadError) -> String {
+        match error {
+            LoadError::Io { path, error } => format!("{}: {}", path.display(), error),
+            LoadError::Located { offset, message } => self.located(*offset, message),
+        }
+    }
+
+    pub fn render_diagnostic(&self, diagnostic: &Diagnostic) -> String {
+        match diagnostic.span {
+            Some((start, _end)) => self.located(start, &diagnostic.message),
+            None => diagnostic.message.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// `message` is about the byte at `offset` of the `SourceMap`.
+    Located { offset: usize, message: String },
+}
+
+/// Parses the file at `path` as the root module and loads the files of the
+/// modules it declares. Every file read is added to `sources`.
+pub fn load_root_module(
+    path: &Path,
+    sources: &mut SourceMap,
+) -> Result<ModuleStatement, LoadError> {
+    let mut loader = Loader {
+        sources,
+        loading: vec![],
+    };
+
+    loader.load_file(path)
+}
+
+/// Parses the interface at `path`, written for another program by
+/// `interface::write_interface`, as module `name` of this program. Types and
+/// traits in the interface are named by their path in the other program, and
+/// become named by their path under `name`.
+pub fn load_interface(
+    name: &str,
+    path: &Path,
+    sources: &mut SourceMap,
+) -> Result<ModuleStatement, LoadError> {
+    let text = std::fs::read_to_string(path).map_err(|error| LoadError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let file = sources.add(path.to_path_buf(), text);
+    let mut interface = parse_file(sources, file)?;
+
+    prefix_types(&mut interface.statements, name);
+
+    Ok(ModuleStatement {
+        id: Identifier::from(name.to_string()),
+        public: false,
+        statements: interface.statements,
+    })
+}
+
+fn prefix_types(statements: &mut [Statement], prefix: &str) {
+    for statement in statements {
+        let types: Vec<&mut Type> = match statement {
+            Statement::Module(m) => {
+                prefix_types(&mut m.statements, prefix);
+                continue;
+            }
+            Statement::StructDefinition(struct_) => struct_
+                .fields
+                .iter_mut()
+                .map(|(_id, type_)| type_)
+                .collect(),
+            Statement::EnumDefinition(enum_) => enum_
+                .variants
+                .iter_mut()
+                .flat_map(|(_id, types)| types)
+                .collect(),
+            Statement::TypeAlias(alias) => vec![&mut alias.type_],
+            Statement::Newtype(newtype) => vec![&mut newtype.type_],
+            Statement::FunctionDeclaration(function) => function
+                .parameters
+                .iter_mut()
+                .map(|(_id, type_)| type_)
+                .chain(std::iter::once(&mut function.return_type))
+                .collect(),
+            Statement::ExternFunctionDeclaration(function) => function
+                .parameters
+                .iter_mut()
+                .map(|(_id, type_)| type_)
+                .chain(std::iter::once(&mut function.return_type))
+                .collect(),
+            Statement::TraitDefinition(trait_) => trait_
+                .methods
+                .iter_mut()
+                .flat_map(|method| {
+                    method
+                        .parameters
+                        .iter_mut()
+                        .map(|(_id, type_)| type_)
+                        .chain(std::iter::once(&mut method.return_type))
+                })
+                .collect(),
+            Statement::Impl(impl_) => {
+                if let Some(trait_) = &mut impl_.trait_ {
+                    trait_.value = format!("{}::{}", prefix, trait_.value);
+                }
+
+                impl_
+                    .declarations
+                    .iter_mut()
+                    .flat_map(|function| {
+                        function
+                            .parameters
+                            .iter_mut()
+                            .map(|(_id, type_)| type_)
+                            .chain(std::iter::once(&mut function.return_type))
+                    })
+                    .chain(std::iter::once(&mut impl_.type_))
+                    .collect()
+            }
+            _ => continue,
+        };
+
+        for type_ in types {
+            prefix_type(type_, prefix);
+        }
+    }
+}
+
+fn prefix_type(type_: &mut Type, prefix: &str) {
+    match type_ {
+        Type::Named(id) if id.value == "Self" => {}
+        Type::Named(id) => id.value = format!("{}::{}", prefix, id.value),
+        Type::Function {
+            parameters,
+            return_type,
+        } => {
+            for parameter in parameters {
+                prefix_type(parameter, prefix);
+            }
+            prefix_type(return_type, prefix);
+        }
+        Type::Tuple(types) => {
+            for type_ in types {
+                prefix_type(type_, prefix);
+            }
+        }
+        Type::Array { element_type, .. } | Type::Slice(element_type) => {
+            prefix_type(element_type, prefix)
+        }
+        Type::Pointer { pointee, .. } => prefix_type(pointee, prefix),
+        Type::Boolean | Type::Char | Type::Int(_) | Type::UInt(_) | Type::Float(_) | Type::None => {
+        }
+    }
+}
+
+struct Loader<'a> {
+    sources: &'a mut SourceMap,
+    /// The files being loaded, each declaring a module of the next one, with
+    /// their canonical path to tell when a file includes itself.
+    loading: Vec<(PathBuf, FileId)>,
+}
+
+impl Loader<'_> {
+    fn load_file(&mut self, path: &Path) -> Result<ModuleStatement, LoadError> {
+        let io_error = |error| LoadError::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+
+        let canonical = path.canonicalize().map_err(io_error)?;
+        let text = std::fs::read_to_string(path).map_err(io_error)?;
+        let file = self.sources.add(path.to_path_buf(), text);
+        let mut module = parse_file(self.sources, file)?;
+
+        self.loading.push((canonical, file));
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        self.load_declarations(&mut module.statements, directory)?;
+        self.loading.pop();
+
+        Ok(module)
+    }
+
+    /// Replaces the `mod foo;` declarations in `statements` with the modules
+    /// they name. Their files are in `directory`.
+    fn load_declarations(
+        &mut self,
+        statements: &mut [Statement],
+        directory: &Path,
+    ) -> Result<(), LoadError> {
+        for statement in statements {
+            let declaration = match statement {
+                Statement::Module(m) => {
+                    let directory = directory.join(&m.id.value);
+                    self.load_declarations(&mut m.statements, &directory)?;
+                    continue;
+                }
+                Statement::ModuleDeclaration(declaration) => declaration,
+                _ => continue,
+            };
+
+            let path = self.module_file(declaration, directory)?;
+            let loaded = self.load_file(&path)?;
+
+            *statement = Statement::Module(Box::new(ModuleStatement {
+                id: declaration.id.clone(),
+                public: declaration.public,
+                statements: loaded.statements,
+            }));
+        }
+
+        Ok(())
+    }
+
+    fn module_file(
+        &self,
+        declaration: &ModuleDeclarationStatement,
+        directory: &Path,
+    ) -> Result<PathBuf, LoadError> {
+        let name = &declaration.id.value;
+        let error = |message| LoadError::Located {
+            offset: declaration.span.0,
+            message,
+        };
+
+        let flat = directory.join(format!("{}.toy", name));
+        let nested = directory.join(name).join("mod.toy");
+
+        let path = match (flat.is_file(), nested.is_file()) {
+            (true, false) => flat,
+            (false, true) => nested,
+            (true, true) => {
+                return Err(error(format!(
+                    "module {} is in both {} and {}",
+                    name,
+                    flat.display(),
+                    nested.display()
+                )))
+            }
+            (false, false) => {
+                return Err(error(format!(
+                    "file not found for module {}, expected {} or {}",
+                    name,
+                    flat.display(),
+                    nested.display()
+                )))
+            }
+        };
+
+        let canonical = path.canonicalize().map_err(|error| LoadError::Io {
+            path: path.clone(),
+            error,
+        })?;
+
+        if let Some(start) = self.loading.iter().position(|(c, _)| *c == canonical) {
+            let cycle: Vec<_> = self.loading[start..]
+                .iter()
+                .map(|(_, file)| self.sources.file(*file).path.display().to_string())
+                .chain(std::iter::once(path.display().to_string()))
+                .collect();
+
+            return Err(error(format!(
+                "module {} includes itself: {}",
+                name,
+                cycle.join(" -> ")
+            )));
+        }
+
+        Ok(path)
+    }
+}
+
+fn parse_file(sources: &SourceMap, file: FileId) -> Result<ModuleStatement, LoadError> {
+    let file = sources.file(file);
+
+    ModuleParser::new()
+        .parse(file.start, &file.text)
+        .map_err(|error| {
+            let (offset, message) = describe_parse_error(&error);
+            LoadError::Located {
+                offset: file.start + offset,
+                message,
+            }
+        })
+}
+
+type ParseError<T> = lalrpop_util::ParseError<usize, T, EscapeError>;
+
+/// The offset an error is at and what is wrong there.
+fn describe_parse_error<T: Display>(error: &ParseError<T>) -> (usize, String) {
+    // Terminals matched by a regex, like identifiers and literals, are left
+    // out as their patterns are not helpful.
+    let expected = |expected: &[String]| {
+        let expected: Vec<_> = expected
+            .iter()
+            .filter(|terminal| terminal.starts_with('"'))
+            .map(String::as_str)
+            .collect();
+
+        match expected.as_slice() {
+            [] => String::new(),
+            [one] => format!(", expected {}", one),
+            _ => format!(", expected one of {}", expected.join(", ")),
+        }
+    };
+
+    match error {
+        ParseError::InvalidToken { location } => (*location, "invalid token".to_string()),
+        ParseError::UnrecognizedEOF {
+            location,
+            expected: tokens,
+        } => (
+            *location,
+            format!("unexpected end of file{}", expected(tokens)),
+        ),
+        ParseError::UnrecognizedToken {
+            token: (start, token, _end),
+            expected: tokens,
+        } => (
+            *start,
+            format!("unexpected `{}`{}", token, expected(tokens)),
+        ),
+        ParseError::ExtraToken {
+            token: (start, token, _end),
+        } => (*start, format!("unexpected `{}`", token)),
+        ParseError::User { error } => (error.span.0, error.message.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory holding `files`, given as paths and contents.
+    fn program(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("toyc-{}-{}", name, std::process::id()));
+
+        if directory.exists() {
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+
+        for (path, text) in files {
+            let path = directory.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+
+        directory
+    }
+
+    fn load(directory: &Path) -> Result<ModuleStatement, String> {
+        let mut sources = SourceMap::default();
+
+        load_root_module(&directory.join("main.toy"), &mut sources)
+            .map_err(|error| sources.render(&error))
+    }
+
+    #[test]
+    fn loads_declared_modules() {
+        let directory = program(
+            "modules",
+            &[
+                ("main.toy", "mod math; mod io { mod file; }"),
+                ("math.toy", "mod vector; fn add(a: i32): i32 => a;"),
+                ("vector/mod.toy", "struct Vector { x: f32 }"),
+                ("io/file.toy", "fn open(): bool => true;"),
+            ],
+        );
+
+        let module = load(&directory).unwrap();
+
+        let math = match &module.statements[0] {
+            Statement::Module(math) => math,
+            statement => panic!("expected a module, found {:?}", statement),
+        };
+        assert_eq!(math.id.value, "math");
+        assert!(matches!(&math.statements[0], Statement::Module(m) if m.id.value == "vector"));
+        assert!(matches!(
+            &math.statements[1],
+            Statement::FunctionDefinition(_)
+        ));
+
+        match &module.statements[1] {
+            Statement::Module(io) => assert!(matches!(
+                &io.statements[0],
+                Statement::Module(file) if file.statements.len() == 1
+            )),
+            statement => panic!("expected a module, found {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn missing_module_file() {
+        let directory = program("missing", &[("main.toy", "fn f(): i32 => 0;\n  mod gone;")]);
+
+        let error = load(&directory).unwrap_err();
+
+        assert!(error.contains("main.toy:2:3: file not found for module gone"));
+    }
+
+    #[test]
+    fn parse_errors_name_their_file() {
+        let directory = program(
+            "parse-error",
+            &[
+                ("main.toy", "mod broken;"),
+                ("broken.toy", "\nfn f(): i32 => ;"),
+            ],
+        );
+
+        let error = load(&directory).unwrap_err();
+
+        assert!(error.contains("broken.toy:2:16: unexpected `;`"));
+    }
+
+    #[test]
+    fn resolve_diagnostics_name_their_file() {
+        let directory = program(
+            "resolve",
+            &[
+                ("main.toy", "mod math;\nfn f(): i32 => math::secret();"),
+                ("math.toy", "fn secret(): i32 => 1;\nuse nowhere::*;"),
+            ],
+        );
+
+        let mut sources = SourceMap::default();
+        let mut module = load_root_module(&directory.join("main.toy"), &mut sources).unwrap();
+        let diagnostics = toy_parser::resolve::resolve_names(&mut module).unwrap_err();
+        let errors: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| sources.render_diagnostic(diagnostic))
+            .collect();
+
+        assert!(errors[0].ends_with("math.toy:2:1: unresolved import nowhere::*"));
+        assert!(errors[1].ends_with("main.toy:2:16: item math::secret is private"));
+    }
+
+    #[test]
+    fn interface_types_are_named_under_the_extern() {
+        let directory = program(
+            "interface",
+            &[(
+                "geo.toyi",
+                "pub struct Point { x: i32 }\n\
+                 pub mod shapes { pub fn dot(p: *const Point): shapes::Dot = \"root::shapes::dot\"; }",
+            )],
+        );
+
+        let mut sources = SourceMap::default();
+        let module = load_interface("geo", &directory.join("geo.toyi"), &mut sources).unwrap();
+        assert_eq!(module.id.value, "geo");
+
+        let shapes = match &module.statements[1] {
+            Statement::Module(shapes) => shapes,
+            statement => panic!("expected a module, found {:?}", statement),
+        };
+        match &shapes.statements[0] {
+            Statement::FunctionDeclaration(dot) => {
+                assert!(matches!(
+                    &dot.parameters[0].1,
+                    Type::Pointer { pointee, .. } if **pointee == Type::Named("geo::Point".to_string().into())
+                ));
+                assert_eq!(
+                    dot.return_type,
+                    Type::Named("geo::shapes::Dot".to_string().into())
+                );
+                assert_eq!(dot.symbol, "root::shapes::dot");
+            }
+            statement => panic!("expected a function declaration, found {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn interface_traits_and_impls_are_named_under_the_extern() {
+        let directory = program(
+            "interface-impls",
+            &[(
+                "geo.toyi",
+                "pub trait Area { fn area(self): Self; }\n\
+                 impl Area for Rect { fn area(self): Rect = \"root::<Rect as Area>::area\"; }",
+            )],
+        );
+
+        let mut sources = SourceMap::default();
+        let module = load_interface("geo", &directory.join("geo.toyi"), &mut sources).unwrap();
+
+        match &module.statements[0] {
+            Statement::TraitDefinition(area) => {
+                assert_eq!(
+                    area.methods[0].return_type,
+                    Type::Named("Self".to_string().into())
+                );
+            }
+            statement => panic!("expected a trait, found {:?}", statement),
+        }
+        match &module.statements[1] {
+            Statement::Impl(impl_) => {
+                assert_eq!(impl_.trait_, Some("geo::Area".to_string().into()));
+                assert_eq!(impl_.type_, Type::Named("geo::Rect".to_string().into()));
+                assert_eq!(
+                    impl_.declarations[0].parameters[0].1,
+                    Type::Named("Self".to_string().into())
+                );
+                assert_eq!(
+                    impl_.declarations[0].return_type,
+                    Type::Named("geo::Rect".to_string().into())
+                );
+            }
+            statement => panic!("expected an impl, found {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn recursive_modules() {
+        let directory = program(
+            "cycle",
+            &[
+                ("main.toy", "mod a;"),
+                ("a.toy", "mod b;"),
+                ("b.toy", "mod a;"),
+            ],
+        );
+
+        let error = load(&directory).unwrap_err();
+
+        assert!(error.contains("b.toy:1:1: module a includes itself"));
+        let path = |name| directory.join(name).display().to_string();
+        assert!(error.ends_with(&format!(
+            "{} -> {} -> {}",
+            path("a.toy"),
+            path("b.toy"),
+            path("a.toy")
+        )));
+    }
+}
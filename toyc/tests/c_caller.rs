@@ -0,0 +1,32 @@
+//! Links the object file of a Toy program with a C program calling it.
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn c_calls_public_functions_of_the_root_module() {
+    let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+    let out = std::env::temp_dir().join(format!("toyc-c-caller-{}", std::process::id()));
+    std::fs::create_dir_all(&out).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_toyc"))
+        .arg(examples.join("argument.toy"))
+        .current_dir(&out)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = Command::new("cc")
+        .args(["-no-pie", "-o", "argument"])
+        .arg(examples.join("main.c"))
+        .arg("argument.toy.o")
+        .current_dir(&out)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = Command::new(out.join("argument")).status().unwrap();
+    assert_eq!(status.code(), Some(20));
+
+    std::fs::remove_dir_all(&out).unwrap();
+}